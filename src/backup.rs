@@ -0,0 +1,105 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+
+const BACKUP_TAG: &str = ".bak-";
+
+/// Copy `path` to a timestamped sibling `{file}.bak-<timestamp>`.
+pub fn snapshot(path: &Path, now: DateTime<Utc>) -> io::Result<PathBuf> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(BACKUP_TAG);
+    // Fixed-width so the latest backup also sorts last
+    name.push(now.format("%Y%m%dT%H%M%S%.6fZ").to_string());
+    let backup = path.with_file_name(name);
+    fs::copy(path, &backup)?;
+    Ok(backup)
+}
+
+pub fn latest(path: &Path) -> io::Result<Option<PathBuf>> {
+    let prefix = match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) => format!("{}{}", name, BACKUP_TAG),
+        None => return Ok(None),
+    };
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name();
+        match name.to_str() {
+            Some(name) if name.starts_with(&prefix) => backups.push(name.to_string()),
+            _ => (),
+        }
+    }
+    Ok(backups
+        .into_iter()
+        .max()
+        .map(|name| path.with_file_name(name)))
+}
+
+/// Overwrite `path` with its most recent backup, returning which backup was used.
+pub fn restore(path: &Path) -> io::Result<Option<PathBuf>> {
+    match latest(path)? {
+        Some(backup) => {
+            fs::copy(&backup, path)?;
+            Ok(Some(backup))
+        }
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::scratch::ScratchDir;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_restore_after_rewrite() {
+        let dir = ScratchDir::new("backup-restore");
+        let path = dir.join("Witchy.feed");
+        fs::write(
+            &path,
+            "<http://example.com/1>\nread 2017-07-17T03:21:21+00:00\n",
+        )
+        .unwrap();
+
+        let backup = snapshot(&path, Utc.ymd(2018, 1, 2).and_hms(3, 4, 5)).unwrap();
+        assert!(backup
+            .to_string_lossy()
+            .ends_with("Witchy.feed.bak-20180102T030405.000000Z"));
+
+        // A destructive rewrite
+        fs::write(&path, "").unwrap();
+
+        assert_eq!(restore(&path).unwrap(), Some(backup));
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "<http://example.com/1>\nread 2017-07-17T03:21:21+00:00\n",
+        );
+    }
+
+    #[test]
+    fn test_latest_backup() {
+        let dir = ScratchDir::new("backup-latest");
+        let path = dir.join("Electrum.feed");
+        assert_eq!(latest(&path).unwrap(), None);
+        assert_eq!(restore(&path).unwrap(), None);
+
+        fs::write(&path, "old").unwrap();
+        snapshot(&path, Utc.ymd(2018, 1, 2).and_hms(3, 4, 5)).unwrap();
+        fs::write(&path, "new").unwrap();
+        let newer = snapshot(&path, Utc.ymd(2018, 11, 2).and_hms(3, 4, 5)).unwrap();
+        fs::write(
+            dir.join("Electrum Quest.feed.bak-20990101T000000.000000Z"),
+            "",
+        )
+        .unwrap();
+
+        assert_eq!(latest(&path).unwrap(), Some(newer));
+    }
+}
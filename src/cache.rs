@@ -0,0 +1,356 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+
+use crate::config::{stable_hash, write_atomically};
+use crate::error::Error;
+
+/// Where cached feeds are kept, relative to the data dir
+pub const CACHE_DIR: &str = "cache";
+
+/// The most feed bodies to keep before the least recently used are evicted
+pub const MAX_CACHE_BYTES: u64 = 50 * 1024 * 1024;
+
+const INDEX_FILE: &str = "index";
+
+#[derive(Clone, Debug, PartialEq)]
+struct CacheEntry {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    /// The `Content-Type` it was served with, since a 304 doesn't say again
+    content_type: Option<String>,
+    size: u64,
+    last_access: DateTime<Utc>,
+}
+
+/// Downloaded feed bodies and their ETags, Last-Modified dates, and Content-Types, so unchanged
+/// feeds can be skipped with a conditional GET. Bodies are written to `temp_path`
+/// as they're downloaded and then `insert`ed. The index is only written by `save`.
+#[derive(Debug)]
+pub struct Cache {
+    dir: PathBuf,
+    max_bytes: u64,
+    entries: HashMap<String, CacheEntry>,
+}
+
+impl Cache {
+    pub fn open(dir: PathBuf, max_bytes: u64) -> Result<Self, Error> {
+        fs::create_dir_all(&dir).map_err(|err| {
+            Error::Msg(format!(
+                "Error creating cache directory {}: {}",
+                dir.display(),
+                err
+            ))
+        })?;
+        let mut entries = HashMap::new();
+        let index = fs::read_to_string(dir.join(INDEX_FILE)).unwrap_or_default();
+        for line in index.lines() {
+            // url, etag, size, last access, then last modified and content type,
+            // which older indexes don't have. Missing headers are left empty.
+            let fields: Vec<_> = line.split('\t').collect();
+            let (url, etag, size, last_access, last_modified, content_type) = match fields[..] {
+                [url, etag, size, last_access] => (url, etag, size, last_access, "", ""),
+                [url, etag, size, last_access, last_modified] => {
+                    (url, etag, size, last_access, last_modified, "")
+                }
+                [url, etag, size, last_access, last_modified, content_type] => {
+                    (url, etag, size, last_access, last_modified, content_type)
+                }
+                _ => {
+                    debug!("Skipping invalid cache index line {:?}", line);
+                    continue;
+                }
+            };
+            let present = |text: &str| Some(text.to_string()).filter(|text| !text.is_empty());
+            let entry = match (size.parse(), last_access.parse()) {
+                (Ok(size), Ok(last_access)) => Some((
+                    url,
+                    CacheEntry {
+                        etag: present(etag),
+                        last_modified: present(last_modified),
+                        content_type: present(content_type),
+                        size,
+                        last_access,
+                    },
+                )),
+                _ => None,
+            };
+            match entry {
+                Some((url, entry)) => {
+                    entries.insert(url.into(), entry);
+                }
+                None => debug!("Skipping invalid cache index line {:?}", line),
+            }
+        }
+        Ok(Cache {
+            dir,
+            max_bytes,
+            entries,
+        })
+    }
+
+    fn body_path(&self, url: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}", stable_hash(url)))
+    }
+
+    pub fn etag(&self, url: &str) -> Option<&str> {
+        self.entries.get(url)?.etag.as_ref().map(|etag| &etag[..])
+    }
+
+    pub fn last_modified(&self, url: &str) -> Option<&str> {
+        self.entries
+            .get(url)?
+            .last_modified
+            .as_ref()
+            .map(|date| &date[..])
+    }
+
+    pub fn content_type(&self, url: &str) -> Option<&str> {
+        self.entries
+            .get(url)?
+            .content_type
+            .as_ref()
+            .map(|content_type| &content_type[..])
+    }
+
+    /// Where to write a new body for `url` before it's `insert`ed
+    pub fn temp_path(&self, url: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}.tmp", stable_hash(url)))
+    }
+
+    /// The cached body for `url`, which counts as using it at `now`
+    pub fn get(&mut self, url: &str, now: DateTime<Utc>) -> Option<File> {
+        let body = match self.entries.get(url) {
+            Some(_) => File::open(self.body_path(url)).ok(),
+            None => return None,
+        };
+        match body {
+            Some(body) => {
+                if let Some(entry) = self.entries.get_mut(url) {
+                    entry.last_access = now;
+                }
+                Some(body)
+            }
+            None => {
+                debug!("Cached body for <{}> is missing", url);
+                self.entries.remove(url);
+                None
+            }
+        }
+    }
+
+    /// Add the body that was written to `temp_path(url)`
+    pub fn insert(
+        &mut self,
+        url: &str,
+        etag: Option<&str>,
+        last_modified: Option<&str>,
+        content_type: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let temp = self.temp_path(url);
+        let size = fs::metadata(&temp)?.len();
+        if size > self.max_bytes {
+            let _ = fs::remove_file(&temp);
+            self.remove(url);
+            return Ok(());
+        }
+        fs::rename(&temp, self.body_path(url))?;
+        self.entries.insert(
+            url.into(),
+            CacheEntry {
+                etag: etag.map(From::from),
+                last_modified: last_modified.map(From::from),
+                content_type: content_type.map(From::from),
+                size,
+                last_access: now,
+            },
+        );
+        self.evict();
+        Ok(())
+    }
+
+    fn remove(&mut self, url: &str) {
+        if self.entries.remove(url).is_some() {
+            let _ = fs::remove_file(self.body_path(url));
+        }
+    }
+
+    /// Drop the least recently used entries until the cache fits in `max_bytes`
+    fn evict(&mut self) {
+        let mut total: u64 = self.entries.values().map(|entry| entry.size).sum();
+        while total > self.max_bytes {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_access)
+                .map(|(url, entry)| (url.clone(), entry.size));
+            let (url, size) = match oldest {
+                Some(oldest) => oldest,
+                None => break,
+            };
+            debug!("Evicting <{}> from the cache", url);
+            self.remove(&url);
+            total -= size;
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let mut urls: Vec<_> = self.entries.keys().collect();
+        urls.sort();
+        write_atomically(&self.dir.join(INDEX_FILE), |file| {
+            for url in urls {
+                let entry = &self.entries[url];
+                writeln!(
+                    file,
+                    "{}\t{}\t{}\t{}\t{}\t{}",
+                    url,
+                    entry.etag.as_ref().map_or("", |etag| &etag[..]),
+                    entry.size,
+                    entry.last_access.to_rfc3339(),
+                    entry.last_modified.as_ref().map_or("", |date| &date[..]),
+                    entry
+                        .content_type
+                        .as_ref()
+                        .map_or("", |content_type| &content_type[..]),
+                )?;
+            }
+            Ok(())
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::scratch::ScratchDir;
+    use chrono::TimeZone;
+    use std::io::Read;
+
+    fn insert(
+        cache: &mut Cache,
+        url: &str,
+        etag: Option<&str>,
+        last_modified: Option<&str>,
+        body: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        fs::write(cache.temp_path(url), body)?;
+        cache.insert(url, etag, last_modified, None, now)
+    }
+
+    fn read(body: Option<File>) -> Option<String> {
+        let mut text = String::new();
+        body?.read_to_string(&mut text).unwrap();
+        Some(text)
+    }
+
+    #[test]
+    fn test_insert_and_hit() {
+        let dir = ScratchDir::new("cache-hit");
+        let now = Utc.ymd(2018, 1, 2).and_hms(3, 4, 5);
+        let mut cache = Cache::open(dir.to_path_buf(), MAX_CACHE_BYTES).unwrap();
+        assert_eq!(cache.etag("http://example.com/feed"), None);
+        assert!(cache.get("http://example.com/feed", now).is_none());
+
+        insert(
+            &mut cache,
+            "http://example.com/feed",
+            Some("\"abc\""),
+            None,
+            "<rss/>",
+            now,
+        )
+        .unwrap();
+        insert(
+            &mut cache,
+            "http://example.com/dated",
+            None,
+            Some("Sat, 01 Jan 2000 00:00:00 GMT"),
+            "<feed/>",
+            now,
+        )
+        .unwrap();
+        fs::write(cache.temp_path("http://example.com/json"), "{}").unwrap();
+        cache
+            .insert(
+                "http://example.com/json",
+                Some("\"def\""),
+                None,
+                Some("application/feed+json"),
+                now,
+            )
+            .unwrap();
+        assert_eq!(cache.etag("http://example.com/feed"), Some("\"abc\""));
+        assert_eq!(
+            read(cache.get("http://example.com/feed", now)),
+            Some("<rss/>".into())
+        );
+        cache.save().unwrap();
+
+        let mut reopened = Cache::open(dir.to_path_buf(), MAX_CACHE_BYTES).unwrap();
+        assert_eq!(reopened.etag("http://example.com/feed"), Some("\"abc\""));
+        assert_eq!(
+            read(reopened.get("http://example.com/feed", now)),
+            Some("<rss/>".into())
+        );
+        assert_eq!(reopened.etag("http://example.com/dated"), None);
+        assert_eq!(
+            reopened.last_modified("http://example.com/dated"),
+            Some("Sat, 01 Jan 2000 00:00:00 GMT")
+        );
+        assert_eq!(reopened.content_type("http://example.com/feed"), None);
+        assert_eq!(
+            reopened.content_type("http://example.com/json"),
+            Some("application/feed+json")
+        );
+        assert_eq!(reopened.entries, cache.entries);
+    }
+
+    #[test]
+    fn test_old_index() {
+        let dir = ScratchDir::new("cache-old-index");
+        fs::write(
+            dir.join(INDEX_FILE),
+            "http://example.com/feed\t\"abc\"\t6\t2018-01-02T03:04:05+00:00\n",
+        )
+        .unwrap();
+        let cache = Cache::open(dir.to_path_buf(), MAX_CACHE_BYTES).unwrap();
+        assert_eq!(cache.etag("http://example.com/feed"), Some("\"abc\""));
+        assert_eq!(cache.last_modified("http://example.com/feed"), None);
+    }
+
+    #[test]
+    fn test_evict_least_recently_used() {
+        let dir = ScratchDir::new("cache-evict");
+        let day = |day| Utc.ymd(2018, 1, day).and_hms(0, 0, 0);
+        let mut cache = Cache::open(dir.to_path_buf(), 10).unwrap();
+        insert(&mut cache, "http://a.com/", Some("a"), None, "aaaa", day(1)).unwrap();
+        insert(&mut cache, "http://b.com/", Some("b"), None, "bbbb", day(2)).unwrap();
+        assert!(cache.get("http://a.com/", day(3)).is_some());
+
+        // b was used longest ago
+        insert(&mut cache, "http://c.com/", Some("c"), None, "cccc", day(4)).unwrap();
+        assert_eq!(cache.etag("http://b.com/"), None);
+        assert!(!cache.body_path("http://b.com/").exists());
+        assert_eq!(cache.etag("http://a.com/"), Some("a"));
+        assert_eq!(cache.etag("http://c.com/"), Some("c"));
+
+        // Too big to ever fit
+        insert(
+            &mut cache,
+            "http://d.com/",
+            Some("d"),
+            None,
+            "dddddddddddd",
+            day(5),
+        )
+        .unwrap();
+        assert_eq!(cache.etag("http://d.com/"), None);
+        assert_eq!(cache.etag("http://a.com/"), Some("a"));
+    }
+}
@@ -1,12 +1,25 @@
+use std::collections::HashMap;
 use std::env;
-use std::fs::{File, OpenOptions};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Child, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
 
+use chrono::{DateTime, FixedOffset, Local, Utc};
+use clap::ArgMatches;
+use log::LevelFilter;
+use regex::Regex;
+
+use crate::backup;
+use crate::cache::{self, Cache};
 use crate::error::{Error, ParseError};
-use crate::feed::FeedInfo;
+use crate::feed::{Feed, FeedFormat, FeedInfo, OpenMode};
+use crate::fetch;
 use crate::parser;
 use crate::platform;
+use crate::state;
 
 #[derive(Debug, Clone)]
 enum PathWrapper {
@@ -14,39 +27,176 @@ enum PathWrapper {
     ErrorIfMissing(PathBuf),
 }
 
+/// Extra request headers for each feed, kept out of the main config so that
+/// it can be shared without leaking API keys.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Secrets {
+    headers: HashMap<String, Vec<(String, String)>>,
+}
+
+impl Secrets {
+    pub fn parse(text: &str, path: &Path) -> Result<Self, Error> {
+        let mut secrets = Secrets::default();
+        let entries = parser::parse_secrets(text).map_err(|err| match err {
+            ParseError::Expected { msg, row, .. } => Error::Msg(format!(
+                "Line {}: Error parsing {}\n\nExpected {}",
+                row,
+                path.display(),
+                msg,
+            )),
+        })?;
+        for (name, header, value) in entries {
+            secrets
+                .headers
+                .entry(name.into())
+                .or_insert_with(Vec::new)
+                .push((header.into(), value.into()));
+        }
+        Ok(secrets)
+    }
+
+    /// The headers to send for a feed. Feeds marked `@ secrets` must have some.
+    pub fn headers_for(&self, info: &FeedInfo) -> Result<&[(String, String)], Error> {
+        match self.headers.get(&info.name) {
+            Some(headers) => Ok(headers),
+            None if info.needs_secrets() => Err(Error::Msg(format!(
+                "{} (Needs @ secrets, but has no headers in the secrets file)",
+                info.name,
+            ))),
+            None => Ok(&[]),
+        }
+    }
+}
+
+/// Which ready feeds `--mark-read` catches up on instead of opening
+#[derive(Clone, Debug, PartialEq)]
+pub enum MarkRead {
+    All,
+    Feed(String),
+}
+
 #[derive(Clone)]
 pub struct Args {
     only_fetch: bool,
-    feed_root: Option<PathBuf>,
+    backup: bool,
+    feed_format: Option<FeedFormat>,
+    shell: bool,
+    feed_roots: Vec<PathBuf>,
+    default_feed_root: Option<PathBuf>,
+    data_dir: Option<PathBuf>,
     config: PathWrapper,
     open_command: Option<Vec<String>>,
+    feed_commands: Vec<(String, Vec<String>)>,
+    only: Option<String>,
+    open_mode: OpenMode,
+    max_tabs: Option<usize>,
+    mark_read: Option<MarkRead>,
+    queue_file: Option<PathBuf>,
+    open_stdin: bool,
+    open_delay: Duration,
+    https_upgrade: bool,
+    secrets: Secrets,
+    timezone: Option<FixedOffset>,
+    jobs: usize,
+    timeout: Option<Duration>,
+    attempts: usize,
+    user_agent: String,
 }
 
 impl Args {
-    pub fn new(
-        only_fetch: bool,
-        feed_root: Option<&str>,
-        config: Option<&str>,
-        command: Option<&str>,
-    ) -> Result<Self, Error> {
-        let command = if let Some(command) = command {
-            match parser::parse_command(command) {
-                Ok(command) => Some(command),
-                Err(ParseError::Expected { msg, .. }) => {
-                    let msg = format!("Error parsing command: expected {}", msg);
-                    return Err(Error::Msg(msg));
-                }
-            }
-        } else {
-            None
+    pub fn new(matches: &ArgMatches<'_>) -> Result<Self, Error> {
+        let command = match matches.value_of("open-with") {
+            Some(command) => Some(parse_open_command(command)?),
+            None => None,
         };
+        let mut feed_commands = Vec::new();
+        if let Some(values) = matches.values_of("open-with-for") {
+            let values: Vec<_> = values.collect();
+            for pair in values.chunks(2) {
+                feed_commands.push((pair[0].to_string(), parse_open_command(pair[1])?));
+            }
+        }
 
-        Ok(Args {
-            only_fetch,
-            feed_root: feed_root.map(From::from),
-            config: config_path(config)?,
+        let mut args = Args {
+            only_fetch: matches.is_present("fetch"),
+            backup: !matches.is_present("no-backup"),
+            feed_format: match matches.value_of("feed-format") {
+                Some("json") => Some(FeedFormat::Json),
+                Some(_) => Some(FeedFormat::Lines),
+                None => None,
+            },
+            shell: matches.is_present("shell"),
+            feed_roots: matches
+                .values_of("feeds")
+                .map(|roots| roots.map(From::from).collect())
+                .unwrap_or_default(),
+            default_feed_root: matches.value_of("default-feeds").map(From::from),
+            data_dir: matches.value_of("data-dir").map(From::from),
+            config: config_path(matches.value_of("config"), matches.value_of("config-dir"))?,
             open_command: command,
-        })
+            feed_commands,
+            only: matches.value_of("only").map(From::from),
+            open_mode: if matches.is_present("open-first-only") {
+                OpenMode::FirstOnly
+            } else if matches.is_present("open-all") {
+                OpenMode::All
+            } else {
+                OpenMode::Policy
+            },
+            max_tabs: match matches.value_of("max-tabs") {
+                Some(max_tabs) => Some(parse_max_tabs(max_tabs)?),
+                None => None,
+            },
+            mark_read: if matches.is_present("mark-read") {
+                Some(match matches.value_of("mark-read") {
+                    Some(name) => MarkRead::Feed(name.into()),
+                    None => MarkRead::All,
+                })
+            } else {
+                None
+            },
+            queue_file: matches.value_of("queue-file").map(From::from),
+            open_stdin: matches.is_present("open-stdin"),
+            open_delay: match matches.value_of("open-delay") {
+                Some(delay) => parse_open_delay(delay)?,
+                None => Duration::from_millis(0),
+            },
+            https_upgrade: matches.is_present("https-upgrade"),
+            secrets: Secrets::default(),
+            timezone: match matches.value_of("tz") {
+                Some(tz) => Some(parse_timezone(tz)?),
+                None => None,
+            },
+            jobs: match matches
+                .value_of("jobs")
+                .map(String::from)
+                .or_else(|| env::var("FEEDBURST_JOBS").ok())
+            {
+                Some(jobs) => parse_jobs(&jobs)?,
+                None => DEFAULT_JOBS,
+            },
+            timeout: match matches.value_of("timeout") {
+                Some(timeout) => parse_timeout(timeout)?,
+                None => Some(fetch::DEFAULT_TIMEOUT),
+            },
+            attempts: match matches.value_of("attempts") {
+                Some(attempts) => parse_attempts(attempts)?,
+                None => fetch::DEFAULT_ATTEMPTS,
+            },
+            user_agent: match matches
+                .value_of("user-agent")
+                .map(String::from)
+                .or_else(|| env::var("FEEDBURST_USER_AGENT").ok())
+            {
+                Some(user_agent) => {
+                    fetch::user_agent_header(&user_agent)?;
+                    user_agent
+                }
+                None => fetch::DEFAULT_USER_AGENT.into(),
+            },
+        };
+        args.secrets = args.read_secrets()?;
+        Ok(args)
     }
 
     pub fn config_path(&self) -> &PathBuf {
@@ -71,9 +221,250 @@ impl Args {
         }
     }
 
+    /// Like `config_file`, but also says whether the file was just created, which
+    /// means this is the first run
+    pub fn open_config(&self) -> Result<(File, bool), Error> {
+        let created = match self.config {
+            PathWrapper::CreateIfMissing(ref path) => !path.exists(),
+            PathWrapper::ErrorIfMissing(_) => false,
+        };
+        Ok((self.config_file()?, created))
+    }
+
+    /// Like `config_file`, but it's an error if there isn't one instead of creating it
+    pub fn existing_config_file(&self) -> Result<File, Error> {
+        let path = self.config_path();
+        File::open(path)
+            .map_err(|err| Error::Msg(format!("Cannot open file {}: {}", path.display(), err)))
+    }
+
+    /// The secrets file lives next to the config file
+    pub fn secrets_path(&self) -> PathBuf {
+        self.config_path().with_file_name("secrets")
+    }
+
+    fn read_secrets(&self) -> Result<Secrets, Error> {
+        let path = self.secrets_path();
+        match fs::read_to_string(&path) {
+            Ok(text) => Secrets::parse(&text, &path),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(Secrets::default()),
+            Err(err) => Err(Error::Msg(format!(
+                "Cannot open file {}: {}",
+                path.display(),
+                err
+            ))),
+        }
+    }
+
+    pub fn open_mode(&self) -> OpenMode {
+        self.open_mode
+    }
+
+    /// The most comics to open across every feed in one run, from `--max-tabs`
+    pub fn max_tabs(&self) -> Option<usize> {
+        self.max_tabs
+    }
+
+    pub fn mark_read(&self) -> Option<&MarkRead> {
+        self.mark_read.as_ref()
+    }
+
+    /// The one feed to read, from `--only`
+    pub fn only(&self) -> Option<&str> {
+        self.only.as_ref().map(|name| &name[..])
+    }
+
+    /// The feeds named by `--open-with-for`
+    pub fn scoped_feeds(&self) -> impl Iterator<Item = &str> {
+        self.feed_commands.iter().map(|(name, _)| &name[..])
+    }
+
+    /// Mark all of a feed's comics read without opening any, for `--mark-read`,
+    /// returning how many were unread
+    pub fn mark_feed_read(&self, feed: &mut Feed) -> Result<usize, Error> {
+        let unread = feed.unread_count();
+        feed.read();
+        self.save_feed(feed)?;
+        Ok(unread)
+    }
+
+    /// Open a feed's comics, then mark them read. Opening comes first so a failed
+    /// open leaves them unread for next time, which means a failure to save the
+    /// read afterwards would show them again; the error says so. At most `tabs`
+    /// comics are opened, for `--max-tabs`, and the rest are left unread.
+    pub fn open_and_read<C: Clock>(
+        &self,
+        feed: &mut Feed,
+        pacer: &mut OpenPacer<C>,
+        tabs: usize,
+    ) -> Result<(), Error> {
+        let now = Utc::now();
+        let mut urls = self.open_list(feed, now);
+        if urls.len() > tabs {
+            urls.truncate(tabs);
+            self.open_urls(&feed.info, &urls, pacer)?;
+            feed.read_opened(&urls);
+        } else {
+            self.open_urls(&feed.info, &urls, pacer)?;
+            match self.timezone {
+                Some(tz) => feed.read_list(now.with_timezone(&tz)),
+                None => feed.read_list(now.with_timezone(&Local)),
+            }
+        }
+        self.save_feed(feed).map_err(|err| {
+            Error::Msg(format!(
+                "{}\nThe comics were opened but couldn't be marked read, \
+                 so they'll be shown again next time",
+                err
+            ))
+        })
+    }
+
+    /// Write a feed's new events to its file, which is replaced all at once so
+    /// it's never left with half an event
+    pub fn save_feed(&self, feed: &mut Feed) -> Result<(), Error> {
+        let path = self.feed_path(&feed.info)?;
+        feed.persist(&path, self.feed_format).map_err(|err| {
+            Error::Msg(format!(
+                "Error writing feed file {}: {}",
+                path.display(),
+                err
+            ))
+        })
+    }
+
+    /// Whether to try `http://` feeds over HTTPS first, from `--https-upgrade`
+    pub fn https_upgrade(&self) -> bool {
+        self.https_upgrade
+    }
+
+    /// Keeps successive opens at least `--open-delay` apart
+    pub fn open_pacer(&self) -> OpenPacer {
+        OpenPacer::new(self.open_delay, SystemClock)
+    }
+
+    /// How many feeds to download at once
+    pub fn jobs(&self) -> usize {
+        self.jobs
+    }
+
+    /// How long to wait for each feed to download, or `None` to wait as long as it takes
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// How many times to try downloading each feed, from `--attempts`
+    pub fn attempts(&self) -> usize {
+        self.attempts
+    }
+
+    /// The `User-Agent` to download feeds with
+    pub fn user_agent(&self) -> &str {
+        &self.user_agent
+    }
+
+    /// The timezone from `--tz`, or `None` for the system's local time
+    pub fn timezone(&self) -> Option<FixedOffset> {
+        self.timezone
+    }
+
+    /// The URLs to open for a feed at `now`, with days counted in the `--tz`
+    pub fn open_list(&self, feed: &Feed, now: DateTime<Utc>) -> Vec<String> {
+        match self.timezone {
+            Some(tz) => feed.open_list_at(self.open_mode(), now.with_timezone(&tz)),
+            None => feed.open_list_at(self.open_mode(), now.with_timezone(&Local)),
+        }
+    }
+
+    pub fn secrets(&self) -> &Secrets {
+        &self.secrets
+    }
+
+    /// `--feeds` overrides every feed's `root`, while `--default-feeds` is only
+    /// used for feeds that don't have a `root`.
+    pub fn feed_path(&self, info: &FeedInfo) -> Result<PathBuf, Error> {
+        if !self.feed_roots.is_empty() {
+            return find_in_roots(&self.feed_roots, &feed_file_name(&info.name));
+        }
+        let root = info.root.as_ref().or(self.default_feed_root.as_ref());
+        feed_path(root, self.data_dir.as_ref(), &info.name)
+    }
+
+    /// Where the feed called `name` is stored, for `--print-feed-path`. Unlike
+    /// `feed_file`, the file isn't created if it's missing.
+    pub fn feed_path_named(&self, feeds: &[FeedInfo], name: &str) -> Result<PathBuf, Error> {
+        let info = feeds
+            .iter()
+            .find(|info| info.name == name)
+            .ok_or_else(|| Error::Msg(format!("No feed named \"{}\" in your config", name)))?;
+        self.feed_path(info)
+    }
+
+    /// Check that no feed would be stored in the config file, a file it
+    /// `include`s, or another feed's file, which a `root` next to the config or
+    /// a name containing `/` could cause. Feeds whose path can't be found are
+    /// left to fail when they're read.
+    pub fn check_feed_paths(&self, feeds: &[FeedInfo], included: &[PathBuf]) -> Result<(), Error> {
+        let config = resolve_path(self.config_path());
+        let included: Vec<_> = included.iter().map(|path| resolve_path(path)).collect();
+        let mut seen: HashMap<PathBuf, &str> = HashMap::new();
+        for info in feeds {
+            let path = match self.feed_path(info) {
+                Ok(path) => path,
+                Err(_) => continue,
+            };
+            let resolved = resolve_path(&path);
+            if resolved == config {
+                return Err(Error::Msg(format!(
+                    "Feed \"{}\" would be stored in {}, which is your config file",
+                    info.name,
+                    path.display()
+                )));
+            }
+            if included.contains(&resolved) {
+                return Err(Error::Msg(format!(
+                    "Feed \"{}\" would be stored in {}, which your config file includes",
+                    info.name,
+                    path.display()
+                )));
+            }
+            if let Some(other) = seen.insert(resolved, &info.name) {
+                return Err(Error::Msg(format!(
+                    "Feeds \"{}\" and \"{}\" would both be stored in {}",
+                    other,
+                    info.name,
+                    path.display()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that a feed's file, or the directory it'll be created in, can be
+    /// written to, for `validate`
+    pub fn check_feed_dir(&self, info: &FeedInfo) -> Result<(), Error> {
+        let path = self.feed_path(info)?;
+        let target = if path.exists() {
+            path.as_path()
+        } else {
+            path.parent().unwrap_or_else(|| Path::new("."))
+        };
+        match fs::metadata(target) {
+            Ok(ref metadata) if metadata.permissions().readonly() => Err(Error::Msg(format!(
+                "Error: {} can't be written to",
+                target.display()
+            ))),
+            Ok(_) => Ok(()),
+            Err(err) => Err(Error::Msg(format!(
+                "Error: cannot open {}: {}",
+                target.display(),
+                err
+            ))),
+        }
+    }
+
     pub fn feed_file(&self, info: &FeedInfo) -> Result<File, Error> {
-        let root = self.feed_root.as_ref().or_else(|| info.root.as_ref());
-        let path = feed_path(root, &info.name)?;
+        let path = self.feed_path(info)?;
         OpenOptions::new()
             .read(true)
             .write(true)
@@ -88,33 +479,193 @@ impl Args {
             })
     }
 
-    pub fn open_url(&self, feed: &FeedInfo, url: &str) -> Result<(), Error> {
-        if let Some(command) = self.open_command.as_ref().or_else(|| feed.command.as_ref()) {
-            let mut found_url = false;
-            let command_str = command.join(" ");
-            let mut command: Vec<String> = (*command).clone();
-            for (i, item) in command.iter_mut().enumerate() {
-                if item.to_uppercase() == "@URL" {
-                    if i == 0 {
-                        let msg = format!(
-                            "@URL can't be the first part of the command (in `{}`)",
-                            command_str
-                        );
-                        return Err(Error::Msg(msg));
-                    }
-                    *item = url.into();
-                    found_url = true;
-                }
-            }
+    /// A file of feedburst's own state, kept alongside the feeds
+    pub fn state_path(&self, name: &str) -> Result<PathBuf, Error> {
+        if !self.feed_roots.is_empty() {
+            return find_in_roots(&self.feed_roots, name);
+        }
+        match self.default_feed_root.as_ref() {
+            Some(root) => Ok(root.join(name)),
+            None => data_path(self.data_dir.as_ref(), name),
+        }
+    }
+
+    pub fn open_cache(&self) -> Result<Cache, Error> {
+        let dir = self.state_path(cache::CACHE_DIR)?;
+        Cache::open(dir, cache::MAX_CACHE_BYTES)
+    }
+
+    pub fn read_last_run(&self) -> Result<Option<DateTime<Utc>>, Error> {
+        let path = self.state_path(state::LAST_RUN_FILE)?;
+        match File::open(&path) {
+            Ok(mut file) => state::read_last_run(&mut file),
+            Err(_) => Ok(None),
+        }
+    }
+
+    pub fn record_last_run(&self, when: DateTime<Utc>) -> Result<(), Error> {
+        let path = self.state_path(state::LAST_RUN_FILE)?;
+        let mut file = File::create(&path)
+            .map_err(|err| Error::Msg(format!("Cannot open file {}: {}", path.display(), err)))?;
+        Ok(state::write_last_run(&mut file, when)?)
+    }
+
+    /// Snapshot a feed's file before rewriting it, unless backups are disabled.
+    pub fn backup_feed(&self, info: &FeedInfo) -> Result<(), Error> {
+        let path = self.feed_path(info)?;
+        if !self.backup || !path.exists() {
+            return Ok(());
+        }
+        let backup = backup::snapshot(&path, Utc::now()).map_err(|err| {
+            Error::Msg(format!(
+                "Error backing up feed file {}: {}",
+                path.display(),
+                err
+            ))
+        })?;
+        debug!("Backed up \"{}\" to {:?}", info.name, backup);
+        Ok(())
+    }
+
+    /// Rewrite a feed's whole file in the `--feed-format`, or the one it's
+    /// already in, after backing it up
+    pub fn rewrite_feed(&self, feed: &Feed) -> Result<(), Error> {
+        let path = self.feed_path(&feed.info)?;
+        self.backup_feed(&feed.info)?;
+        let format = match self.feed_format {
+            Some(format) => format,
+            None => fs::read(&path)
+                .map(|contents| FeedFormat::of(&contents))
+                .unwrap_or(FeedFormat::Lines),
+        };
+        write_atomically(&path, |file| feed.write_format(file, format)).map_err(|err| {
+            Error::Msg(format!(
+                "Error rewriting feed file {}: {}",
+                path.display(),
+                err
+            ))
+        })
+    }
+
+    /// Point a feed at its new URL in the config file, or the included file
+    /// it's in, for `@ follow-redirects`
+    pub fn update_feed_url(&self, info: &FeedInfo, url: &str) -> Result<(), Error> {
+        let path = info.source.as_ref().unwrap_or_else(|| self.config_path());
+        let text = fs::read_to_string(path)?;
+        let updated = parser::replace_feed_url(&text, &info.name, url).ok_or_else(|| {
+            Error::Msg(format!(
+                "No feed named \"{}\" in {}",
+                info.name,
+                path.display()
+            ))
+        })?;
+        write_atomically(path, |file| file.write_all(updated.as_bytes()))
+            .map_err(|err| Error::Msg(format!("Error updating {}: {}", path.display(), err)))
+    }
+
+    pub fn restore_feed(&self, info: &FeedInfo) -> Result<PathBuf, Error> {
+        let path = self.feed_path(info)?;
+        let restored = backup::restore(&path).map_err(|err| {
+            Error::Msg(format!(
+                "Error restoring feed file {}: {}",
+                path.display(),
+                err
+            ))
+        })?;
+        restored.ok_or_else(|| Error::Msg(format!("No backups found for feed \"{}\"", info.name)))
+    }
+
+    /// `--open-with-for` this feed, then `--open-with`, then the feed's `command` or
+    /// the config's `open-with`, which are resolved by the parser. `None` means the
+    /// platform's opener.
+    fn open_command<'a>(&'a self, feed: &'a FeedInfo) -> Option<&'a Vec<String>> {
+        self.feed_commands
+            .iter()
+            .find(|(name, _)| *name == feed.name)
+            .map(|(_, command)| command)
+            .or(self.open_command.as_ref())
+            .or(feed.command.as_ref())
+    }
 
-            if !found_url {
-                command.push(url.into());
+    /// Like `open_url`, but returns the opener's process instead of waiting for it to finish
+    #[allow(unused)]
+    pub fn spawn_url(&self, feed: &FeedInfo, url: &str) -> Result<Child, Error> {
+        if let Some(command) = self.open_command(feed) {
+            let command = expand_command(command, url, self.shell)?;
+            Ok(platform::command(&command).spawn()?)
+        } else {
+            platform::spawn_url(url)
+        }
+    }
+
+    /// Open each of `urls`, or with `--open-stdin`, hand them all to one run of
+    /// the open command on its stdin, one per line
+    pub fn open_urls<C: Clock>(
+        &self,
+        feed: &FeedInfo,
+        urls: &[String],
+        pacer: &mut OpenPacer<C>,
+    ) -> Result<(), Error> {
+        if self.queue_file.is_some() {
+            for url in urls {
+                self.open_url(feed, url)?;
             }
+            return Ok(());
+        }
+        if !self.open_stdin {
+            for url in urls {
+                pacer.wait();
+                self.open_url(feed, url)?;
+            }
+            return Ok(());
+        }
+        let command = match self.open_command(feed) {
+            Some(command) => command,
+            None => {
+                return Err(Error::Msg(
+                    "--open-stdin needs an open command, from --open-with or the config".into(),
+                ))
+            }
+        };
+        let command_str = command.join(" ");
+        if command
+            .iter()
+            .any(|item| item.to_ascii_uppercase().contains("@URL"))
+        {
+            return Err(Error::Msg(format!(
+                "--open-stdin passes the URLs on stdin, so the open command can't use @URL or @URLENC (in `{}`)",
+                command_str
+            )));
+        }
+        let command = if self.shell {
+            let parts: Vec<_> = command.iter().map(|item| shell_part(item)).collect();
+            platform::shell_command(&parts.join(" "))
+        } else {
+            command.clone()
+        };
+        pacer.wait();
+        let mut child = platform::command(&command).stdin(Stdio::piped()).spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            for url in urls {
+                writeln!(stdin, "{}", url)?;
+            }
+        }
+        if child.wait()?.success() {
+            Ok(())
+        } else {
+            let msg = format!("Error running open command `{}`", command_str);
+            Err(Error::Msg(msg))
+        }
+    }
 
-            let exit_status = Command::new(&command[0])
-                .args(&command[1..])
-                .spawn()?
-                .wait()?;
+    pub fn open_url(&self, feed: &FeedInfo, url: &str) -> Result<(), Error> {
+        if let Some(ref path) = self.queue_file {
+            return queue_url(path, url);
+        }
+        if let Some(command) = self.open_command(feed) {
+            let command_str = command.join(" ");
+            let command = expand_command(command, url, self.shell)?;
+            let exit_status = platform::command(&command).spawn()?.wait()?;
 
             if exit_status.success() {
                 Ok(())
@@ -128,7 +679,411 @@ impl Args {
     }
 }
 
-fn feed_path(root: Option<&PathBuf>, name: &str) -> Result<PathBuf, Error> {
+/// Where `OpenPacer` gets the time from, so tests can control it
+pub trait Clock {
+    fn now(&self) -> Instant;
+    fn sleep(&mut self, duration: Duration);
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        thread::sleep(duration)
+    }
+}
+
+/// Spaces out opens for launchers that drop URLs opened in quick succession.
+/// Time spent between feeds counts toward the delay.
+pub struct OpenPacer<C = SystemClock> {
+    delay: Duration,
+    last_open: Option<Instant>,
+    clock: C,
+}
+
+impl<C: Clock> OpenPacer<C> {
+    pub fn new(delay: Duration, clock: C) -> Self {
+        OpenPacer {
+            delay,
+            last_open: None,
+            clock,
+        }
+    }
+
+    /// Sleep until `delay` has passed since the last open, and count this as an open
+    pub fn wait(&mut self) {
+        if let Some(last_open) = self.last_open {
+            let elapsed = self.clock.now().saturating_duration_since(last_open);
+            if elapsed < self.delay {
+                self.clock.sleep(self.delay - elapsed);
+            }
+        }
+        self.last_open = Some(self.clock.now());
+    }
+}
+
+/// Append a URL to the `--queue-file`, for a reader that's watching it. A FIFO
+/// blocks here until something reads it.
+fn queue_url(path: &Path, url: &str) -> Result<(), Error> {
+    let mut file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .map_err(|err| Error::Msg(format!("Cannot open file {}: {}", path.display(), err)))?;
+    writeln!(file, "{}", url)
+        .map_err(|err| Error::Msg(format!("Error writing to {}: {}", path.display(), err)))
+}
+
+/// The most detailed log level to show with `verbosity` `-v` flags
+pub fn log_level(verbosity: u64) -> LevelFilter {
+    match verbosity {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// What `--check-command` substitutes into the command to show what would run
+const EXAMPLE_URL: &str = "https://example.com/comic?page=1";
+
+fn parse_open_command(text: &str) -> Result<Vec<String>, Error> {
+    parser::parse_command(text).map_err(|ParseError::Expected { msg, .. }| {
+        Error::Msg(format!("Error parsing command: expected {}", msg))
+    })
+}
+
+/// Check an open command without running it, for `--check-command`, describing
+/// the parts it was split into and what it would run.
+pub fn check_command(text: &str, shell: bool) -> Result<String, Error> {
+    let command = parse_open_command(text)?;
+    if command.is_empty() {
+        return Err(Error::Msg("Error parsing command: it's empty".into()));
+    }
+
+    let placeholder = Regex::new(r"@([A-Z][A-Z_]*)").unwrap();
+    for item in &command {
+        let upper = item.to_ascii_uppercase();
+        if upper != "@URL" && upper.replace("@URLENC", "").contains("@URL") {
+            return Err(Error::Msg(format!(
+                "@URL must be an argument of its own (in `{}`), use @URLENC inside a larger one",
+                item
+            )));
+        }
+        for name in placeholder.captures_iter(item) {
+            if &name[1] != "URL" && &name[1] != "URLENC" {
+                return Err(Error::Msg(format!(
+                    "Unknown placeholder @{} (in `{}`), expected @URL or @URLENC",
+                    &name[1], item
+                )));
+            }
+        }
+    }
+
+    let expanded = expand_command(&command, EXAMPLE_URL, shell)?;
+    let mut out = format!("Parsed into {} parts:\n", command.len());
+    for item in &command {
+        out.push_str(&format!("    {:?}\n", item));
+    }
+    out.push_str(&format!("For <{}> it would run:\n", EXAMPLE_URL));
+    for item in &expanded {
+        out.push_str(&format!("    {:?}\n", item));
+    }
+    Ok(out)
+}
+
+/// A part of an open command as it goes in a `--shell` script. Parts that are
+/// empty or have spaces, which must have been quoted in the command, are quoted
+/// again so the shell keeps them whole, while ones like `|` are left to the shell.
+fn shell_part(item: &str) -> String {
+    if item.is_empty() || item.contains(char::is_whitespace) {
+        platform::shell_quote(item)
+    } else {
+        item.into()
+    }
+}
+
+/// Substitute `url` into an open command, producing the program and its arguments.
+/// `@URL` must be an argument of its own, while `@URLENC` is replaced with the
+/// percent-encoded URL anywhere in an argument.
+/// In shell mode the whole command is handed to the platform shell as one script.
+fn expand_command(command: &[String], url: &str, shell: bool) -> Result<Vec<String>, Error> {
+    let mut found_url = false;
+    let mut command = command.to_vec();
+    for (i, item) in command.iter_mut().enumerate() {
+        if item.to_ascii_uppercase().contains("@URLENC") {
+            if i == 0 && !shell {
+                let msg = format!(
+                    "@URLENC can't be in the first part of the command (in `{}`)",
+                    command.join(" ")
+                );
+                return Err(Error::Msg(msg));
+            }
+            let encoded = percent_encode(url);
+            while let Some(start) = item.to_ascii_uppercase().find("@URLENC") {
+                item.replace_range(start..start + "@URLENC".len(), &encoded);
+            }
+            found_url = true;
+        } else if item.to_uppercase() == "@URL" {
+            if i == 0 && !shell {
+                let msg = format!(
+                    "@URL can't be the first part of the command (in `{}`)",
+                    command.join(" ")
+                );
+                return Err(Error::Msg(msg));
+            }
+            *item = if shell {
+                platform::shell_quote(url)
+            } else {
+                url.into()
+            };
+            found_url = true;
+        } else if shell {
+            *item = shell_part(item);
+        }
+    }
+
+    if !found_url {
+        command.push(if shell {
+            platform::shell_quote(url)
+        } else {
+            url.into()
+        });
+    }
+
+    if shell {
+        Ok(platform::shell_command(&command.join(" ")))
+    } else {
+        Ok(command)
+    }
+}
+
+/// Replace the file at `path` with what `write` produces. The new contents go to a
+/// temporary file that's renamed into place, so the file is never half-written.
+pub fn write_atomically<F>(path: &Path, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut File) -> io::Result<()>,
+{
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp = path.with_file_name(temp_name);
+    let result = File::create(&temp).and_then(|mut file| {
+        write(&mut file)?;
+        file.sync_all()?;
+        fs::rename(&temp, path)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Pairs of feeds with the same URL, which is allowed but probably a mistake,
+/// as (later feed, earlier feed)
+pub fn shared_urls(feeds: &[FeedInfo]) -> Vec<(&str, &str)> {
+    let mut seen: HashMap<&str, &str> = HashMap::new();
+    let mut shared = Vec::new();
+    for info in feeds {
+        if let Some(other) = seen.insert(&info.url, &info.name) {
+            shared.push((&info.name[..], other));
+        }
+    }
+    shared
+}
+
+/// An example line for the config, shown on the first run
+const EXAMPLE_FEED: &str =
+    r#""Goodbye to Halos" <http://goodbyetohalos.com/feed/> @ 2 new comics @ on Monday"#;
+
+/// What's shown instead of "You're not following any comics" when the config was
+/// just created
+pub fn first_run_message(config: &Path) -> String {
+    format!(
+        "Welcome to feedburst! Your config file is at {}\n\
+         Follow a comic by adding a line with its name, its feed, and when to read it, like:\n\n    \
+         {}\n\n\
+         Then run feedburst again to fetch it.",
+        config.display(),
+        EXAMPLE_FEED
+    )
+}
+
+/// How many feeds are downloaded at once without `--jobs` or `FEEDBURST_JOBS`
+const DEFAULT_JOBS: usize = 4;
+
+fn parse_jobs(text: &str) -> Result<usize, Error> {
+    match text.trim().parse() {
+        Ok(jobs) if jobs >= 1 => Ok(jobs),
+        _ => Err(Error::Msg(format!(
+            "Invalid number of jobs {:?}, expected a whole number of at least 1",
+            text
+        ))),
+    }
+}
+
+/// The longest `--timeout` allowed, an hour
+const MAX_TIMEOUT_SECS: u64 = 60 * 60;
+
+fn parse_timeout(text: &str) -> Result<Option<Duration>, Error> {
+    match text.trim().parse() {
+        Ok(0) => Ok(None),
+        Ok(secs) if secs <= MAX_TIMEOUT_SECS => Ok(Some(Duration::from_secs(secs))),
+        _ => Err(Error::Msg(format!(
+            "Invalid timeout {:?}, expected a whole number of seconds up to {}, or 0 for none",
+            text, MAX_TIMEOUT_SECS
+        ))),
+    }
+}
+
+fn parse_attempts(text: &str) -> Result<usize, Error> {
+    match text.trim().parse() {
+        Ok(attempts) if attempts >= 1 => Ok(attempts),
+        _ => Err(Error::Msg(format!(
+            "Invalid number of attempts {:?}, expected a whole number of at least 1",
+            text
+        ))),
+    }
+}
+
+fn parse_max_tabs(text: &str) -> Result<usize, Error> {
+    match text.trim().parse() {
+        Ok(max_tabs) if max_tabs >= 1 => Ok(max_tabs),
+        _ => Err(Error::Msg(format!(
+            "Invalid number of tabs {:?}, expected a whole number of at least 1",
+            text
+        ))),
+    }
+}
+
+/// Split `max` tabs between feeds that want `wanted` tabs each, handing them
+/// out one at a time in turn, so the first feed with lots of comics can't use
+/// them all up. The earlier feeds get the leftovers.
+pub fn share_tabs(wanted: &[usize], max: usize) -> Vec<usize> {
+    let mut shares = vec![0; wanted.len()];
+    let mut left = max;
+    while left > 0 {
+        let mut handed_out = false;
+        for (share, &want) in shares.iter_mut().zip(wanted) {
+            if left > 0 && *share < want {
+                *share += 1;
+                left -= 1;
+                handed_out = true;
+            }
+        }
+        if !handed_out {
+            break;
+        }
+    }
+    shares
+}
+
+fn parse_open_delay(text: &str) -> Result<Duration, Error> {
+    match text.trim().parse() {
+        Ok(millis) => Ok(Duration::from_millis(millis)),
+        Err(_) => Err(Error::Msg(format!(
+            "Invalid open delay {:?}, expected a whole number of milliseconds",
+            text
+        ))),
+    }
+}
+
+/// Parse a `--tz` of "UTC" or an offset like "+09:00" or "-0500"
+fn parse_timezone(text: &str) -> Result<FixedOffset, Error> {
+    let invalid = || {
+        Error::Msg(format!(
+            "Invalid timezone {:?}, expected \"UTC\" or an offset like \"+09:00\"",
+            text
+        ))
+    };
+    if text.eq_ignore_ascii_case("utc") || text == "Z" {
+        return Ok(FixedOffset::east(0));
+    }
+    let sign = match text.chars().next() {
+        Some('+') => 1,
+        Some('-') => -1,
+        _ => return Err(invalid()),
+    };
+    let digits = text[1..].replace(':', "");
+    if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let hours: i32 = digits[..2].parse().map_err(|_| invalid())?;
+    let minutes: i32 = digits[2..].parse().map_err(|_| invalid())?;
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60)).ok_or_else(invalid)
+}
+
+/// Encode everything but the unreserved characters of RFC 3986
+fn percent_encode(text: &str) -> String {
+    let mut out = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                out.push(byte as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+/// `path` under `--data-dir` if it was given, or else the platform data dir
+fn data_path(data_dir: Option<&PathBuf>, path: &str) -> Result<PathBuf, Error> {
+    match data_dir {
+        Some(data_dir) => {
+            let path = data_dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).map_err(|err| {
+                Error::Msg(format!(
+                    "Error creating data directory {}: {}",
+                    data_dir.display(),
+                    err
+                ))
+            })?;
+            Ok(path)
+        }
+        None => platform::data_path(path),
+    }
+}
+
+/// `file_name` in the first of the `--feeds` roots that has it, or else in the
+/// first one that can be written to
+fn find_in_roots(roots: &[PathBuf], file_name: &str) -> Result<PathBuf, Error> {
+    if let Some(root) = roots.iter().find(|root| !root.is_dir()) {
+        return Err(Error::Msg(format!(
+            "Error: {} is not a directory",
+            root.display()
+        )));
+    }
+    if let Some(path) = roots
+        .iter()
+        .map(|root| root.join(file_name))
+        .find(|path| path.exists())
+    {
+        return Ok(path);
+    }
+    let writable = roots.iter().find(|root| match fs::metadata(root) {
+        Ok(metadata) => !metadata.permissions().readonly(),
+        Err(_) => false,
+    });
+    match writable {
+        Some(root) => {
+            debug!("Using the first writable feed root: {:?}", root);
+            Ok(root.join(file_name))
+        }
+        None => Err(Error::Msg(
+            "Error: none of the --feeds directories can be written to".into(),
+        )),
+    }
+}
+
+fn feed_path(
+    root: Option<&PathBuf>,
+    data_dir: Option<&PathBuf>,
+    name: &str,
+) -> Result<PathBuf, Error> {
     if let Some(root) = root {
         debug!("Using feed specified on the command line: {:?}", root);
         let root = Path::new(root);
@@ -138,19 +1093,71 @@ fn feed_path(root: Option<&PathBuf>, name: &str) -> Result<PathBuf, Error> {
                 root.display()
             )))
         } else {
-            Ok(root.join(format!("{}.feed", name)))
+            Ok(root.join(feed_file_name(name)))
         }
     } else {
-        let path = platform::data_path(&format!("feeds/{}.feed", name))?;
-        debug!("Using platform data: {:?}", path);
+        let path = data_path(data_dir, &format!("feeds/{}", feed_file_name(name)))?;
+        debug!("Using data dir: {:?}", path);
         Ok(path)
     }
 }
 
-fn config_path(path: Option<&str>) -> Result<PathWrapper, Error> {
+/// `path` made absolute, with as much of it as exists canonicalized, so different
+/// spellings of the same file compare equal even before it's created
+fn resolve_path(path: &Path) -> PathBuf {
+    if let Ok(path) = path.canonicalize() {
+        return path;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) if parent != Path::new("") => resolve_path(parent).join(name),
+        _ => env::current_dir()
+            .map(|dir| dir.join(path))
+            .unwrap_or_else(|_| path.into()),
+    }
+}
+
+/// FNV-1a, which unlike `DefaultHasher` is stable across Rust versions, so it
+/// can be used in file names
+pub fn stable_hash(text: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in text.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// Leaves room under the usual 255 byte limit for the backup suffix
+const MAX_FILE_NAME: usize = 200;
+
+/// `{name}.feed`, except that names too long for the filesystem are cut short
+/// and given a hash of the full name so they stay unique.
+fn feed_file_name(name: &str) -> String {
+    let file_name = format!("{}.feed", name);
+    if file_name.len() <= MAX_FILE_NAME {
+        return file_name;
+    }
+
+    let hash = stable_hash(name);
+    let mut end = MAX_FILE_NAME - "-0123456789abcdef.feed".len();
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}-{:016x}.feed", &name[..end], hash)
+}
+
+fn config_path(path: Option<&str>, dir: Option<&str>) -> Result<PathWrapper, Error> {
     if let Some(path) = path {
         debug!("Using config specified on command line: {}", path);
         Ok(PathWrapper::ErrorIfMissing(path.into()))
+    } else if let Some(dir) = dir {
+        debug!("Using config dir specified on command line: {}", dir);
+        fs::create_dir_all(dir).map_err(|err| {
+            Error::Msg(format!("Error creating config directory {}: {}", dir, err))
+        })?;
+        Ok(PathWrapper::CreateIfMissing(
+            Path::new(dir).join("config.feeds"),
+        ))
     } else if let Some(path) = env::var_os("FEEDBURST_CONFIG_FILE") {
         debug!(
             "Using config specified as FEEDBURST_CONFIG_FILE: {}",
@@ -166,3 +1173,947 @@ fn config_path(path: Option<&str>) -> Result<PathWrapper, Error> {
         Ok(PathWrapper::CreateIfMissing(path))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::feed::UpdateSpec;
+    use crate::scratch::ScratchDir;
+
+    fn command(parts: &[&str]) -> Vec<String> {
+        parts.iter().map(|&part| part.into()).collect()
+    }
+
+    #[test]
+    fn test_expand_command() {
+        let url = "http://example.com/?comic=1&page=2";
+        assert_eq!(
+            expand_command(&command(&["firefox", "--new-tab", "@URL"]), url, false).unwrap(),
+            command(&["firefox", "--new-tab", url]),
+        );
+        assert_eq!(
+            expand_command(&command(&["firefox"]), url, false).unwrap(),
+            command(&["firefox", url]),
+        );
+        assert!(expand_command(&command(&["@url", "firefox"]), url, false).is_err());
+    }
+
+    #[test]
+    fn test_check_command() {
+        assert_eq!(
+            check_command("mpv --fs '@URL'", false).unwrap(),
+            "Parsed into 3 parts:\n    \"mpv\"\n    \"--fs\"\n    \"@URL\"\n\
+             For <https://example.com/comic?page=1> it would run:\n    \"mpv\"\n    \"--fs\"\n    \
+             \"https://example.com/comic?page=1\"\n",
+        );
+        assert!(check_command("myviewer u=@URLENC", false).is_ok());
+        assert!(check_command("mail me@example.com", false).is_ok());
+
+        let error = |command| match check_command(command, false) {
+            Err(Error::Msg(msg)) => msg,
+            other => panic!("expected an error for {:?}, got {:?}", command, other),
+        };
+        assert!(error("@URL firefox").starts_with("@URL can't be the first part"));
+        assert!(error("viewer u=@URL").starts_with("@URL must be an argument of its own"));
+        assert!(error("mpv @URL @TITLE").starts_with("Unknown placeholder @TITLE"));
+        assert!(error("mpv 'unclosed").starts_with("Error parsing command"));
+        assert!(error("   ").starts_with("Error parsing command"));
+    }
+
+    #[test]
+    fn test_expand_encoded_url() {
+        let url = "http://example.com/?comic=1&page=2";
+        assert_eq!(
+            expand_command(
+                &command(&["myviewer", "myviewer://open?u=@URLENC"]),
+                url,
+                false
+            )
+            .unwrap(),
+            command(&[
+                "myviewer",
+                "myviewer://open?u=http%3A%2F%2Fexample.com%2F%3Fcomic%3D1%26page%3D2",
+            ]),
+        );
+        assert_eq!(
+            expand_command(
+                &command(&["viewer", "--from=@urlenc", "--to=@URLENC"]),
+                "a b",
+                false
+            )
+            .unwrap(),
+            command(&["viewer", "--from=a%20b", "--to=a%20b"]),
+        );
+        assert!(expand_command(&command(&["@URLENC"]), url, false).is_err());
+    }
+
+    fn make_args(open_command: Option<Vec<String>>) -> Args {
+        Args {
+            only_fetch: false,
+            backup: true,
+            feed_format: None,
+            shell: false,
+            feed_roots: Vec::new(),
+            default_feed_root: None,
+            data_dir: None,
+            config: PathWrapper::ErrorIfMissing("config.feeds".into()),
+            open_command,
+            feed_commands: Vec::new(),
+            only: None,
+            open_mode: OpenMode::Policy,
+            max_tabs: None,
+            mark_read: None,
+            queue_file: None,
+            open_stdin: false,
+            open_delay: Duration::from_millis(0),
+            https_upgrade: false,
+            secrets: Secrets::default(),
+            timezone: None,
+            jobs: DEFAULT_JOBS,
+            timeout: Some(fetch::DEFAULT_TIMEOUT),
+            attempts: fetch::DEFAULT_ATTEMPTS,
+            user_agent: fetch::DEFAULT_USER_AGENT.into(),
+        }
+    }
+
+    fn make_info() -> FeedInfo {
+        FeedInfo {
+            name: "Test Comic".into(),
+            url: "http://example.com/feed".into(),
+            update_policies: Default::default(),
+            root: None,
+            command: None,
+            source: None,
+        }
+    }
+
+    #[test]
+    fn test_open_command_precedence() {
+        let feeds = parser::parse_config(
+            "\"Plain\" <http://example.com/a>\n\
+             command w3m\n\
+             \"Own\" <http://example.com/b>\n",
+        )
+        .unwrap();
+        let args = make_args(None);
+        assert_eq!(args.open_command(&feeds[0]), None, "the platform's opener");
+        assert_eq!(args.open_command(&feeds[1]), Some(&vec!["w3m".into()]));
+
+        let feeds = parser::parse_config(
+            "open-with firefox\n\
+             \"Plain\" <http://example.com/a>\n\
+             command w3m\n\
+             \"Own\" <http://example.com/b>\n",
+        )
+        .unwrap();
+        assert_eq!(args.open_command(&feeds[0]), Some(&vec!["firefox".into()]));
+        assert_eq!(args.open_command(&feeds[1]), Some(&vec!["w3m".into()]));
+
+        let cli = vec!["lynx".to_string()];
+        let args = make_args(Some(cli.clone()));
+        assert_eq!(args.open_command(&feeds[0]), Some(&cli));
+        assert_eq!(args.open_command(&feeds[1]), Some(&cli));
+    }
+
+    #[test]
+    fn test_feed_root_precedence() {
+        let root = ScratchDir::new("roots");
+        let (cli, config, default) = (root.join("cli"), root.join("config"), root.join("default"));
+        for dir in &[&cli, &config, &default] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+
+        let plain = make_info();
+        let rooted = FeedInfo {
+            root: Some(config.clone()),
+            ..make_info()
+        };
+
+        let mut args = make_args(None);
+        args.default_feed_root = Some(default.clone());
+        assert_eq!(
+            args.feed_path(&plain).unwrap(),
+            default.join("Test Comic.feed")
+        );
+        assert_eq!(
+            args.feed_path(&rooted).unwrap(),
+            config.join("Test Comic.feed")
+        );
+
+        args.feed_roots = vec![cli.clone()];
+        assert_eq!(args.feed_path(&plain).unwrap(), cli.join("Test Comic.feed"));
+        assert_eq!(
+            args.feed_path(&rooted).unwrap(),
+            cli.join("Test Comic.feed")
+        );
+    }
+
+    #[test]
+    fn test_check_feed_dir() {
+        let root = ScratchDir::new("check-dir");
+        let (writable, readonly) = (root.join("writable"), root.join("readonly"));
+        for dir in &[&writable, &readonly] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        let mut permissions = std::fs::metadata(&readonly).unwrap().permissions();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(&readonly, permissions).unwrap();
+
+        let rooted = |root: PathBuf| FeedInfo {
+            root: Some(root),
+            ..make_info()
+        };
+        let args = make_args(None);
+        assert!(args.check_feed_dir(&rooted(writable.clone())).is_ok());
+        match args.check_feed_dir(&rooted(readonly.clone())) {
+            Err(Error::Msg(msg)) => assert!(msg.contains("can't be written to")),
+            other => panic!("expected a read-only error, got {:?}", other),
+        }
+        assert!(args.check_feed_dir(&rooted(root.join("missing"))).is_err());
+
+        let mut permissions = std::fs::metadata(&readonly).unwrap().permissions();
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        std::fs::set_permissions(&readonly, permissions).unwrap();
+    }
+
+    #[test]
+    fn test_multiple_feed_roots() {
+        let root = ScratchDir::new("multi");
+        let (synced, local) = (root.join("synced"), root.join("local"));
+        for dir in &[&synced, &local] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        std::fs::write(local.join("Local Comic.feed"), "").unwrap();
+
+        let mut args = make_args(None);
+        args.feed_roots = vec![synced.clone(), local.clone()];
+        let local_info = FeedInfo {
+            name: "Local Comic".into(),
+            ..make_info()
+        };
+        assert_eq!(
+            args.feed_path(&local_info).unwrap(),
+            local.join("Local Comic.feed")
+        );
+        assert_eq!(
+            args.feed_path(&make_info()).unwrap(),
+            synced.join("Test Comic.feed")
+        );
+
+        // New feeds skip roots that can't be written to
+        let writable = std::fs::metadata(&synced).unwrap().permissions();
+        let mut permissions = writable.clone();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(&synced, permissions).unwrap();
+        assert_eq!(
+            args.feed_path(&make_info()).unwrap(),
+            local.join("Test Comic.feed")
+        );
+        std::fs::set_permissions(&synced, writable).unwrap();
+
+        args.feed_roots.push(root.join("missing"));
+        assert!(args.feed_path(&make_info()).is_err());
+    }
+
+    #[test]
+    fn test_mark_feed_read() {
+        let root = ScratchDir::new("mark-read");
+        // Would fail if anything were opened
+        let mut args = make_args(Some(command(&["/nonexistent/viewer", "@URL"])));
+        args.default_feed_root = Some(root.to_path_buf());
+        let info = make_info();
+        std::fs::write(
+            args.feed_path(&info).unwrap(),
+            "<http://example.com/1>\n<http://example.com/2>\n",
+        )
+        .unwrap();
+
+        let mut feed = info.read_feed(&mut args.feed_file(&info).unwrap()).unwrap();
+        assert_eq!(feed.get_reading_list().into_urls().len(), 2);
+        assert_eq!(args.mark_feed_read(&mut feed).unwrap(), 2);
+        assert!(feed.get_reading_list().is_empty());
+
+        let reloaded = info.read_feed(&mut args.feed_file(&info).unwrap()).unwrap();
+        assert!(reloaded.get_reading_list().is_empty());
+        assert_eq!(reloaded.all_comics().len(), 2);
+    }
+
+    #[test]
+    fn test_scoped_open_command() {
+        let mut args = make_args(Some(command(&["firefox"])));
+        args.feed_commands = vec![("Test Comic".into(), command(&["mpv", "@URL"]))];
+        let mut other = make_info();
+        other.name = "Other Comic".into();
+        other.command = Some(command(&["chromium"]));
+
+        assert_eq!(
+            args.open_command(&make_info()),
+            Some(&command(&["mpv", "@URL"]))
+        );
+        assert_eq!(args.open_command(&other), Some(&command(&["firefox"])));
+        args.open_command = None;
+        assert_eq!(
+            args.open_command(&make_info()),
+            Some(&command(&["mpv", "@URL"]))
+        );
+        assert_eq!(args.open_command(&other), Some(&command(&["chromium"])));
+    }
+
+    #[test]
+    fn test_first_run() {
+        let root = ScratchDir::new("first-run");
+        let mut args = make_args(None);
+        args.config = PathWrapper::CreateIfMissing(root.join("config.feeds"));
+
+        let (_, created) = args.open_config().unwrap();
+        assert!(created);
+        assert!(args.config_path().exists());
+        // Still empty, but it was there already
+        let (_, created) = args.open_config().unwrap();
+        assert!(!created);
+        args.config = PathWrapper::ErrorIfMissing(root.join("config.feeds"));
+        assert!(!args.open_config().unwrap().1);
+
+        let message = first_run_message(args.config_path());
+        assert!(message.contains(&args.config_path().display().to_string()));
+        assert!(message.contains(EXAMPLE_FEED));
+        assert_eq!(parser::parse_config(EXAMPLE_FEED).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_shared_urls() {
+        let feed = |name: &str, url: &str| FeedInfo {
+            name: name.into(),
+            url: url.into(),
+            ..make_info()
+        };
+        let feeds = [
+            feed("Witchy", "http://witchycomic.com/feed"),
+            feed("Electrum", "http://electrum.cubemelon.net/feed"),
+            feed("Witchy Again", "http://witchycomic.com/feed"),
+        ];
+        assert_eq!(shared_urls(&feeds), vec![("Witchy Again", "Witchy")]);
+        assert!(shared_urls(&feeds[..2]).is_empty());
+    }
+
+    #[test]
+    fn test_print_paths() {
+        let root = ScratchDir::new("print");
+        let mut args = make_args(None);
+        args.config = PathWrapper::CreateIfMissing(root.join("config.feeds"));
+        args.default_feed_root = Some(root.to_path_buf());
+        let feeds = vec![make_info()];
+
+        assert!(args.existing_config_file().is_err());
+        assert!(!args.config_path().exists());
+        let path = args.feed_path_named(&feeds, "Test Comic").unwrap();
+        assert_eq!(path, args.feed_path(&feeds[0]).unwrap());
+        assert_eq!(path, root.join("Test Comic.feed"));
+        assert!(!path.exists());
+        assert_eq!(
+            args.feed_path_named(&feeds, "Missing")
+                .unwrap_err()
+                .to_string(),
+            "No feed named \"Missing\" in your config"
+        );
+
+        // The same file that's read and written later
+        args.feed_file(&feeds[0]).unwrap();
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn test_feed_path_collisions() {
+        let root = ScratchDir::new("collide");
+        let mut args = make_args(None);
+        args.config = PathWrapper::ErrorIfMissing(root.join("comics.feed"));
+        args.default_feed_root = Some(root.to_path_buf());
+        let named = |name: &str| FeedInfo {
+            name: name.into(),
+            ..make_info()
+        };
+
+        assert!(args
+            .check_feed_paths(&[named("Witchy"), named("Electrum")], &[])
+            .is_ok());
+
+        let error = args.check_feed_paths(&[named("Witchy"), named("comics")], &[]);
+        assert_eq!(
+            error.unwrap_err().to_string(),
+            format!(
+                "Feed \"comics\" would be stored in {}, which is your config file",
+                root.join("comics.feed").display()
+            )
+        );
+
+        let included = [root.join("genres.feed")];
+        let error = args.check_feed_paths(&[named("genres")], &included);
+        assert_eq!(
+            error.unwrap_err().to_string(),
+            format!(
+                "Feed \"genres\" would be stored in {}, which your config file includes",
+                root.join("genres.feed").display()
+            )
+        );
+
+        let error = args.check_feed_paths(&[named("Witchy"), named("./Witchy")], &[]);
+        assert_eq!(
+            error.unwrap_err().to_string(),
+            format!(
+                "Feeds \"Witchy\" and \"./Witchy\" would both be stored in {}",
+                root.join("./Witchy.feed").display()
+            )
+        );
+
+        // Left to fail when the feed is read
+        let elsewhere = FeedInfo {
+            root: Some(root.join("missing")),
+            ..named("comics")
+        };
+        args.default_feed_root = None;
+        args.data_dir = Some(root.join("data"));
+        assert!(args.check_feed_paths(&[elsewhere], &[]).is_ok());
+    }
+
+    #[test]
+    fn test_update_feed_url() {
+        let dir = ScratchDir::new("update-url");
+        let mut args = make_args(None);
+        args.config = PathWrapper::ErrorIfMissing(dir.join("config.feeds"));
+        let config = "include more.feeds\n\"XKCD\" <https://xkcd.com/atom.xml>\n";
+        let more = "\"Witchy\" <http://witchycomic.com/feed> @ follow-redirects\n";
+        fs::write(dir.join("config.feeds"), config).unwrap();
+        fs::write(dir.join("more.feeds"), more).unwrap();
+
+        let xkcd = FeedInfo {
+            name: "XKCD".into(),
+            ..make_info()
+        };
+        args.update_feed_url(&xkcd, "https://xkcd.com/rss.xml")
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("config.feeds")).unwrap(),
+            "include more.feeds\n\"XKCD\" <https://xkcd.com/rss.xml>\n"
+        );
+
+        // Feeds from an included file are changed there
+        let witchy = FeedInfo {
+            name: "Witchy".into(),
+            source: Some(dir.join("more.feeds")),
+            ..make_info()
+        };
+        args.update_feed_url(&witchy, "https://witchycomic.com/feed")
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("more.feeds")).unwrap(),
+            "\"Witchy\" <https://witchycomic.com/feed> @ follow-redirects\n"
+        );
+        assert!(fs::read_to_string(dir.join("config.feeds"))
+            .unwrap()
+            .starts_with("include more.feeds\n"));
+    }
+
+    #[test]
+    fn test_dir_overrides() {
+        let root = ScratchDir::new("dirs");
+        let (data, config) = (root.join("data"), root.join("config"));
+        let config_dir = config.to_str().unwrap();
+
+        let mut args = make_args(None);
+        args.config = config_path(None, Some(config_dir)).unwrap();
+        assert_eq!(args.config_path(), &config.join("config.feeds"));
+        assert_eq!(args.secrets_path(), config.join("secrets"));
+        assert!(config.is_dir());
+        match config_path(Some("other.feeds"), Some(config_dir)).unwrap() {
+            PathWrapper::ErrorIfMissing(path) => assert_eq!(path, Path::new("other.feeds")),
+            other => panic!("--config should win over --config-dir, got {:?}", other),
+        }
+
+        args.data_dir = Some(data.clone());
+        assert_eq!(
+            args.feed_path(&make_info()).unwrap(),
+            data.join("feeds").join("Test Comic.feed"),
+        );
+        assert!(data.join("feeds").is_dir());
+        assert_eq!(
+            args.state_path(state::LAST_RUN_FILE).unwrap(),
+            data.join(state::LAST_RUN_FILE),
+        );
+
+        // Explicit feed roots still take precedence over the data dir
+        args.feed_roots = vec![root.to_path_buf()];
+        assert_eq!(
+            args.feed_path(&make_info()).unwrap(),
+            root.join("Test Comic.feed"),
+        );
+    }
+
+    #[test]
+    fn test_secret_headers() {
+        let text = r#"
+# Kept out of the synced config
+"Test Comic" X-Api-Key: abc123
+"Test Comic" Referer: http://example.com/
+"#;
+        let secrets = Secrets::parse(text, Path::new("secrets")).unwrap();
+        let info = FeedInfo {
+            update_policies: vec![UpdateSpec::Secrets].into_iter().collect(),
+            ..make_info()
+        };
+        let headers = secrets.headers_for(&info).unwrap();
+        assert_eq!(headers.len(), 2);
+
+        let client = crate::fetch::build_client(&info, None, fetch::DEFAULT_USER_AGENT).unwrap();
+        let request = crate::fetch::build_request(&client, &info.url, headers)
+            .build()
+            .unwrap();
+        assert_eq!(request.headers()["X-Api-Key"], "abc123");
+        assert_eq!(request.headers()["Referer"], "http://example.com/");
+
+        let other = FeedInfo {
+            name: "Other Comic".into(),
+            ..info.clone()
+        };
+        assert!(secrets.headers_for(&other).is_err());
+        let other = FeedInfo {
+            name: "Other Comic".into(),
+            ..make_info()
+        };
+        assert_eq!(secrets.headers_for(&other).unwrap(), &[]);
+
+        assert!(Secrets::parse("\"Test Comic\" abc123", Path::new("secrets")).is_err());
+    }
+
+    #[test]
+    fn test_jobs() {
+        use clap::{App, Arg};
+
+        let root = ScratchDir::new("jobs");
+        let config = root.join("config.feeds");
+        let config = config.to_str().unwrap();
+        let args = |jobs: &[&str]| {
+            let app = App::new("feedburst")
+                .arg(Arg::with_name("config").long("config").takes_value(true))
+                .arg(Arg::with_name("jobs").long("jobs").takes_value(true));
+            let mut argv = vec!["feedburst", "--config", config];
+            argv.extend_from_slice(jobs);
+            Args::new(&app.get_matches_from(argv))
+        };
+
+        assert_eq!(args(&["--jobs", "8"]).unwrap().jobs(), 8);
+        assert!(args(&["--jobs", "0"]).is_err());
+        // No other test sets it, so it's only changed here
+        env::set_var("FEEDBURST_JOBS", "6");
+        assert_eq!(args(&[]).unwrap().jobs(), 6);
+        assert_eq!(args(&["--jobs", "2"]).unwrap().jobs(), 2, "--jobs wins");
+        env::remove_var("FEEDBURST_JOBS");
+        assert_eq!(args(&[]).unwrap().jobs(), DEFAULT_JOBS);
+    }
+
+    #[test]
+    fn test_timeout() {
+        use clap::{App, Arg};
+
+        let root = ScratchDir::new("timeout");
+        let config = root.join("config.feeds");
+        let config = config.to_str().unwrap();
+        let args = |timeout: &[&str]| {
+            let app = App::new("feedburst")
+                .arg(Arg::with_name("config").long("config").takes_value(true))
+                .arg(Arg::with_name("timeout").long("timeout").takes_value(true));
+            let mut argv = vec!["feedburst", "--config", config];
+            argv.extend_from_slice(timeout);
+            Args::new(&app.get_matches_from(argv))
+        };
+
+        assert_eq!(
+            args(&["--timeout", "30"]).unwrap().timeout(),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(args(&["--timeout", "0"]).unwrap().timeout(), None);
+        assert_eq!(args(&[]).unwrap().timeout(), Some(fetch::DEFAULT_TIMEOUT));
+        match args(&["--timeout", "3601"]) {
+            Err(Error::Msg(msg)) => assert_eq!(
+                msg,
+                "Invalid timeout \"3601\", expected a whole number of seconds up to 3600, or 0 for none"
+            ),
+            other => panic!("expected an error for 3601, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn test_parse_jobs_and_timeout() {
+        assert_eq!(parse_jobs("8").unwrap(), 8);
+        assert_eq!(parse_jobs(" 1 ").unwrap(), 1);
+        assert_eq!(parse_timeout("30").unwrap(), Some(Duration::from_secs(30)));
+        assert_eq!(parse_timeout("0").unwrap(), None);
+        let jobs = |bad: &str| {
+            let expected = format!(
+                "Invalid number of jobs {:?}, expected a whole number of at least 1",
+                bad
+            );
+            (parse_jobs(bad).err(), expected)
+        };
+        let timeout = |bad: &str| {
+            let expected = format!(
+                "Invalid timeout {:?}, expected a whole number of seconds up to 3600, or 0 for none",
+                bad
+            );
+            (parse_timeout(bad).err(), expected)
+        };
+        let rejected = [
+            jobs("0"),
+            jobs("many"),
+            jobs("2.5"),
+            timeout("3601"),
+            timeout("-5"),
+            timeout("1.5"),
+            timeout("forever"),
+        ];
+        for (error, expected) in rejected {
+            match error {
+                Some(Error::Msg(msg)) => assert_eq!(msg, expected),
+                other => panic!("expected {:?}, got {:?}", expected, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_attempts() {
+        assert_eq!(parse_attempts("1").unwrap(), 1);
+        assert_eq!(parse_attempts(" 5 ").unwrap(), 5);
+        for bad in &["0", "-1", "lots"] {
+            match parse_attempts(bad) {
+                Err(Error::Msg(msg)) => assert_eq!(
+                    msg,
+                    format!(
+                        "Invalid number of attempts {:?}, expected a whole number of at least 1",
+                        bad
+                    )
+                ),
+                other => panic!("expected an error for {:?}, got {:?}", bad, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_timezone() {
+        assert_eq!(parse_timezone("UTC").unwrap(), FixedOffset::east(0));
+        assert_eq!(
+            parse_timezone("+09:00").unwrap(),
+            FixedOffset::east(9 * 3600)
+        );
+        assert_eq!(
+            parse_timezone("-0530").unwrap(),
+            FixedOffset::west(5 * 3600 + 30 * 60)
+        );
+        assert!(parse_timezone("Europe/Paris").is_err());
+        assert!(parse_timezone("+9").is_err());
+        assert!(parse_timezone("+99:00").is_err());
+    }
+
+    #[test]
+    fn test_long_feed_name() {
+        assert_eq!(feed_file_name("Test Comic"), "Test Comic.feed");
+
+        let long = "A Very Long Comic ".repeat(17)[..300].to_string();
+        let file_name = feed_file_name(&long);
+        assert!(file_name.len() <= MAX_FILE_NAME);
+        assert!(file_name.starts_with("A Very Long Comic A Very"));
+        assert!(file_name.ends_with(".feed"));
+        assert_eq!(feed_file_name(&long), file_name, "should be stable");
+        let other = format!("{}!", long);
+        assert_ne!(feed_file_name(&other), file_name);
+
+        // Never split a character
+        let wide = "漫".repeat(100);
+        assert!(feed_file_name(&wide).len() <= MAX_FILE_NAME);
+
+        let root = ScratchDir::new("long");
+        let mut args = make_args(None);
+        args.feed_roots = vec![root.to_path_buf()];
+        let info = FeedInfo {
+            name: long,
+            ..make_info()
+        };
+        assert!(args.feed_file(&info).is_ok());
+        assert_eq!(args.feed_path(&info).unwrap(), root.join(file_name));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_open_stdin() {
+        let root = ScratchDir::new("stdin");
+        let received = root.join("received");
+        let script = format!("cat > '{}'", received.display());
+
+        let mut args = make_args(Some(command(&["sh", "-c", &script])));
+        args.open_stdin = true;
+        let urls = vec![
+            "http://example.com/1".to_string(),
+            "http://example.com/2".to_string(),
+        ];
+        args.open_urls(&make_info(), &urls, &mut args.open_pacer())
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&received).unwrap(),
+            "http://example.com/1\nhttp://example.com/2\n",
+        );
+
+        let mut failing = make_args(Some(command(&["sh", "-c", "cat > /dev/null; exit 1"])));
+        failing.open_stdin = true;
+        assert!(failing
+            .open_urls(&make_info(), &urls, &mut failing.open_pacer())
+            .is_err());
+
+        let mut platform = make_args(None);
+        platform.open_stdin = true;
+        assert!(platform
+            .open_urls(&make_info(), &urls, &mut platform.open_pacer())
+            .is_err());
+
+        // The URLs only go to stdin
+        let mut placeholder = make_args(Some(command(&["xargs", "echo", "@URL"])));
+        placeholder.open_stdin = true;
+        let error = placeholder
+            .open_urls(&make_info(), &urls, &mut placeholder.open_pacer())
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("--open-stdin passes the URLs on stdin"));
+
+        // Parts that were quoted stay whole in the shell, and the rest is left to it
+        let spaced = root.join("got urls");
+        let spaced_str = spaced.display().to_string();
+        let mut shell = make_args(Some(command(&["cat", "|", "sort", "-r", ">", &spaced_str])));
+        shell.open_stdin = true;
+        shell.shell = true;
+        shell
+            .open_urls(&make_info(), &urls, &mut shell.open_pacer())
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&spaced).unwrap(),
+            "http://example.com/2\nhttp://example.com/1\n",
+        );
+    }
+
+    struct FakeClock {
+        now: Instant,
+        sleeps: Vec<Duration>,
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            self.now
+        }
+
+        fn sleep(&mut self, duration: Duration) {
+            self.sleeps.push(duration);
+            self.now += duration;
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_open_delay() {
+        let ms = Duration::from_millis;
+        let clock = FakeClock {
+            now: Instant::now(),
+            sleeps: vec![],
+        };
+        let mut pacer = OpenPacer::new(ms(100), clock);
+        let args = make_args(Some(command(&["true", "@URL"])));
+        let urls: Vec<_> = (1..=3)
+            .map(|i| format!("http://example.com/{}", i))
+            .collect();
+
+        // Only between opens, not before the first
+        args.open_urls(&make_info(), &urls, &mut pacer).unwrap();
+        assert_eq!(pacer.clock.sleeps, vec![ms(100), ms(100)]);
+
+        // Time spent on something else counts toward the delay
+        pacer.clock.now += ms(30);
+        args.open_urls(&make_info(), &urls[..1], &mut pacer)
+            .unwrap();
+        assert_eq!(pacer.clock.sleeps, vec![ms(100), ms(100), ms(70)]);
+        pacer.clock.now += ms(500);
+        args.open_urls(&make_info(), &urls[..1], &mut pacer)
+            .unwrap();
+        assert_eq!(pacer.clock.sleeps.len(), 3);
+
+        let mut no_delay = OpenPacer::new(
+            ms(0),
+            FakeClock {
+                now: Instant::now(),
+                sleeps: vec![],
+            },
+        );
+        args.open_urls(&make_info(), &urls, &mut no_delay).unwrap();
+        assert!(no_delay.clock.sleeps.is_empty());
+
+        assert_eq!(parse_open_delay("250").unwrap(), ms(250));
+        assert!(parse_open_delay("-1").is_err());
+        assert!(parse_open_delay("soon").is_err());
+    }
+
+    #[test]
+    fn test_log_level() {
+        assert_eq!(log_level(0), LevelFilter::Warn);
+        assert_eq!(log_level(1), LevelFilter::Info);
+        assert_eq!(log_level(2), LevelFilter::Debug);
+        assert_eq!(log_level(3), LevelFilter::Trace);
+        assert_eq!(log_level(7), LevelFilter::Trace);
+    }
+
+    #[test]
+    fn test_queue_file() {
+        let root = ScratchDir::new("queue");
+        let queue = root.join("queue");
+        std::fs::write(&queue, "http://example.com/0\n").unwrap();
+
+        // Would fail if it were run
+        let mut args = make_args(Some(command(&["/nonexistent/viewer", "@URL"])));
+        args.queue_file = Some(queue.clone());
+        let info = FeedInfo {
+            update_policies: vec![UpdateSpec::OpenAll].into_iter().collect(),
+            ..make_info()
+        };
+        let events = "<http://example.com/1>\n<http://example.com/2>\n";
+        let mut feed = info.read_feed(&mut events.as_bytes()).unwrap();
+        for url in feed.get_open_list(args.open_mode()) {
+            args.open_url(&feed.info, &url).unwrap();
+        }
+        feed.read();
+        assert!(feed.get_reading_list().is_empty());
+        assert_eq!(
+            std::fs::read_to_string(&queue).unwrap(),
+            "http://example.com/0\nhttp://example.com/1\nhttp://example.com/2\n",
+        );
+    }
+
+    #[test]
+    fn test_save_fails_after_open() {
+        let root = ScratchDir::new("save-fails");
+        let queue = root.join("queue");
+        let mut args = make_args(None);
+        args.queue_file = Some(queue.clone());
+        args.default_feed_root = Some(root.to_path_buf());
+        let info = make_info();
+        // A directory where the feed file should be, so it can't be written
+        std::fs::create_dir_all(args.feed_path(&info).unwrap()).unwrap();
+
+        let events = "<http://example.com/1>\n<http://example.com/2>\n";
+        let mut feed = info.read_feed(&mut events.as_bytes()).unwrap();
+        let err = match args.open_and_read(&mut feed, &mut args.open_pacer(), usize::MAX) {
+            Err(Error::Msg(err)) => err,
+            other => panic!("expected the save to fail, got {:?}", other),
+        };
+        assert!(err.starts_with("Error writing feed file"), "{}", err);
+        assert!(err.ends_with("they'll be shown again next time"), "{}", err);
+        assert_eq!(
+            std::fs::read_to_string(&queue).unwrap(),
+            "http://example.com/1\n",
+        );
+    }
+
+    #[test]
+    fn test_share_tabs() {
+        // Under the cap, everyone gets what they want
+        assert_eq!(share_tabs(&[3, 1, 2], 10), [3, 1, 2]);
+        // The first feed doesn't get all of them
+        assert_eq!(share_tabs(&[10, 2, 3], 6), [2, 2, 2]);
+        assert_eq!(share_tabs(&[10, 1, 3], 7), [3, 1, 3]);
+        assert_eq!(share_tabs(&[5, 5, 5], 4), [2, 1, 1]);
+        assert_eq!(share_tabs(&[5, 5, 5], 2), [1, 1, 0]);
+        assert_eq!(share_tabs(&[], 3), Vec::<usize>::new());
+        assert!(parse_max_tabs("0").is_err());
+        assert_eq!(parse_max_tabs("4").unwrap(), 4);
+    }
+
+    #[test]
+    fn test_open_at_most() {
+        let root = ScratchDir::new("open-up-to");
+        let queue = root.join("queue");
+        let mut args = make_args(None);
+        args.queue_file = Some(queue.clone());
+        args.default_feed_root = Some(root.to_path_buf());
+        args.open_mode = OpenMode::All;
+        let info = make_info();
+
+        let events = "<http://example.com/1>\n<http://example.com/2>\n<http://example.com/3>\n";
+        let mut feed = info.read_feed(&mut events.as_bytes()).unwrap();
+        let mut pacer = args.open_pacer();
+        args.open_and_read(&mut feed, &mut pacer, 2).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&queue).unwrap(),
+            "http://example.com/1\nhttp://example.com/2\n",
+        );
+        assert_eq!(
+            feed.get_reading_list().into_urls(),
+            ["http://example.com/3"]
+        );
+        args.open_and_read(&mut feed, &mut pacer, 2).unwrap();
+        assert!(feed.get_reading_list().is_empty());
+
+        // With `@ max` the newest are opened, and older ones are skipped over
+        std::fs::remove_file(&queue).unwrap();
+        let info = FeedInfo {
+            update_policies: vec![UpdateSpec::Max(3)].into_iter().collect(),
+            ..make_info()
+        };
+        let events = (1..=5)
+            .map(|n| format!("<http://example.com/{}>\n", n))
+            .collect::<String>();
+        let mut feed = info.read_feed(&mut events.as_bytes()).unwrap();
+        args.open_and_read(&mut feed, &mut pacer, 2).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&queue).unwrap(),
+            "http://example.com/3\nhttp://example.com/4\n",
+        );
+        assert_eq!(
+            feed.get_reading_list().into_urls(),
+            ["http://example.com/5"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_spawn_url() {
+        let args = make_args(Some(command(&["sleep", "@URL"])));
+        let mut child = args.spawn_url(&make_info(), "5").unwrap();
+        assert!(
+            child.try_wait().unwrap().is_none(),
+            "should still be running"
+        );
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        let args = make_args(Some(command(&["true"])));
+        let mut child = args.spawn_url(&make_info(), "http://example.com/").unwrap();
+        assert!(child.wait().unwrap().success());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_expand_shell_command() {
+        let url = "http://example.com/?comic=1&page=2";
+        assert_eq!(
+            expand_command(
+                &command(&["firefox", "@URL", "&&", "notify-send", "comic opened"]),
+                url,
+                true,
+            )
+            .unwrap(),
+            command(&[
+                "sh",
+                "-c",
+                "firefox 'http://example.com/?comic=1&page=2' && notify-send 'comic opened'",
+            ]),
+        );
+        assert_eq!(
+            expand_command(&command(&["echo"]), "http://example.com/it's", true).unwrap(),
+            command(&["sh", "-c", "echo 'http://example.com/it'\\''s'"]),
+        );
+    }
+}
@@ -1,20 +1,71 @@
-use chrono::{DateTime, Local, Utc, Weekday};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, TimeZone, Utc, Weekday};
 use regex::Regex;
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
 use std::io::{self, Read, Seek, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use crate::config::write_atomically;
 use crate::error::{Error, ParseError, Span};
 use crate::parser::parse_events;
 
+/// Written at the top of feed files by `--config-migrate`. Files without it
+/// are from version 1, before partial reads and bookmarks. Version 3 added
+/// filtered entries, version 4 recap markers, version 5 fetch times, version 6
+/// content hashes, and version 7 publication dates.
+pub const FEED_FORMAT_VERSION: usize = 7;
+
 #[derive(Hash, Clone, Debug, PartialEq, Eq)]
 pub enum UpdateSpec {
     On(Weekday),
-    Every(usize),
+    Every(usize, TimeUnit),
+    EveryFrom(usize, NaiveDate),
     Comics(usize),
+    /// Wait for a number of comics, or for a number of days since the last read
+    ComicsOrAfter(usize, usize),
     Overlap(usize),
+    /// Show at most this many of the most recent comics, recap included
+    Max(usize),
     Filter(FilterType, String),
+    /// An `@ filter "..."` expression, which every kept entry has to match
+    FilterExpr(FilterExpr),
     OpenAll,
+    OpenPage(String),
+    OpenHomepage,
+    Silent,
+    Insecure,
+    RecordFiltered,
+    /// Show read comics again when their content changes
+    NotifyUpdates,
+    Secrets,
+    FollowRedirects,
+    UrlTemplate(String),
+    SlugFrom(String),
+    /// Presentation only, for showing the feed's name
+    Color(Color),
+    Icon(String),
+    Serial,
+    /// Keep unread comics in order of publication, even if older ones arrive late
+    PreserveOrder,
+    /// Count `@ every` from when there were enough comics, instead of from the last read
+    EveryFromThreshold,
+    /// Never ready on Saturdays or Sundays, whatever the other policies say
+    SkipWeekends,
+    /// Show at most this many new comics for each day since the last read
+    Pace(usize),
+    /// Which of an entry's dates to order and date its comic by
+    OrderBy(DateField),
+}
+
+/// Which comics to open in this run, from `--open-first-only` and `--open-all`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpenMode {
+    /// Whatever the feed's `@ open all` says
+    Policy,
+    FirstOnly,
+    All,
 }
 
 #[derive(Hash, Clone, Debug, PartialEq, Eq)]
@@ -25,6 +76,341 @@ pub enum FilterType {
     IgnoreUrl,
 }
 
+/// Which date of a feed entry `@ order-by` uses, `Published` by default
+#[derive(Hash, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DateField {
+    Published,
+    Updated,
+}
+
+/// What an `@ every` interval is counted in
+#[derive(Hash, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TimeUnit {
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+impl TimeUnit {
+    pub fn name(self) -> &'static str {
+        match self {
+            TimeUnit::Hour => "hour",
+            TimeUnit::Day => "day",
+            TimeUnit::Week => "week",
+            TimeUnit::Month => "month",
+        }
+    }
+}
+
+/// What `--json` prints for a feed. This is separate from `Feed` so the output
+/// doesn't change along with the file format.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct FeedReport {
+    pub name: String,
+    pub url: String,
+    pub unread_count: usize,
+    pub ready: bool,
+    pub reading_list: ReportList,
+    pub color: Option<&'static str>,
+    pub icon: Option<String>,
+}
+
+/// A `ReadingList` in a `FeedReport`, kept in its parts so tools can tell
+/// recapped and updated comics from new ones
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ReportList {
+    pub recap: Vec<ReportComic>,
+    pub updated: Vec<ReportComic>,
+    pub new: Vec<ReportComic>,
+}
+
+/// A comic in a `ReportList`, with when it was published if the feed said
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ReportComic {
+    pub url: String,
+    pub published: Option<String>,
+}
+
+/// A terminal color to show a feed's name in
+#[derive(Hash, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+}
+
+impl Color {
+    pub const ALL: [Color; 8] = [
+        Color::Black,
+        Color::Red,
+        Color::Green,
+        Color::Yellow,
+        Color::Blue,
+        Color::Magenta,
+        Color::Cyan,
+        Color::White,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Color::Black => "black",
+            Color::Red => "red",
+            Color::Green => "green",
+            Color::Yellow => "yellow",
+            Color::Blue => "blue",
+            Color::Magenta => "magenta",
+            Color::Cyan => "cyan",
+            Color::White => "white",
+        }
+    }
+
+    /// `text` wrapped in the ANSI escapes for this color
+    pub fn paint(self, text: &str) -> String {
+        let code = 30 + Color::ALL.iter().position(|&c| c == self).unwrap_or(0);
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    }
+}
+
+/// The part of an entry a filter expression looks at
+#[derive(Hash, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FilterField {
+    Title,
+    Url,
+}
+
+/// A boolean combination of patterns, like `title ~ /chapter/ and url !~ /guest/`
+#[derive(Hash, Clone, Debug, PartialEq, Eq)]
+pub enum FilterExpr {
+    Matches(FilterField, String),
+    NotMatches(FilterField, String),
+    Not(Box<FilterExpr>),
+    And(Box<FilterExpr>, Box<FilterExpr>),
+    Or(Box<FilterExpr>, Box<FilterExpr>),
+}
+
+impl FilterExpr {
+    /// Every pattern in the expression, in order
+    pub fn patterns(&self) -> Vec<&str> {
+        match *self {
+            FilterExpr::Matches(_, ref pat) | FilterExpr::NotMatches(_, ref pat) => vec![pat],
+            FilterExpr::Not(ref inner) => inner.patterns(),
+            FilterExpr::And(ref lhs, ref rhs) | FilterExpr::Or(ref lhs, ref rhs) => {
+                let mut patterns = lhs.patterns();
+                patterns.extend(rhs.patterns());
+                patterns
+            }
+        }
+    }
+
+    pub fn matches(&self, title: &str, url: &str) -> bool {
+        let field = |field: &FilterField| match *field {
+            FilterField::Title => title,
+            FilterField::Url => url,
+        };
+        // @Performance: Avoid compiling so many regexes
+        match *self {
+            FilterExpr::Matches(ref kind, ref pat) => {
+                Regex::new(pat).unwrap().is_match(field(kind))
+            }
+            FilterExpr::NotMatches(ref kind, ref pat) => {
+                !Regex::new(pat).unwrap().is_match(field(kind))
+            }
+            FilterExpr::Not(ref inner) => !inner.matches(title, url),
+            FilterExpr::And(ref lhs, ref rhs) => lhs.matches(title, url) && rhs.matches(title, url),
+            FilterExpr::Or(ref lhs, ref rhs) => lhs.matches(title, url) || rhs.matches(title, url),
+        }
+    }
+
+    /// How tightly the expression binds, so `Display` knows where parentheses go
+    fn precedence(&self) -> u8 {
+        match *self {
+            FilterExpr::Or(_, _) => 0,
+            FilterExpr::And(_, _) => 1,
+            _ => 2,
+        }
+    }
+
+    fn fmt_operand(&self, fmt: &mut fmt::Formatter<'_>, min_precedence: u8) -> fmt::Result {
+        if self.precedence() < min_precedence {
+            write!(fmt, "({})", self)
+        } else {
+            write!(fmt, "{}", self)
+        }
+    }
+}
+
+/// Writes the expression the way it's spelled inside the quotes of `@ filter`
+impl fmt::Display for FilterExpr {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pattern = |fmt: &mut fmt::Formatter<'_>, kind: &FilterField, op: &str, pat: &str| {
+            let field = match *kind {
+                FilterField::Title => "title",
+                FilterField::Url => "url",
+            };
+            let delim = ['/', '|', '#', '!']
+                .iter()
+                .cloned()
+                .find(|&c| !pat.contains(c))
+                .unwrap_or('/');
+            write!(fmt, "{} {} {}{}{}", field, op, delim, pat, delim)
+        };
+        match *self {
+            FilterExpr::Matches(ref kind, ref pat) => pattern(fmt, kind, "~", pat),
+            FilterExpr::NotMatches(ref kind, ref pat) => pattern(fmt, kind, "!~", pat),
+            FilterExpr::Not(ref inner) => {
+                write!(fmt, "not ")?;
+                inner.fmt_operand(fmt, 2)
+            }
+            FilterExpr::And(ref lhs, ref rhs) => {
+                lhs.fmt_operand(fmt, 1)?;
+                write!(fmt, " and ")?;
+                rhs.fmt_operand(fmt, 2)
+            }
+            FilterExpr::Or(ref lhs, ref rhs) => {
+                lhs.fmt_operand(fmt, 0)?;
+                write!(fmt, " or ")?;
+                rhs.fmt_operand(fmt, 1)
+            }
+        }
+    }
+}
+
+/// Writes the policy the way it's spelled in the config, without the leading `@`
+impl fmt::Display for UpdateSpec {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            UpdateSpec::On(day) => write!(fmt, "on {}", weekday_name(day)),
+            UpdateSpec::Every(count, unit) => {
+                write!(fmt, "every {} {}{}", count, unit.name(), plural(count))
+            }
+            UpdateSpec::EveryFrom(days, anchor) => {
+                write!(fmt, "every {} day{} from {}", days, plural(days), anchor)
+            }
+            UpdateSpec::Comics(count) => write!(fmt, "{} new comic{}", count, plural(count)),
+            UpdateSpec::ComicsOrAfter(count, days) => write!(
+                fmt,
+                "{} new comic{} or after {} day{}",
+                count,
+                plural(count),
+                days,
+                plural(days)
+            ),
+            UpdateSpec::Overlap(count) => {
+                write!(fmt, "overlap {} comic{}", count, plural(count))
+            }
+            UpdateSpec::Filter(ref kind, ref pat) => {
+                let action = match *kind {
+                    FilterType::KeepTitle => "keep title",
+                    FilterType::IgnoreTitle => "ignore title",
+                    FilterType::KeepUrl => "keep url",
+                    FilterType::IgnoreUrl => "ignore url",
+                };
+                let delim = ['/', '|', '#', '!']
+                    .iter()
+                    .cloned()
+                    .find(|&c| !pat.contains(c))
+                    .unwrap_or('/');
+                write!(fmt, "{} {}{}{}", action, delim, pat, delim)
+            }
+            UpdateSpec::FilterExpr(ref expr) => write!(fmt, "filter \"{}\"", expr),
+            UpdateSpec::OpenAll => write!(fmt, "open all"),
+            UpdateSpec::OpenPage(ref url) => write!(fmt, "open feed-page \"{}\"", url),
+            UpdateSpec::OpenHomepage => write!(fmt, "open homepage"),
+            UpdateSpec::Silent => write!(fmt, "silent"),
+            UpdateSpec::Insecure => write!(fmt, "insecure"),
+            UpdateSpec::RecordFiltered => write!(fmt, "record-filtered"),
+            UpdateSpec::NotifyUpdates => write!(fmt, "notify-updates"),
+            UpdateSpec::Secrets => write!(fmt, "secrets"),
+            UpdateSpec::FollowRedirects => write!(fmt, "follow-redirects"),
+            UpdateSpec::UrlTemplate(ref template) => write!(fmt, "url-template \"{}\"", template),
+            UpdateSpec::SlugFrom(ref pat) => write!(fmt, "slug-from \"{}\"", pat),
+            UpdateSpec::Serial => write!(fmt, "serial"),
+            UpdateSpec::PreserveOrder => write!(fmt, "preserve-order"),
+            UpdateSpec::EveryFromThreshold => write!(fmt, "every-from-threshold"),
+            UpdateSpec::Max(count) => write!(fmt, "max {} comic{}", count, plural(count)),
+            UpdateSpec::SkipWeekends => write!(fmt, "skip weekends"),
+            UpdateSpec::Pace(count) => write!(fmt, "pace {} per day", count),
+            UpdateSpec::OrderBy(DateField::Published) => write!(fmt, "order-by published"),
+            UpdateSpec::OrderBy(DateField::Updated) => write!(fmt, "order-by updated"),
+            UpdateSpec::Color(color) => write!(fmt, "color {}", color.name()),
+            UpdateSpec::Icon(ref icon) => write!(fmt, "icon \"{}\"", icon),
+        }
+    }
+}
+
+fn weekday_name(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "Monday",
+        Weekday::Tue => "Tuesday",
+        Weekday::Wed => "Wednesday",
+        Weekday::Thu => "Thursday",
+        Weekday::Fri => "Friday",
+        Weekday::Sat => "Saturday",
+        Weekday::Sun => "Sunday",
+    }
+}
+
+/// A day relative to `now`: "today", "tomorrow", a weekday in the next week,
+/// or the date
+pub fn describe_day<Tz: TimeZone>(day: DateTime<Tz>, now: DateTime<Tz>) -> String {
+    let days = day
+        .date()
+        .naive_local()
+        .signed_duration_since(now.date().naive_local())
+        .num_days();
+    match days {
+        0 => "today".into(),
+        1 => "tomorrow".into(),
+        2..=6 => weekday_name(day.weekday()).into(),
+        _ => day.naive_local().format("%Y-%m-%d").to_string(),
+    }
+}
+
+/// The start of `date` in `tz`, or `None` if it's skipped by a DST change
+fn local_midnight<Tz: TimeZone>(tz: &Tz, date: NaiveDate) -> Option<DateTime<Tz>> {
+    tz.from_local_datetime(&date.and_hms(0, 0, 0)).earliest()
+}
+
+fn is_weekend(day: Weekday) -> bool {
+    day == Weekday::Sat || day == Weekday::Sun
+}
+
+/// The start of the next Monday if `datetime` is on a weekend, otherwise `datetime`
+fn after_weekend<Tz: TimeZone>(datetime: DateTime<Tz>) -> DateTime<Tz> {
+    let date = datetime.date().naive_local();
+    let days = match date.weekday() {
+        Weekday::Sat => 2,
+        Weekday::Sun => 1,
+        _ => return datetime,
+    };
+    local_midnight(&datetime.timezone(), date + Duration::days(days)).unwrap_or(datetime)
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+/// The same day `months` later, or the last day of that month if it's shorter
+fn add_months(date: NaiveDate, months: usize) -> NaiveDate {
+    let month0 = date.month0() as usize + months;
+    let year = date.year() + (month0 / 12) as i32;
+    let month = (month0 % 12) as u32 + 1;
+    (1..=date.day())
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+        .unwrap_or(date)
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct FeedInfo {
     pub name: String,
@@ -32,12 +418,17 @@ pub struct FeedInfo {
     pub update_policies: HashSet<UpdateSpec>,
     pub root: Option<PathBuf>,
     pub command: Option<Vec<String>>,
+    /// The included file the feed is in, or `None` if it's in the config file itself
+    pub source: Option<PathBuf>,
 }
 
 impl FeedInfo {
     pub fn read_feed<R: Read>(&self, reader: &mut R) -> Result<Feed, Error> {
         let mut string = String::new();
         reader.read_to_string(&mut string)?;
+        if is_json(string.as_bytes()) {
+            return Feed::from_json(self, &string);
+        }
 
         let make_error_message = |row: usize, span: Span, msg: &str| -> Error {
             let mut message = format!("Line {}: Error parsing feed \"{}\"\n\n", row, self.name);
@@ -55,37 +446,302 @@ impl FeedInfo {
             Error::Msg(message)
         };
 
-        let events = match parse_events(&string) {
-            Ok(events) => events,
+        match parse_events(&string) {
+            Ok(events) => Ok(self.feed_from_events(events)),
             Err(ParseError::Expected { msg, row, span }) => {
-                return Err(make_error_message(row, span, &msg));
+                Err(make_error_message(row, span, &msg))
             }
-        };
+        }
+    }
 
-        let mut last_read = None;
-        let mut new_comics = 0;
+    fn feed_from_events(&self, events: Vec<FeedEvent>) -> Feed {
         let mut seen_comics = HashSet::new();
         for event in &events {
-            match *event {
-                FeedEvent::ComicUrl(ref url) => {
-                    new_comics += 1;
-                    seen_comics.insert(url.clone());
-                }
-                FeedEvent::Read(date) => {
-                    last_read = Some(date);
-                    new_comics = 0;
-                }
+            if let FeedEvent::ComicUrl(ref url) = *event {
+                seen_comics.insert(url.clone());
             }
         }
+        let progress = Progress::replay(&events);
+        let (new_comics, last_read) = (progress.unread.len(), progress.last_read);
 
-        Ok(Feed {
+        let feed = Feed {
             info: self.clone(),
             new_events: Vec::new(),
             seen_comics,
             last_read,
             new_comics,
             events,
-        })
+        };
+        debug_assert_eq!(feed.check_invariants(), Ok(()));
+        feed
+    }
+
+    /// Compile every pattern in the feed's filters and `@ slug-from`, returning
+    /// a message for each that doesn't. The parser checks them too, so this
+    /// only finds patterns from somewhere else.
+    pub fn check_patterns(&self) -> Vec<String> {
+        let mut patterns = Vec::new();
+        for policy in &self.update_policies {
+            match *policy {
+                UpdateSpec::Filter(_, ref pat) | UpdateSpec::SlugFrom(ref pat) => {
+                    patterns.push(&pat[..])
+                }
+                UpdateSpec::FilterExpr(ref expr) => patterns.extend(expr.patterns()),
+                _ => (),
+            }
+        }
+        patterns.sort();
+        patterns
+            .into_iter()
+            .filter_map(|pat| match Regex::new(pat) {
+                Ok(_) => None,
+                Err(err) => Some(format!("Invalid pattern /{}/: {}", pat, err)),
+            })
+            .collect()
+    }
+
+    /// The feed as a line of config, with its policies in a stable order
+    pub fn to_config_line(&self) -> String {
+        let mut policies: Vec<_> = self
+            .update_policies
+            .iter()
+            .map(|policy| format!(" @ {}", policy))
+            .collect();
+        policies.sort();
+        format!("\"{}\" <{}>{}", self.name, self.url, policies.concat())
+    }
+
+    /// The scheduling policies in English, like "every 7 days, on Saturdays,
+    /// at least 3 new comics". The other policies are left out.
+    pub fn describe_schedule(&self) -> String {
+        let mut every = Vec::new();
+        let mut days = Vec::new();
+        let mut comics = Vec::new();
+        let mut pace = Vec::new();
+        let start = if self.counts_from_threshold() {
+            " after there are enough new comics"
+        } else {
+            ""
+        };
+        for policy in &self.update_policies {
+            match *policy {
+                UpdateSpec::Every(count, unit) => every.push((
+                    (unit, count),
+                    format!("every {} {}{}{}", count, unit.name(), plural(count), start),
+                )),
+                UpdateSpec::EveryFrom(num_days, anchor) => every.push((
+                    (TimeUnit::Day, num_days),
+                    format!("every {} day{} from {}", num_days, plural(num_days), anchor),
+                )),
+                UpdateSpec::On(day) => days.push(day),
+                UpdateSpec::Comics(count) => comics.push((
+                    count,
+                    format!("at least {} new comic{}", count, plural(count)),
+                )),
+                UpdateSpec::ComicsOrAfter(count, num_days) => comics.push((
+                    count,
+                    format!(
+                        "at least {} new comic{} or {} day{} since the last read",
+                        count,
+                        plural(count),
+                        num_days,
+                        plural(num_days)
+                    ),
+                )),
+                UpdateSpec::Pace(count) => {
+                    pace.push(format!("{} comic{} a day", count, plural(count)))
+                }
+                UpdateSpec::Overlap(_)
+                | UpdateSpec::Filter(_, _)
+                | UpdateSpec::FilterExpr(_)
+                | UpdateSpec::OpenAll
+                | UpdateSpec::OpenPage(_)
+                | UpdateSpec::OpenHomepage
+                | UpdateSpec::Silent
+                | UpdateSpec::Insecure
+                | UpdateSpec::RecordFiltered
+                | UpdateSpec::NotifyUpdates
+                | UpdateSpec::Secrets
+                | UpdateSpec::FollowRedirects
+                | UpdateSpec::UrlTemplate(_)
+                | UpdateSpec::SlugFrom(_)
+                | UpdateSpec::Serial
+                | UpdateSpec::PreserveOrder
+                | UpdateSpec::EveryFromThreshold
+                | UpdateSpec::Max(_)
+                | UpdateSpec::SkipWeekends
+                | UpdateSpec::OrderBy(_)
+                | UpdateSpec::Color(_)
+                | UpdateSpec::Icon(_) => (),
+            }
+        }
+        every.sort();
+        comics.sort();
+        days.sort_by_key(Weekday::num_days_from_monday);
+
+        let mut parts: Vec<_> = every.into_iter().map(|(_, part)| part).collect();
+        if !days.is_empty() {
+            let days: Vec<_> = days
+                .into_iter()
+                .map(|day| format!("{}s", weekday_name(day)))
+                .collect();
+            parts.push(format!("on {}", days.join(" or ")));
+        }
+        if self.skips_weekends() {
+            parts.push("not on weekends".into());
+        }
+        parts.extend(comics.into_iter().map(|(_, part)| part));
+        pace.sort();
+        parts.extend(pace);
+        if parts.is_empty() {
+            "as soon as available".into()
+        } else {
+            parts.join(", ")
+        }
+    }
+
+    /// Whether permanent redirects should update this feed's URL in the config
+    pub fn follows_redirects(&self) -> bool {
+        self.update_policies.contains(&UpdateSpec::FollowRedirects)
+    }
+
+    pub fn needs_secrets(&self) -> bool {
+        self.update_policies.contains(&UpdateSpec::Secrets)
+    }
+
+    /// Whether to keep track of the entries the filters leave out
+    pub fn records_filtered(&self) -> bool {
+        self.update_policies.contains(&UpdateSpec::RecordFiltered)
+    }
+
+    /// Whether to keep content hashes, to notice comics that are edited in place
+    pub fn notifies_updates(&self) -> bool {
+        self.update_policies.contains(&UpdateSpec::NotifyUpdates)
+    }
+
+    /// Whether this feed has to be fetched one at a time with the other `@ serial` feeds
+    pub fn is_serial(&self) -> bool {
+        self.update_policies.contains(&UpdateSpec::Serial)
+    }
+
+    /// Whether unread comics are put in order by when they were published
+    pub fn preserves_order(&self) -> bool {
+        self.update_policies.contains(&UpdateSpec::PreserveOrder)
+    }
+
+    /// Whether `@ every` counts from when there were enough comics to read
+    pub fn counts_from_threshold(&self) -> bool {
+        self.update_policies
+            .contains(&UpdateSpec::EveryFromThreshold)
+    }
+
+    /// Whether the feed is held back on weekends, with `@ skip weekends`
+    pub fn skips_weekends(&self) -> bool {
+        self.update_policies.contains(&UpdateSpec::SkipWeekends)
+    }
+
+    /// Whether comics are dated by when their entry was last updated rather than published
+    pub fn orders_by_updated(&self) -> bool {
+        self.update_policies
+            .contains(&UpdateSpec::OrderBy(DateField::Updated))
+    }
+
+    /// How many new comics to show a day with `@ pace`, the slowest if there's more than one
+    pub fn pace(&self) -> Option<usize> {
+        self.update_policies
+            .iter()
+            .filter_map(|policy| match *policy {
+                UpdateSpec::Pace(count) => Some(count),
+                _ => None,
+            })
+            .min()
+    }
+
+    /// Whether to skip TLS certificate verification when fetching this feed
+    pub fn is_insecure(&self) -> bool {
+        self.update_policies.contains(&UpdateSpec::Insecure)
+    }
+
+    /// The `@ color` to show the feed's name in
+    pub fn color(&self) -> Option<Color> {
+        self.update_policies
+            .iter()
+            .find_map(|policy| match *policy {
+                UpdateSpec::Color(color) => Some(color),
+                _ => None,
+            })
+    }
+
+    /// The `@ icon` to show before the feed's name
+    pub fn icon(&self) -> Option<&str> {
+        self.update_policies
+            .iter()
+            .find_map(|policy| match *policy {
+                UpdateSpec::Icon(ref icon) => Some(&icon[..]),
+                _ => None,
+            })
+    }
+
+    /// The name with its icon, and painted in its color if `color` is set
+    pub fn display_name(&self, color: bool) -> String {
+        let name = match self.color() {
+            Some(painted) if color => painted.paint(&self.name),
+            _ => self.name.clone(),
+        };
+        match self.icon() {
+            Some(icon) => format!("{} {}", icon, name),
+            None => name,
+        }
+    }
+
+    /// The single page to open instead of the individual comics, if there is one
+    pub fn landing_page(&self) -> Option<String> {
+        for policy in &self.update_policies {
+            match *policy {
+                UpdateSpec::OpenPage(ref url) => return Some(url.clone()),
+                UpdateSpec::OpenHomepage => {
+                    let url = reqwest::Url::parse(&self.url).ok()?.join("/").ok()?;
+                    return Some(url.into_string());
+                }
+                _ => (),
+            }
+        }
+        None
+    }
+
+    /// The URL to store for an entry: its link, unless there's an `@ url-template`
+    /// to fill in with the slug pulled from its guid (or link) by `@ slug-from`.
+    pub fn comic_url(&self, link: &str, guid: Option<&str>) -> String {
+        let mut template = None;
+        let mut slug_pattern = None;
+        for policy in &self.update_policies {
+            match *policy {
+                UpdateSpec::UrlTemplate(ref pat) => template = Some(pat),
+                UpdateSpec::SlugFrom(ref pat) => slug_pattern = Some(pat),
+                _ => (),
+            }
+        }
+
+        let template = match template {
+            Some(template) => template,
+            None => return link.into(),
+        };
+        let source = guid.unwrap_or(link);
+        let slug = match slug_pattern {
+            // @Performance: Avoid compiling so many regexes
+            Some(pat) => match Regex::new(pat).unwrap().captures(source) {
+                Some(caps) => caps.get(1).or_else(|| caps.get(0)).unwrap().as_str(),
+                None => {
+                    debug!(
+                        "No slug in {:?} for \"{}\", using its link",
+                        source, self.name
+                    );
+                    return link.into();
+                }
+            },
+            None => source,
+        };
+        template.replace("{slug}", slug)
     }
 
     pub fn filter_title(&self, title: &str) -> bool {
@@ -127,14 +783,278 @@ impl FeedInfo {
         }
         true
     }
+
+    /// Whether an entry passes every `@ filter` expression
+    pub fn filter_entry(&self, title: &str, url: &str) -> bool {
+        self.update_policies.iter().all(|policy| match *policy {
+            UpdateSpec::FilterExpr(ref expr) => expr.matches(title, url),
+            _ => true,
+        })
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum FeedEvent {
     ComicUrl(String),
     Read(DateTime<Utc>),
+    /// A partial read, of the unread comics up to and including this one
+    ReadThrough(DateTime<Utc>, String),
+    /// A comic starred to revisit later, which doesn't affect reading
+    Bookmark(String),
+    /// An entry left out by the feed's filters, kept with `@ record-filtered`
+    Filtered(String),
+    /// A read comic that's been shown again for `@ overlap`, so it isn't shown a third time
+    Recapped(String),
+    /// When the feed was last checked, which doesn't affect reading
+    Fetched(DateTime<Utc>),
+    /// A hash of a comic's content, with `@ notify-updates`
+    ContentHash(String, u64),
+    /// When a comic was published, according to the feed
+    Published(String, DateTime<Utc>),
+}
+
+/// Writes the event as its line in the feed file
+impl fmt::Display for FeedEvent {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            FeedEvent::ComicUrl(ref url) => write!(fmt, "<{}>", url),
+            FeedEvent::Read(date) => write!(fmt, "read {}", date.to_rfc3339()),
+            FeedEvent::ReadThrough(date, ref url) => {
+                write!(fmt, "read {} <{}>", date.to_rfc3339(), url)
+            }
+            FeedEvent::Bookmark(ref url) => write!(fmt, "bookmark <{}>", url),
+            FeedEvent::Filtered(ref url) => write!(fmt, "filtered <{}>", url),
+            FeedEvent::Recapped(ref url) => write!(fmt, "recap <{}>", url),
+            FeedEvent::Fetched(date) => write!(fmt, "fetched {}", date.to_rfc3339()),
+            FeedEvent::ContentHash(ref url, hash) => write!(fmt, "hash <{}> {:016x}", url, hash),
+            FeedEvent::Published(ref url, date) => {
+                write!(fmt, "published <{}> {}", url, date.to_rfc3339())
+            }
+        }
+    }
+}
+
+/// How feed files are written, from `--feed-format`. Either is read no matter which is chosen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeedFormat {
+    /// One event per line, which new events are appended to
+    Lines,
+    /// A JSON object with every event, rewritten each time
+    Json,
+}
+
+impl FeedFormat {
+    /// The format a feed file with these contents is already in
+    pub fn of(contents: &[u8]) -> FeedFormat {
+        if is_json(contents) {
+            FeedFormat::Json
+        } else {
+            FeedFormat::Lines
+        }
+    }
+}
+
+/// A feed file in the JSON format, whose events are spelled out field by field
+#[derive(Serialize, Deserialize)]
+struct JsonFeed {
+    version: usize,
+    events: Vec<JsonEvent>,
 }
 
+/// A `FeedEvent` in the JSON format. Dates are RFC 3339 and hashes hexadecimal,
+/// like in the line format.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+enum JsonEvent {
+    Comic { url: String },
+    Read { date: String },
+    ReadThrough { date: String, url: String },
+    Bookmark { url: String },
+    Filtered { url: String },
+    Recap { url: String },
+    Fetched { date: String },
+    Hash { url: String, hash: String },
+    Published { url: String, date: String },
+}
+
+impl From<&FeedEvent> for JsonEvent {
+    fn from(event: &FeedEvent) -> Self {
+        let url = |url: &String| url.clone();
+        match *event {
+            FeedEvent::ComicUrl(ref u) => JsonEvent::Comic { url: url(u) },
+            FeedEvent::Read(date) => JsonEvent::Read {
+                date: date.to_rfc3339(),
+            },
+            FeedEvent::ReadThrough(date, ref u) => JsonEvent::ReadThrough {
+                date: date.to_rfc3339(),
+                url: url(u),
+            },
+            FeedEvent::Bookmark(ref u) => JsonEvent::Bookmark { url: url(u) },
+            FeedEvent::Filtered(ref u) => JsonEvent::Filtered { url: url(u) },
+            FeedEvent::Recapped(ref u) => JsonEvent::Recap { url: url(u) },
+            FeedEvent::Fetched(date) => JsonEvent::Fetched {
+                date: date.to_rfc3339(),
+            },
+            FeedEvent::ContentHash(ref u, hash) => JsonEvent::Hash {
+                url: url(u),
+                hash: format!("{:016x}", hash),
+            },
+            FeedEvent::Published(ref u, date) => JsonEvent::Published {
+                url: url(u),
+                date: date.to_rfc3339(),
+            },
+        }
+    }
+}
+
+impl JsonEvent {
+    fn into_event(self) -> Result<FeedEvent, String> {
+        let date = |date: &str| {
+            DateTime::parse_from_rfc3339(date)
+                .map(|date| date.with_timezone(&Utc))
+                .map_err(|err| format!("invalid date {:?}: {}", date, err))
+        };
+        Ok(match self {
+            JsonEvent::Comic { url } => FeedEvent::ComicUrl(url),
+            JsonEvent::Read { date: ref d } => FeedEvent::Read(date(d)?),
+            JsonEvent::ReadThrough { date: ref d, url } => FeedEvent::ReadThrough(date(d)?, url),
+            JsonEvent::Bookmark { url } => FeedEvent::Bookmark(url),
+            JsonEvent::Filtered { url } => FeedEvent::Filtered(url),
+            JsonEvent::Recap { url } => FeedEvent::Recapped(url),
+            JsonEvent::Fetched { date: ref d } => FeedEvent::Fetched(date(d)?),
+            JsonEvent::Hash { url, hash } => match u64::from_str_radix(&hash, 16) {
+                Ok(hash) => FeedEvent::ContentHash(url, hash),
+                Err(_) => return Err(format!("invalid hash {:?}", hash)),
+            },
+            JsonEvent::Published { url, date: ref d } => FeedEvent::Published(url, date(d)?),
+        })
+    }
+}
+
+/// Whether a feed file's contents are in the JSON format rather than lines
+fn is_json(contents: &[u8]) -> bool {
+    contents.iter().find(|byte| !byte.is_ascii_whitespace()) == Some(&b'{')
+}
+
+/// Which comics have been read, from replaying a feed's history in order
+#[derive(Debug, Default)]
+struct Progress<'a> {
+    read: Vec<&'a str>,
+    unread: Vec<&'a str>,
+    last_read: Option<DateTime<Utc>>,
+    /// When each read happened, and how many comics it covered
+    reads: Vec<(DateTime<Utc>, usize)>,
+    /// The read comics that were already shown again for `@ overlap`
+    recapped: HashSet<&'a str>,
+    /// The latest content hash of each comic
+    hashes: HashMap<&'a str, u64>,
+    /// Read comics whose content has changed since the last read
+    updated: Vec<&'a str>,
+    /// When each comic was published, if the feed said
+    published: HashMap<&'a str, DateTime<Utc>>,
+    /// When each comic was fetched, from the first `fetched` line after it
+    arrived: HashMap<&'a str, DateTime<Utc>>,
+    /// Comics that no `fetched` line has covered yet
+    arriving: Vec<&'a str>,
+}
+
+impl<'a> Progress<'a> {
+    fn replay<I: IntoIterator<Item = &'a FeedEvent>>(events: I) -> Self {
+        Progress::replay_ordered(events, false)
+    }
+
+    /// Like `replay`, but with `by_date` the unread comics are kept in order of
+    /// publication, which partial reads go by too
+    fn replay_ordered<I: IntoIterator<Item = &'a FeedEvent>>(events: I, by_date: bool) -> Self {
+        let mut progress = Progress::default();
+        for event in events {
+            match *event {
+                FeedEvent::ComicUrl(ref url) => {
+                    progress.unread.push(url);
+                    progress.arriving.push(url);
+                }
+                FeedEvent::Fetched(date) => {
+                    for url in progress.arriving.drain(..) {
+                        progress.arrived.insert(url, date);
+                    }
+                }
+                FeedEvent::Read(date) => {
+                    progress.last_read = Some(date);
+                    progress.reads.push((date, progress.unread.len()));
+                    progress.read.append(&mut progress.unread);
+                    progress.updated.clear();
+                }
+                FeedEvent::ReadThrough(date, ref url) => {
+                    progress.last_read = Some(date);
+                    progress.updated.clear();
+                    if by_date {
+                        progress.sort_unread();
+                    }
+                    let count = match progress.unread.iter().position(|x| x == url) {
+                        Some(i) => {
+                            progress.read.extend(progress.unread.drain(..=i));
+                            i + 1
+                        }
+                        None => 0,
+                    };
+                    progress.reads.push((date, count));
+                }
+                FeedEvent::Recapped(ref url) => {
+                    progress.recapped.insert(url);
+                }
+                FeedEvent::Published(ref url, date) => {
+                    progress.published.entry(url).or_insert(date);
+                }
+                FeedEvent::ContentHash(ref url, hash) => {
+                    let changed = match progress.hashes.insert(url, hash) {
+                        Some(old) => old != hash,
+                        None => false,
+                    };
+                    let url = &url[..];
+                    if changed
+                        && !progress.unread.contains(&url)
+                        && !progress.updated.contains(&url)
+                    {
+                        progress.updated.push(url);
+                    }
+                }
+                FeedEvent::Bookmark(_) | FeedEvent::Filtered(_) => (),
+            }
+        }
+        if by_date {
+            progress.sort_unread();
+        }
+        progress
+    }
+
+    /// Put the unread comics with a publication date in order, leaving the
+    /// undated ones where they are
+    fn sort_unread(&mut self) {
+        let published = &self.published;
+        let mut dated: Vec<_> = self
+            .unread
+            .iter()
+            .filter_map(|&url| published.get(url).map(|&date| (date, url)))
+            .collect();
+        dated.sort_by_key(|&(date, _)| date);
+        let mut dated = dated.into_iter().map(|(_, url)| url);
+        for slot in &mut self.unread {
+            if published.contains_key(slot) {
+                *slot = dated.next().unwrap();
+            }
+        }
+    }
+}
+
+/// The cached fields of a `Feed` are always derivable from its history,
+/// `events` followed by the not-yet-written `new_events`:
+///
+/// - `seen_comics` is exactly the set of comic URLs in the history
+/// - no comic URL appears in `new_events` twice, or in both lists
+/// - `new_comics` counts the comics not yet covered by a `Read` or `ReadThrough`
+/// - `last_read` is the date of the last `Read` or `ReadThrough`, if any
+///
+/// See `Feed::check_invariants`.
 #[derive(Clone, Debug, PartialEq)]
 pub struct Feed {
     pub info: FeedInfo,
@@ -146,48 +1066,131 @@ pub struct Feed {
 }
 
 impl Feed {
-    pub fn add_new_comics<S: ::std::borrow::Borrow<String>>(&mut self, urls: &[S]) {
+    /// Add the comics that haven't been seen before, returning how many there were
+    pub fn add_new_comics<S: ::std::borrow::Borrow<String>>(&mut self, urls: &[S]) -> usize {
+        let mut added = 0;
         for url in urls {
             let url = url.borrow();
-            if !self.seen_comics.contains(url) {
+            if self.seen_comics.insert(url.clone()) {
                 self.new_events.push(FeedEvent::ComicUrl(url.clone()));
                 self.new_comics += 1;
+                added += 1;
             }
         }
+        debug_assert_eq!(self.check_invariants(), Ok(()));
+
+        // Silent feeds are only tracked, so their comics are read as soon as they arrive
+        if added > 0 && self.info.update_policies.contains(&UpdateSpec::Silent) {
+            debug!("Marking \"{}\" read because of @silent", self.info.name);
+            self.read();
+        }
+        added
+    }
+
+    /// Record that the comics just added were fetched at `now`, which
+    /// `@ every-from-threshold` needs to know when there were enough of them
+    pub fn record_arrival(&mut self, now: DateTime<Utc>) {
+        if self.info.counts_from_threshold() {
+            self.touch(now);
+        }
+    }
+
+    /// When the unread comics first reached the feed's `@ # new comics`, if
+    /// it counts `@ every` from then and it's known. Otherwise `@ every` counts
+    /// from the last read.
+    fn every_start(&self) -> Option<DateTime<Utc>> {
+        if !self.info.counts_from_threshold() {
+            return None;
+        }
+        let needed = self
+            .info
+            .update_policies
+            .iter()
+            .filter_map(|policy| match *policy {
+                UpdateSpec::Comics(count) | UpdateSpec::ComicsOrAfter(count, _) => Some(count),
+                _ => None,
+            })
+            .max()?;
+        let progress = Progress::replay(self.events.iter().chain(&self.new_events));
+        let url = progress.unread.get(::std::cmp::max(needed, 1) - 1)?;
+        progress.arrived.get(url).cloned()
     }
 
-    pub fn is_scheduled(&self, datetime: DateTime<Local>) -> bool {
+    /// Whether the schedule allows reading at `datetime`, with days counted in its timezone
+    pub fn is_scheduled<Tz: TimeZone>(&self, datetime: DateTime<Tz>) -> bool {
+        if self.info.skips_weekends() && is_weekend(datetime.weekday()) {
+            debug!("Skipping \"{}\" because of @skip weekends", self.info.name);
+            return false;
+        }
         let last_read = match self.last_read {
             Some(last_read) => last_read,
             None => return true,
         };
 
-        let last_read = last_read.with_timezone(&Local);
-        let elapsed_time = datetime.signed_duration_since(last_read);
+        let last_read = last_read.with_timezone(&datetime.timezone());
+        let every_start = self.every_start().map_or(last_read.clone(), |start| {
+            start.with_timezone(&datetime.timezone())
+        });
+        let elapsed_time = datetime.clone().signed_duration_since(every_start.clone());
         let mut day_passed = false;
         let mut day_relevant = false;
 
         for policy in &self.info.update_policies {
             match *policy {
-                UpdateSpec::Every(num_days) => {
+                UpdateSpec::Every(count, unit) => {
                     trace!(
-                        "Rule for \"{}\": @ every {} days (has been {})",
+                        "Rule for \"{}\": @ every {} {}s (has been {} days)",
                         self.info.name,
-                        num_days,
+                        count,
+                        unit.name(),
                         elapsed_time.num_days()
                     );
-                    if elapsed_time.num_days() < num_days as i64 {
+                    let due = match unit {
+                        TimeUnit::Hour => elapsed_time.num_hours() >= count as i64,
+                        TimeUnit::Day => elapsed_time.num_days() >= count as i64,
+                        TimeUnit::Week => elapsed_time.num_weeks() >= count as i64,
+                        // Months aren't all the same length, so count them on the calendar
+                        TimeUnit::Month => {
+                            datetime.date().naive_local()
+                                >= add_months(every_start.date().naive_local(), count)
+                        }
+                    };
+                    if !due {
                         debug!("Skipping \"{}\" because of @every", self.info.name);
                         return false;
                     }
                     trace!("Rule passed!");
                 }
+                UpdateSpec::EveryFrom(num_days, anchor) => {
+                    trace!(
+                        "Rule for \"{}\": @ every {} days from {}",
+                        self.info.name,
+                        num_days,
+                        anchor
+                    );
+                    // Due on the anchor and every num_days after it, regardless of when it was read
+                    let num_days = ::std::cmp::max(num_days, 1) as i64;
+                    let today = datetime.date().naive_local();
+                    let since_anchor = today.signed_duration_since(anchor).num_days();
+                    let last_due = anchor + Duration::days(since_anchor - since_anchor % num_days);
+                    if since_anchor < 0 || last_due <= last_read.date().naive_local() {
+                        debug!("Skipping \"{}\" because of @every from", self.info.name);
+                        return false;
+                    }
+                    trace!("Rule passed!");
+                }
                 UpdateSpec::On(day) => {
                     trace!("Rule for \"{}\": @ on {:?}", self.info.name, day);
                     day_relevant = true;
                     use chrono::Datelike;
+                    // Count calendar days, since a day isn't always 24 hours across DST changes
+                    let days_passed = datetime
+                        .date()
+                        .naive_local()
+                        .signed_duration_since(last_read.date().naive_local())
+                        .num_days();
                     let mut last_day = last_read.weekday();
-                    for _ in 0..elapsed_time.num_days() {
+                    for _ in 0..days_passed {
                         last_day = last_day.succ();
                         if last_day == day {
                             day_passed = true;
@@ -196,10 +1199,39 @@ impl Feed {
                         }
                     }
                 }
-                UpdateSpec::Overlap(_)
-                | UpdateSpec::Comics(_)
+                UpdateSpec::Pace(count) => {
+                    trace!("Rule for \"{}\": @ pace {} per day", self.info.name, count);
+                    // Today's comics have already been read
+                    if datetime.date().naive_local() <= last_read.date().naive_local() {
+                        debug!("Skipping \"{}\" because of @pace", self.info.name);
+                        return false;
+                    }
+                    trace!("Rule passed!");
+                }
+                UpdateSpec::Overlap(_)
+                | UpdateSpec::Comics(_)
+                | UpdateSpec::ComicsOrAfter(_, _)
                 | UpdateSpec::Filter(_, _)
-                | UpdateSpec::OpenAll => (),
+                | UpdateSpec::FilterExpr(_)
+                | UpdateSpec::OpenAll
+                | UpdateSpec::OpenPage(_)
+                | UpdateSpec::OpenHomepage
+                | UpdateSpec::Silent
+                | UpdateSpec::Insecure
+                | UpdateSpec::RecordFiltered
+                | UpdateSpec::NotifyUpdates
+                | UpdateSpec::Secrets
+                | UpdateSpec::FollowRedirects
+                | UpdateSpec::UrlTemplate(_)
+                | UpdateSpec::SlugFrom(_)
+                | UpdateSpec::Serial
+                | UpdateSpec::PreserveOrder
+                | UpdateSpec::EveryFromThreshold
+                | UpdateSpec::Max(_)
+                | UpdateSpec::SkipWeekends
+                | UpdateSpec::OrderBy(_)
+                | UpdateSpec::Color(_)
+                | UpdateSpec::Icon(_) => (),
             }
         }
 
@@ -211,12 +1243,130 @@ impl Feed {
         }
     }
 
-    pub fn is_ready(&self) -> bool {
-        if self.new_comics < 1 {
-            return false;
+    /// The earliest time after `now` that the schedule will allow reading, or
+    /// `None` if it already does, with days counted in `now`'s timezone.
+    /// Whether there will be enough comics isn't known.
+    pub fn next_scheduled<Tz: TimeZone>(&self, now: DateTime<Tz>) -> Option<DateTime<Tz>> {
+        if self.is_scheduled(now.clone()) {
+            return None;
+        }
+        let tz = now.timezone();
+        let last_read = match self.last_read {
+            Some(last_read) => last_read.with_timezone(&tz),
+            // Only a weekend can hold back a feed that's never been read
+            None => return Some(after_weekend(now)),
+        };
+        let read_day = last_read.date().naive_local();
+        let every_start = self
+            .every_start()
+            .map_or(last_read, |start| start.with_timezone(&tz));
+
+        // Each policy only ever goes from holding the feed back to allowing it, so
+        // it's due once the last @ every is, and the first @ on day after that
+        let mut due = now.clone();
+        let mut on_days = Vec::new();
+        for policy in &self.info.update_policies {
+            let policy_due = match *policy {
+                UpdateSpec::Every(count, unit) => match unit {
+                    TimeUnit::Hour => every_start
+                        .clone()
+                        .checked_add_signed(Duration::hours(count as i64)),
+                    TimeUnit::Day => every_start
+                        .clone()
+                        .checked_add_signed(Duration::days(count as i64)),
+                    TimeUnit::Week => every_start
+                        .clone()
+                        .checked_add_signed(Duration::weeks(count as i64)),
+                    TimeUnit::Month => {
+                        local_midnight(&tz, add_months(every_start.date().naive_local(), count))
+                    }
+                },
+                UpdateSpec::EveryFrom(num_days, anchor) => {
+                    let num_days = ::std::cmp::max(num_days, 1) as i64;
+                    let since_anchor = read_day.signed_duration_since(anchor).num_days();
+                    if since_anchor < 0 {
+                        local_midnight(&tz, anchor)
+                    } else {
+                        let periods = since_anchor / num_days + 1;
+                        local_midnight(&tz, anchor + Duration::days(periods * num_days))
+                    }
+                }
+                UpdateSpec::On(day) => {
+                    on_days.push(day);
+                    None
+                }
+                UpdateSpec::Pace(_) => local_midnight(&tz, read_day + Duration::days(1)),
+                UpdateSpec::Overlap(_)
+                | UpdateSpec::Comics(_)
+                | UpdateSpec::ComicsOrAfter(_, _)
+                | UpdateSpec::Filter(_, _)
+                | UpdateSpec::FilterExpr(_)
+                | UpdateSpec::OpenAll
+                | UpdateSpec::OpenPage(_)
+                | UpdateSpec::OpenHomepage
+                | UpdateSpec::Silent
+                | UpdateSpec::Insecure
+                | UpdateSpec::RecordFiltered
+                | UpdateSpec::NotifyUpdates
+                | UpdateSpec::Secrets
+                | UpdateSpec::FollowRedirects
+                | UpdateSpec::UrlTemplate(_)
+                | UpdateSpec::SlugFrom(_)
+                | UpdateSpec::Serial
+                | UpdateSpec::PreserveOrder
+                | UpdateSpec::EveryFromThreshold
+                | UpdateSpec::Max(_)
+                | UpdateSpec::SkipWeekends
+                | UpdateSpec::OrderBy(_)
+                | UpdateSpec::Color(_)
+                | UpdateSpec::Icon(_) => None,
+            };
+            if let Some(policy_due) = policy_due {
+                due = ::std::cmp::max(due, policy_due);
+            }
         }
 
-        if !self.is_scheduled(Local::now()) {
+        let on_due = on_days
+            .into_iter()
+            .filter_map(|day| {
+                let next = (1..=7)
+                    .map(|offset| read_day + Duration::days(offset))
+                    .find(|date| date.weekday() == day)?;
+                local_midnight(&tz, next)
+            })
+            .min();
+        if let Some(on_due) = on_due {
+            due = ::std::cmp::max(due, on_due);
+        }
+        if self.info.skips_weekends() {
+            due = after_weekend(due);
+        }
+        if due > now {
+            Some(due)
+        } else {
+            None
+        }
+    }
+
+    /// Whether the feed should be read at `now`: it has enough new comics, and
+    /// its schedule allows it.
+    pub fn is_ready<Tz: TimeZone>(&self, now: DateTime<Tz>) -> bool {
+        let waited = self
+            .last_read
+            .map(|last_read| now.clone().signed_duration_since(last_read));
+        self.enough_comics_after(waited) && self.is_scheduled(now)
+    }
+
+    /// Whether there are enough new comics to read, regardless of the schedule.
+    /// This is all that's checked with `--catch-up`, so the timeout of
+    /// `@ # new comics or after # days` doesn't apply.
+    pub fn has_enough_comics(&self) -> bool {
+        self.enough_comics_after(None)
+    }
+
+    /// `waited` is how long it's been since the feed was last read, if ever
+    fn enough_comics_after(&self, waited: Option<Duration>) -> bool {
+        if self.new_comics < 1 {
             return false;
         }
 
@@ -235,37 +1385,518 @@ impl Feed {
                     }
                     trace!("Rule passed!");
                 }
-                UpdateSpec::Every(_)
+                UpdateSpec::ComicsOrAfter(num_comics, num_days) => {
+                    trace!(
+                        "Rule for \"{}\": @ {} new comics or after {} days (has {})",
+                        self.info.name,
+                        num_comics,
+                        num_days,
+                        self.new_comics
+                    );
+                    let timed_out = match waited {
+                        Some(waited) => waited.num_days() >= num_days as i64,
+                        None => false,
+                    };
+                    if self.new_comics < num_comics && !timed_out {
+                        debug!(
+                            "Skipping \"{}\" because of @comics or after",
+                            self.info.name
+                        );
+                        return false;
+                    }
+                    trace!("Rule passed!");
+                }
+                UpdateSpec::Every(_, _)
+                | UpdateSpec::EveryFrom(_, _)
                 | UpdateSpec::On(_)
                 | UpdateSpec::Overlap(_)
                 | UpdateSpec::Filter(_, _)
-                | UpdateSpec::OpenAll => (),
+                | UpdateSpec::FilterExpr(_)
+                | UpdateSpec::OpenAll
+                | UpdateSpec::OpenPage(_)
+                | UpdateSpec::OpenHomepage
+                | UpdateSpec::Silent
+                | UpdateSpec::Insecure
+                | UpdateSpec::RecordFiltered
+                | UpdateSpec::NotifyUpdates
+                | UpdateSpec::Secrets
+                | UpdateSpec::FollowRedirects
+                | UpdateSpec::UrlTemplate(_)
+                | UpdateSpec::SlugFrom(_)
+                | UpdateSpec::Serial
+                | UpdateSpec::PreserveOrder
+                | UpdateSpec::EveryFromThreshold
+                | UpdateSpec::Max(_)
+                | UpdateSpec::SkipWeekends
+                | UpdateSpec::OrderBy(_)
+                | UpdateSpec::Pace(_)
+                | UpdateSpec::Color(_)
+                | UpdateSpec::Icon(_) => (),
             }
         }
         true
     }
 
     pub fn read(&mut self) {
-        self.new_events.push(FeedEvent::Read(Utc::now()))
+        self.mark_recapped();
+        let now = Utc::now();
+        self.new_events.push(FeedEvent::Read(now));
+        self.last_read = Some(now);
+        self.new_comics = 0;
+        debug_assert_eq!(self.check_invariants(), Ok(()));
+    }
+
+    /// How many new comics `@ pace` allows at `now`: its count for each calendar
+    /// day since the last read, or for one day if the feed's never been read
+    fn paced_count<Tz: TimeZone>(&self, now: DateTime<Tz>) -> Option<usize> {
+        let pace = self.info.pace()?;
+        let days = match self.last_read {
+            Some(last_read) => {
+                let read_day = last_read
+                    .with_timezone(&now.timezone())
+                    .date()
+                    .naive_local();
+                let days = now.date().naive_local().signed_duration_since(read_day);
+                ::std::cmp::max(days.num_days(), 0) as usize
+            }
+            None => 1,
+        };
+        Some(pace.saturating_mul(days))
+    }
+
+    /// Mark the reading list as of `now` read. That's every unread comic, except
+    /// with `@ pace`, which leaves the ones it held back for another day.
+    pub fn read_list<Tz: TimeZone>(&mut self, now: DateTime<Tz>) {
+        match self.paced_count(now) {
+            Some(allowed) => self.read_first(allowed),
+            None => self.read(),
+        }
+    }
+
+    /// Mark read through the last unread comic in `opened`, when `--max-tabs`
+    /// cuts the open list short. Recap and updated comics aren't unread, so
+    /// opening only those reads nothing, while older comics left out by
+    /// `@ max` are read along with it, as `read_list` would.
+    pub fn read_opened(&mut self, opened: &[String]) {
+        let progress = self.progress();
+        let count = opened
+            .iter()
+            .filter_map(|url| progress.unread.iter().position(|unread| unread == url))
+            .max()
+            .map_or(0, |last| last + 1);
+        self.read_first(count);
+    }
+
+    /// Mark only the oldest `count` unread comics as read, leaving the rest
+    /// to count towards the feed's policies next time.
+    pub fn read_first(&mut self, count: usize) {
+        if count >= self.new_comics {
+            return self.read();
+        } else if count == 0 {
+            return;
+        }
+        self.mark_recapped();
+        let now = Utc::now();
+        let progress = self.progress();
+        let last = progress.unread[count - 1].to_string();
+        self.new_events.push(FeedEvent::ReadThrough(now, last));
+        self.last_read = Some(now);
+        self.new_comics -= count;
+        debug_assert_eq!(self.check_invariants(), Ok(()));
+    }
+
+    /// Whether the comic at `url` is already in the history
+    pub fn has_seen(&self, url: &str) -> bool {
+        self.seen_comics.contains(url)
+    }
+
+    /// How many comics haven't been read yet
+    pub fn unread_count(&self) -> usize {
+        self.new_comics
+    }
+
+    /// The feed for `--json` as of `now`, with `ready` worked out by the caller
+    /// since it depends on `--catch-up` and the time zone
+    pub fn report<Tz: TimeZone>(&self, ready: bool, now: DateTime<Tz>) -> FeedReport {
+        let list = self.reading_list_at(now.clone());
+        let mut dated = self.reading_list_with_dates(now).into_iter();
+        let mut comics = |count: usize| {
+            dated
+                .by_ref()
+                .take(count)
+                .map(|(url, date)| ReportComic {
+                    url,
+                    published: date.map(|date| date.to_rfc3339()),
+                })
+                .collect()
+        };
+        FeedReport {
+            name: self.info.name.clone(),
+            url: self.info.url.clone(),
+            unread_count: self.new_comics,
+            ready,
+            reading_list: ReportList {
+                recap: comics(list.recap.len()),
+                updated: comics(list.updated.len()),
+                new: comics(list.new.len()),
+            },
+            color: self.info.color().map(Color::name),
+            icon: self.info.icon().map(String::from),
+        }
+    }
+
+    /// How many unread comics were published at or after `since`. Comics the
+    /// feed didn't give a date for aren't counted.
+    pub fn unread_since(&self, since: DateTime<Utc>) -> usize {
+        let progress = Progress::replay(self.events.iter().chain(&self.new_events));
+        progress
+            .unread
+            .iter()
+            .filter(|&url| matches!(progress.published.get(url), Some(&date) if date >= since))
+            .count()
+    }
+
+    /// A summary for `feedburst list`, like "3 unread, last read 2024-01-10, ready"
+    pub fn describe_status<Tz: TimeZone>(&self, now: DateTime<Tz>) -> String {
+        let last_read = match self.last_read {
+            Some(last_read) => format!(
+                "last read {}",
+                last_read
+                    .with_timezone(&now.timezone())
+                    .naive_local()
+                    .format("%Y-%m-%d")
+            ),
+            None => "never read".into(),
+        };
+        let ready = if self.is_ready(now) {
+            "ready"
+        } else {
+            "waiting"
+        };
+        format!("{} unread, {}, {}", self.new_comics, last_read, ready)
+    }
+
+    /// The `--porcelain` line for this feed: its name, URL, and number of unread
+    /// comics, separated by tabs. Scripts rely on this, so it must not change.
+    pub fn porcelain_line(&self) -> String {
+        let clean = |text: &str| text.replace(&['\t', '\n', '\r'][..], " ");
+        format!(
+            "{}\t{}\t{}",
+            clean(&self.info.name),
+            clean(&self.info.url),
+            self.new_comics
+        )
+    }
+
+    /// Record the content hashes of the feed's comics, returning how many read
+    /// comics changed and will be shown again as updated
+    pub fn add_content_hashes(&mut self, hashes: &[(String, u64)]) -> usize {
+        let (mut known, before): (HashMap<String, u64>, usize) = {
+            let progress = Progress::replay(self.events.iter().chain(&self.new_events));
+            let known = progress
+                .hashes
+                .iter()
+                .map(|(&url, &hash)| (url.to_string(), hash))
+                .collect();
+            (known, progress.updated.len())
+        };
+        for (url, hash) in hashes {
+            // Filtered entries aren't tracked
+            if !self.seen_comics.contains(url) || known.get(url) == Some(hash) {
+                continue;
+            }
+            known.insert(url.clone(), *hash);
+            self.new_events
+                .push(FeedEvent::ContentHash(url.clone(), *hash));
+        }
+        let progress = Progress::replay(self.events.iter().chain(&self.new_events));
+        progress.updated.len().saturating_sub(before)
+    }
+
+    /// Record when comics were published, for the ones without a date yet
+    pub fn add_publication_dates(&mut self, dates: &[(String, DateTime<Utc>)]) {
+        let mut known: HashSet<String> = {
+            let progress = Progress::replay(self.events.iter().chain(&self.new_events));
+            progress.published.keys().map(|&url| url.into()).collect()
+        };
+        for (url, date) in dates {
+            if self.seen_comics.contains(url) && known.insert(url.clone()) {
+                self.new_events
+                    .push(FeedEvent::Published(url.clone(), *date));
+            }
+        }
+    }
+
+    /// The reading list as of `now` in order, with when each comic was
+    /// published if it's known
+    pub fn reading_list_with_dates<Tz: TimeZone>(
+        &self,
+        now: DateTime<Tz>,
+    ) -> Vec<(String, Option<DateTime<Utc>>)> {
+        let progress = Progress::replay(self.events.iter().chain(&self.new_events));
+        self.reading_list_at(now)
+            .into_urls()
+            .into_iter()
+            .map(|url| {
+                let date = progress.published.get(&url[..]).cloned();
+                (url, date)
+            })
+            .collect()
+    }
+
+    /// Like `ReadingList::describe`, with when the oldest new comic came out if
+    /// it's known: "3 comics since Tuesday"
+    pub fn describe_reading_list<Tz: TimeZone>(&self, now: DateTime<Tz>) -> String {
+        let items = self.reading_list_at(now.clone());
+        let dated = self.reading_list_with_dates(now.clone());
+        let oldest = dated[dated.len() - items.new.len()..]
+            .iter()
+            .filter_map(|&(_, date)| date)
+            .min();
+        let oldest = match oldest {
+            Some(oldest) => oldest.with_timezone(&now.timezone()),
+            None => return items.describe(),
+        };
+        let since = if now.signed_duration_since(oldest.clone()) < Duration::days(7) {
+            weekday_name(oldest.weekday()).to_string()
+        } else {
+            oldest.naive_local().format("%Y-%m-%d").to_string()
+        };
+        format!("{} since {}", items.describe(), since)
+    }
+
+    /// Record that the feed was checked at `now`, without fetching anything
+    pub fn touch(&mut self, now: DateTime<Utc>) {
+        self.new_events.push(FeedEvent::Fetched(now));
+    }
+
+    /// When the feed was last checked, if it ever was
+    pub fn last_fetched(&self) -> Option<DateTime<Utc>> {
+        self.events
+            .iter()
+            .chain(&self.new_events)
+            .rev()
+            .find_map(|event| match *event {
+                FeedEvent::Fetched(date) => Some(date),
+                _ => None,
+            })
+    }
+
+    /// The feed's history replayed, in the order its policies ask for
+    fn progress(&self) -> Progress<'_> {
+        Progress::replay_ordered(
+            self.events.iter().chain(&self.new_events),
+            self.info.preserves_order(),
+        )
+    }
+
+    /// Remember that the reading list's recap was shown, as part of reading it
+    fn mark_recapped(&mut self) {
+        let recap = self.get_reading_list().recap;
+        self.new_events
+            .extend(recap.into_iter().map(FeedEvent::Recapped));
+    }
+
+    /// Every comic in the feed, oldest first
+    pub fn all_comics(&self) -> Vec<&str> {
+        self.events
+            .iter()
+            .chain(&self.new_events)
+            .filter_map(|event| match *event {
+                FeedEvent::ComicUrl(ref url) => Some(&url[..]),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// When the feed was read, and how many comics were read each time
+    pub fn read_history(&self) -> Vec<(DateTime<Utc>, usize)> {
+        Progress::replay(self.events.iter().chain(&self.new_events)).reads
+    }
+
+    /// Record entries that were filtered out, skipping ones already recorded
+    pub fn add_filtered<S: ::std::borrow::Borrow<String>>(&mut self, urls: &[S]) -> usize {
+        let mut known: HashSet<String> = self.filtered().into_iter().map(String::from).collect();
+        let mut added = 0;
+        for url in urls {
+            let url = url.borrow();
+            if known.insert(url.clone()) {
+                self.new_events.push(FeedEvent::Filtered(url.clone()));
+                added += 1;
+            }
+        }
+        added
+    }
+
+    /// The entries that were filtered out, oldest first
+    pub fn filtered(&self) -> Vec<&str> {
+        self.events
+            .iter()
+            .chain(&self.new_events)
+            .filter_map(|event| match *event {
+                FeedEvent::Filtered(ref url) => Some(&url[..]),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Star a comic to come back to, returning false if it already was
+    pub fn bookmark(&mut self, url: &str) -> bool {
+        if self.bookmarks().contains(&url) {
+            return false;
+        }
+        self.new_events.push(FeedEvent::Bookmark(url.into()));
+        true
+    }
+
+    /// The bookmarked comics, in the order they were bookmarked
+    pub fn bookmarks(&self) -> Vec<&str> {
+        self.events
+            .iter()
+            .chain(&self.new_events)
+            .filter_map(|event| match *event {
+                FeedEvent::Bookmark(ref url) => Some(&url[..]),
+                _ => None,
+            })
+            .collect()
     }
 
     pub fn write_changes<W: Write + Seek>(&mut self, writer: &mut W) -> io::Result<()> {
         writer.seek(io::SeekFrom::End(0))?;
         for event in &self.new_events {
-            match *event {
-                FeedEvent::ComicUrl(ref url) => writeln!(writer, "<{}>", url)?,
-                FeedEvent::Read(date) => writeln!(writer, "read {}", date.to_rfc3339())?,
-            }
+            writeln!(writer, "{}", event)?;
         }
         trace!(
             "Wrote changes for \"{}\", new events moved to old",
             self.info.name
         );
         self.events.append(&mut self.new_events);
+        debug_assert_eq!(self.check_invariants(), Ok(()));
+        Ok(())
+    }
+
+    /// Like `write_changes`, but writes a copy of the file at `path` with the new
+    /// events on the end and renames it into place, so the file is never left with
+    /// a partial event if feedburst is killed or the disk fills up part way.
+    /// JSON can't be appended to, so in that format (or to switch formats) the
+    /// whole history is written instead. With no `format`, the file stays in
+    /// the one it's in.
+    pub fn persist(&mut self, path: &Path, format: Option<FeedFormat>) -> io::Result<()> {
+        if self.new_events.is_empty() {
+            return Ok(());
+        }
+        let mut contents = match fs::read(path) {
+            Ok(contents) => contents,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err),
+        };
+        let format = format.unwrap_or_else(|| FeedFormat::of(&contents));
+        if format == FeedFormat::Json || is_json(&contents) {
+            write_atomically(path, |file| self.write_format(file, format))?;
+            self.events.append(&mut self.new_events);
+            return Ok(());
+        }
+        if !contents.is_empty() && !contents.ends_with(b"\n") {
+            contents.push(b'\n');
+        }
+        let mut contents = io::Cursor::new(contents);
+        self.write_changes(&mut contents)?;
+        write_atomically(path, |file| file.write_all(contents.get_ref()))
+    }
+
+    /// Drop repeated comics, bookmarks, and filtered entries from the history,
+    /// keeping the first of each so reads still cover the same comics. Returns
+    /// how many events were removed; write the result with `write_all`.
+    pub fn repair(&mut self) -> usize {
+        let mut seen = HashSet::new();
+        let before = self.events.len() + self.new_events.len();
+        let mut events: Vec<_> = self.events.drain(..).collect();
+        events.append(&mut self.new_events);
+        self.events = events
+            .into_iter()
+            .filter(|event| match *event {
+                FeedEvent::ComicUrl(ref url) => seen.insert(("comic", url.clone())),
+                FeedEvent::Bookmark(ref url) => seen.insert(("bookmark", url.clone())),
+                FeedEvent::Filtered(ref url) => seen.insert(("filtered", url.clone())),
+                FeedEvent::Recapped(ref url) => seen.insert(("recap", url.clone())),
+                FeedEvent::Published(ref url, _) => seen.insert(("published", url.clone())),
+                FeedEvent::Read(_)
+                | FeedEvent::ReadThrough(_, _)
+                | FeedEvent::Fetched(_)
+                | FeedEvent::ContentHash(_, _) => true,
+            })
+            .collect();
+
+        let progress = Progress::replay(&self.events);
+        self.new_comics = progress.unread.len();
+        self.last_read = progress.last_read;
+        self.seen_comics = self.all_comics().into_iter().map(String::from).collect();
+        debug_assert_eq!(self.check_invariants(), Ok(()));
+        before - self.events.len()
+    }
+
+    /// Write the whole history in the current format, for a fresh copy of the file
+    pub fn write_all<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "version {}", FEED_FORMAT_VERSION)?;
+        for event in self.events.iter().chain(&self.new_events) {
+            writeln!(writer, "{}", event)?;
+        }
         Ok(())
     }
 
-    pub fn get_reading_list(&self) -> Vec<String> {
+    /// Write the whole history as a JSON object, with the same events as `write_all`
+    pub fn export_json<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let feed = JsonFeed {
+            version: FEED_FORMAT_VERSION,
+            events: self
+                .events
+                .iter()
+                .chain(&self.new_events)
+                .map(JsonEvent::from)
+                .collect(),
+        };
+        serde_json::to_writer_pretty(&mut *writer, &feed)?;
+        writeln!(writer)
+    }
+
+    /// Load a feed written by `export_json`
+    pub fn from_json(info: &FeedInfo, text: &str) -> Result<Feed, Error> {
+        let error = |msg: String| {
+            Error::Msg(format!(
+                "Error parsing feed \"{}\" as JSON: {}",
+                info.name, msg
+            ))
+        };
+        let feed: JsonFeed = serde_json::from_str(text).map_err(|err| error(err.to_string()))?;
+        if feed.version > FEED_FORMAT_VERSION {
+            return Err(error(format!(
+                "expected a feed format version of at most {} (was this file written by a newer feedburst?)",
+                FEED_FORMAT_VERSION
+            )));
+        }
+        let events = feed
+            .events
+            .into_iter()
+            .map(JsonEvent::into_event)
+            .collect::<Result<_, _>>()
+            .map_err(error)?;
+        Ok(info.feed_from_events(events))
+    }
+
+    /// Write the whole history in either format
+    pub fn write_format<W: Write>(&self, writer: &mut W, format: FeedFormat) -> io::Result<()> {
+        match format {
+            FeedFormat::Lines => self.write_all(writer),
+            FeedFormat::Json => self.export_json(writer),
+        }
+    }
+
+    pub fn get_reading_list(&self) -> ReadingList {
+        self.reading_list_at(Local::now())
+    }
+
+    /// The reading list as of `now`, which only matters with `@ pace`
+    pub fn reading_list_at<Tz: TimeZone>(&self, now: DateTime<Tz>) -> ReadingList {
         let mut additional = 0;
         for policy in &self.info.update_policies {
             if let UpdateSpec::Overlap(n) = *policy {
@@ -277,31 +1908,1579 @@ impl Feed {
             self.info.name,
             additional
         );
-        let mut finishing = false;
-        let mut result = Vec::new();
-        for event in self.events.iter().chain(&self.new_events).rev() {
-            match *event {
-                FeedEvent::ComicUrl(ref url) => {
-                    if finishing {
-                        if additional == 0 {
-                            break;
-                        }
-                        additional -= 1;
-                    }
-                    result.push(url.clone());
-                }
-                FeedEvent::Read(when) => {
-                    finishing = true;
-                    trace!("Read at {}", when);
-                }
-            }
+        let progress = self.progress();
+        let recap_start = progress.read.len().saturating_sub(additional);
+        let mut result = ReadingList {
+            // Comics from an earlier recap aren't shown again, even if they're still in range
+            recap: progress.read[recap_start..]
+                .iter()
+                .filter(|url| !progress.recapped.contains(*url))
+                .filter(|url| !progress.updated.contains(*url))
+                .map(|&url| url.to_string())
+                .collect(),
+            updated: progress
+                .updated
+                .iter()
+                .map(|&url| url.to_string())
+                .collect(),
+            new: progress.unread.iter().map(|&url| url.to_string()).collect(),
+        };
+        if let Some(allowed) = self.paced_count(now) {
+            result.new.truncate(allowed);
+        }
+        let max = self
+            .info
+            .update_policies
+            .iter()
+            .filter_map(|policy| match *policy {
+                UpdateSpec::Max(count) => Some(count),
+                _ => None,
+            })
+            .min();
+        if let Some(max) = max {
+            result.keep_last(max);
         }
         debug!(
             "Reading list for \"{}\" has {}",
             self.info.name,
-            result.len()
+            result.describe()
         );
-        result.reverse();
         result
     }
+
+    /// The URLs to open for the reading list. A landing page is always opened
+    /// on its own, whatever the `mode`.
+    #[cfg(test)]
+    pub fn get_open_list(&self, mode: OpenMode) -> Vec<String> {
+        self.open_list_at(mode, Local::now())
+    }
+
+    /// The comics to open as of `now`, which only matters with `@ pace`
+    pub fn open_list_at<Tz: TimeZone>(&self, mode: OpenMode, now: DateTime<Tz>) -> Vec<String> {
+        let mut items = self.reading_list_at(now).into_urls();
+        if items.is_empty() {
+            return items;
+        }
+
+        let open_all = match mode {
+            OpenMode::Policy => self.info.update_policies.contains(&UpdateSpec::OpenAll),
+            OpenMode::FirstOnly => false,
+            OpenMode::All => true,
+        };
+        if let Some(page) = self.info.landing_page() {
+            vec![page]
+        } else if open_all {
+            // Open all the comics instead of just the earliest one
+            items
+        } else {
+            items.truncate(1);
+            items
+        }
+    }
+
+    /// Verify the invariants documented on `Feed`, describing the first one that's broken.
+    pub fn check_invariants(&self) -> Result<(), String> {
+        let mut comics = HashSet::new();
+        for event in &self.events {
+            if let FeedEvent::ComicUrl(ref url) = *event {
+                comics.insert(url);
+            }
+        }
+        for event in &self.new_events {
+            if let FeedEvent::ComicUrl(ref url) = *event {
+                if !comics.insert(url) {
+                    return Err(format!("new comic <{}> was already in the feed", url));
+                }
+            }
+        }
+        let progress = Progress::replay(self.events.iter().chain(&self.new_events));
+        let (new_comics, last_read) = (progress.unread.len(), progress.last_read);
+
+        if comics.len() != self.seen_comics.len()
+            || comics.iter().any(|url| !self.seen_comics.contains(*url))
+        {
+            return Err("seen_comics doesn't match the comics in the feed".into());
+        }
+        if new_comics != self.new_comics {
+            return Err(format!(
+                "new_comics is {}, but there are {} comics since the last read",
+                self.new_comics, new_comics,
+            ));
+        }
+        if last_read != self.last_read {
+            return Err(format!(
+                "last_read is {:?}, but the last read event is {:?}",
+                self.last_read, last_read,
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The comics to read, oldest first. `recap` holds already-read comics shown again
+/// because of `@ overlap`, and `updated` ones that changed since they were read
+/// with `@ notify-updates`, which both come before the `new` ones.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReadingList {
+    pub recap: Vec<String>,
+    pub updated: Vec<String>,
+    pub new: Vec<String>,
+}
+
+impl ReadingList {
+    pub fn len(&self) -> usize {
+        self.recap.len() + self.updated.len() + self.new.len()
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop the oldest comics until there are at most `max`, starting with the recap
+    fn keep_last(&mut self, max: usize) {
+        let mut excess = self.len().saturating_sub(max);
+        for list in [&mut self.recap, &mut self.updated, &mut self.new] {
+            let dropped = ::std::cmp::min(excess, list.len());
+            list.drain(..dropped);
+            excess -= dropped;
+        }
+    }
+
+    pub fn into_urls(self) -> Vec<String> {
+        let mut urls = self.recap;
+        urls.extend(self.updated);
+        urls.extend(self.new);
+        urls
+    }
+
+    /// Like "3 comics", or "2 recap + 1 updated + 3 new" when there's more than new comics
+    pub fn describe(&self) -> String {
+        if !self.updated.is_empty() {
+            let mut parts = Vec::new();
+            if !self.recap.is_empty() {
+                parts.push(format!("{} recap", self.recap.len()));
+            }
+            parts.push(format!("{} updated", self.updated.len()));
+            parts.push(format!("{} new", self.new.len()));
+            parts.join(" + ")
+        } else if self.recap.is_empty() {
+            let plural = if self.new.len() == 1 {
+                "comic"
+            } else {
+                "comics"
+            };
+            format!("{} {}", self.new.len(), plural)
+        } else {
+            format!("{} recap + {} new", self.recap.len(), self.new.len())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::scratch::ScratchDir;
+    use chrono::Local;
+    use std::iter::FromIterator;
+
+    fn make_feed(events: &str, policies: Vec<UpdateSpec>) -> Feed {
+        let info = FeedInfo {
+            name: "Test Comic".into(),
+            url: "http://example.com/feed".into(),
+            update_policies: HashSet::from_iter(policies),
+            root: None,
+            command: None,
+            source: None,
+        };
+        info.read_feed(&mut events.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_duplicates_in_one_batch() {
+        let mut feed = make_feed("<http://example.com/1>\n", vec![]);
+        let added = feed.add_new_comics(&[
+            "http://example.com/1".to_string(),
+            "http://example.com/2".to_string(),
+            "http://example.com/2".to_string(),
+        ]);
+        assert_eq!(added, 1);
+        assert_eq!(
+            feed.get_reading_list().into_urls(),
+            vec!["http://example.com/1", "http://example.com/2"],
+        );
+        assert_eq!(feed.new_comics, 2);
+    }
+
+    #[test]
+    fn test_read_comic_reappears() {
+        let url = |num: usize| format!("http://example.com/{}", num);
+        let mut feed = make_feed("", vec![]);
+        assert_eq!(feed.add_new_comics(&[url(1), url(2)]), 2);
+        feed.read();
+
+        // The feed drops the first comic for a while, then puts it back after a
+        // few more reads, and the history is reloaded in between
+        assert_eq!(feed.add_new_comics(&[url(2), url(3)]), 1);
+        feed.read();
+        let mut history = Vec::new();
+        feed.write_all(&mut history).unwrap();
+        let mut feed = feed.info.read_feed(&mut &history[..]).unwrap();
+        assert_eq!(feed.add_new_comics(&[url(1), url(2), url(3), url(4)]), 1);
+        assert_eq!(feed.new_comics, 1);
+        assert_eq!(feed.get_reading_list().into_urls(), vec![url(4)]);
+        assert_eq!(feed.all_comics().len(), 4);
+    }
+
+    #[test]
+    fn test_read_before_comics() {
+        let url = |num: usize| format!("http://example.com/{}", num);
+        // Hand-edited, so the reads come before any comics
+        let history = "read 2017-07-17T03:21:21+00:00
+read 2017-07-18T03:21:21+00:00 <http://example.com/2>
+<http://example.com/1>
+<http://example.com/2>
+<http://example.com/3>
+";
+        for policies in [
+            vec![],
+            vec![UpdateSpec::Overlap(1)],
+            vec![UpdateSpec::Max(5)],
+            vec![UpdateSpec::PreserveOrder],
+        ] {
+            let mut feed = make_feed(history, policies.clone());
+            assert_eq!(feed.check_invariants(), Ok(()), "{:?}", policies);
+            assert_eq!(feed.unread_count(), 3, "{:?}", policies);
+            assert_eq!(
+                feed.get_reading_list().into_urls(),
+                vec![url(1), url(2), url(3)],
+                "{:?}",
+                policies
+            );
+            // The read through a comic that hadn't arrived yet didn't read it
+            assert_eq!(feed.read_history().len(), 2);
+            assert_eq!(
+                feed.last_read,
+                Some(Utc.ymd(2017, 7, 18).and_hms(3, 21, 21))
+            );
+
+            feed.read_first(2);
+            assert_eq!(feed.get_reading_list().new, vec![url(3)]);
+            feed.add_new_comics(&[url(4)]);
+            feed.read();
+            assert_eq!(feed.unread_count(), 0);
+            assert!(feed.get_reading_list().new.is_empty());
+            assert_eq!(feed.check_invariants(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_fetched_comics_readable_without_reload() {
+        let mut feed = make_feed(
+            "<http://example.com/1>\nread 2017-07-17T03:21:21+00:00\n",
+            vec![],
+        );
+        feed.add_new_comics(&["http://example.com/2".to_string()]);
+        let mut file = io::Cursor::new(Vec::new());
+        feed.write_changes(&mut file).unwrap();
+        assert_eq!(file.into_inner(), b"<http://example.com/2>\n");
+
+        assert!(
+            feed.is_ready(Local::now()),
+            "the written comic still counts as new"
+        );
+        assert_eq!(feed.get_reading_list().new, vec!["http://example.com/2"]);
+    }
+
+    #[test]
+    fn test_persist() {
+        let dir = ScratchDir::new("persist");
+        let path = dir.join("Test Comic.feed");
+        // Missing its last newline, as if an older version was cut off after the URL
+        let before =
+            "<http://example.com/1>\nread 2017-07-17T03:21:21+00:00\n<http://example.com/2>";
+        fs::write(&path, before).unwrap();
+
+        // Killed part way through writing, which leaves the old file as it was
+        let killed = write_atomically(&path, |file| {
+            file.write_all(b"<http://example.com/2>\nread 2017-")?;
+            Err(io::Error::new(io::ErrorKind::WriteZero, "killed"))
+        });
+        assert!(killed.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), before);
+        assert!(!dir.join("Test Comic.feed.tmp").exists());
+
+        let mut feed = make_feed(&fs::read_to_string(&path).unwrap(), vec![]);
+        feed.add_new_comics(&["http://example.com/3".to_string()]);
+        feed.read();
+        feed.persist(&path, None).unwrap();
+        assert!(feed.new_events.is_empty());
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(written.starts_with(&format!("{}\n<http://example.com/3>\nread ", before)));
+        let reloaded = feed.info.read_feed(&mut written.as_bytes()).unwrap();
+        assert!(reloaded.get_reading_list().is_empty());
+        assert_eq!(reloaded.all_comics().len(), 3);
+
+        // Nothing new doesn't touch the file
+        feed.persist(&path, Some(FeedFormat::Lines)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), written);
+    }
+
+    #[test]
+    fn test_json_format() {
+        let history = r#"version 7
+<http://example.com/1>
+<http://example.com/2>
+read 2018-01-01T00:00:00+00:00 <http://example.com/1>
+bookmark <http://example.com/1>
+filtered <http://example.com/guest>
+<http://example.com/3>
+read 2018-01-02T00:00:00+00:00
+recap <http://example.com/3>
+fetched 2018-01-03T04:05:06+00:00
+hash <http://example.com/3> 00000000deadbeef
+published <http://example.com/3> 2018-01-01T12:00:00+00:00
+"#;
+        let feed = make_feed(history, vec![]);
+        let mut json = Vec::new();
+        feed.export_json(&mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.contains(r#""event": "read-through""#));
+        assert!(json.contains(r#""hash": "00000000deadbeef""#));
+
+        // Loaded directly, or picked out by `read_feed`
+        let loaded = Feed::from_json(&feed.info, &json).unwrap();
+        assert_eq!(loaded, feed);
+        assert_eq!(feed.info.read_feed(&mut json.as_bytes()).unwrap(), feed);
+        let mut lines = Vec::new();
+        loaded.write_all(&mut lines).unwrap();
+        assert_eq!(String::from_utf8(lines).unwrap(), history);
+
+        // Saving switches the whole file to the chosen format
+        let dir = ScratchDir::new("json");
+        let path = dir.join("Test Comic.feed");
+        fs::write(&path, history).unwrap();
+        let mut feed = make_feed(history, vec![]);
+        feed.add_new_comics(&["http://example.com/4".to_string()]);
+        feed.persist(&path, Some(FeedFormat::Json)).unwrap();
+        assert!(feed.new_events.is_empty());
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(written.starts_with('{'));
+        let reloaded = feed.info.read_feed(&mut written.as_bytes()).unwrap();
+        assert_eq!(reloaded, feed);
+
+        // Without a format it stays JSON
+        let mut feed = reloaded;
+        feed.add_new_comics(&["http://example.com/5".to_string()]);
+        feed.persist(&path, None).unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(written.starts_with('{'));
+        assert_eq!(feed.info.read_feed(&mut written.as_bytes()).unwrap(), feed);
+
+        feed.read();
+        feed.persist(&path, Some(FeedFormat::Lines)).unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(written.starts_with(history));
+        assert_eq!(feed.info.read_feed(&mut written.as_bytes()).unwrap(), feed);
+
+        let bad_date = r#"{"version": 7, "events": [{"event": "read", "date": "soon"}]}"#;
+        assert!(Feed::from_json(&feed.info, bad_date).is_err());
+        let newer = r#"{"version": 999, "events": []}"#;
+        assert!(Feed::from_json(&feed.info, newer).is_err());
+    }
+
+    #[test]
+    fn test_silent_feed() {
+        let mut feed = make_feed("<http://example.com/1>\n", vec![UpdateSpec::Silent]);
+        feed.add_new_comics(&["http://example.com/2".to_string()]);
+        assert!(!feed.is_ready(Local::now()));
+        assert!(feed.get_reading_list().is_empty());
+        assert!(feed.last_read.is_some());
+
+        let mut file = io::Cursor::new(Vec::new());
+        feed.write_changes(&mut file).unwrap();
+        let written = String::from_utf8(file.into_inner()).unwrap();
+        assert!(written.starts_with("<http://example.com/2>\nread "));
+
+        // Nothing new, so nothing else to record
+        feed.add_new_comics(&["http://example.com/2".to_string()]);
+        assert!(feed.new_events.is_empty());
+    }
+
+    #[test]
+    fn test_reading_list_recap() {
+        let events = "<http://example.com/1>\n<http://example.com/2>\n<http://example.com/3>\n\
+                      read 2017-07-17T03:21:21+00:00\n<http://example.com/4>\n";
+        let feed = make_feed(events, vec![UpdateSpec::Overlap(2)]);
+        let list = feed.get_reading_list();
+        assert_eq!(
+            list.recap,
+            vec!["http://example.com/2", "http://example.com/3"]
+        );
+        assert_eq!(list.new, vec!["http://example.com/4"]);
+        assert_eq!(list.describe(), "2 recap + 1 new");
+        assert_eq!(
+            list.into_urls(),
+            vec![
+                "http://example.com/2",
+                "http://example.com/3",
+                "http://example.com/4",
+            ],
+        );
+
+        let feed = make_feed(events, vec![UpdateSpec::Overlap(5)]);
+        assert_eq!(feed.get_reading_list().recap.len(), 3);
+
+        let feed = make_feed(events, vec![]);
+        let list = feed.get_reading_list();
+        assert!(list.recap.is_empty());
+        assert_eq!(list.describe(), "1 comic");
+    }
+
+    #[test]
+    fn test_max_comics() {
+        let events = "<http://example.com/1>\n<http://example.com/2>\n<http://example.com/3>\n\
+                      read 2017-07-17T03:21:21+00:00\n<http://example.com/4>\n\
+                      <http://example.com/5>\n<http://example.com/6>\n";
+        let urls = |nums: &[usize]| {
+            nums.iter()
+                .map(|num| format!("http://example.com/{}", num))
+                .collect::<Vec<_>>()
+        };
+
+        let feed = make_feed(events, vec![UpdateSpec::Max(2)]);
+        assert_eq!(feed.get_reading_list().into_urls(), urls(&[5, 6]));
+        assert_eq!(feed.get_open_list(OpenMode::FirstOnly), urls(&[5]));
+
+        // The overlap is added first, so the recap counts towards the max
+        let feed = make_feed(events, vec![UpdateSpec::Overlap(2), UpdateSpec::Max(4)]);
+        let list = feed.get_reading_list();
+        assert_eq!(list.recap, urls(&[3]));
+        assert_eq!(list.new, urls(&[4, 5, 6]));
+        let feed = make_feed(events, vec![UpdateSpec::Overlap(2), UpdateSpec::Max(2)]);
+        let list = feed.get_reading_list();
+        assert!(list.recap.is_empty());
+        assert_eq!(list.new, urls(&[5, 6]));
+
+        // The smallest max wins, and a big one changes nothing
+        let feed = make_feed(events, vec![UpdateSpec::Max(10), UpdateSpec::Max(1)]);
+        assert_eq!(feed.get_reading_list().into_urls(), urls(&[6]));
+        let feed = make_feed(events, vec![UpdateSpec::Overlap(1), UpdateSpec::Max(10)]);
+        assert_eq!(feed.get_reading_list().into_urls(), urls(&[3, 4, 5, 6]));
+
+        // Skipped comics are read along with the rest
+        let mut feed = make_feed(events, vec![UpdateSpec::Max(2)]);
+        feed.read();
+        assert!(feed.get_reading_list().is_empty());
+    }
+
+    #[test]
+    fn test_recap_shown_once() {
+        let events = "<http://example.com/1>\n<http://example.com/2>\n<http://example.com/3>\n\
+                      read 2017-07-17T03:21:21+00:00\n<http://example.com/4>\n";
+        let mut feed = make_feed(events, vec![UpdateSpec::Overlap(2)]);
+        assert_eq!(
+            feed.get_reading_list().recap,
+            vec!["http://example.com/2", "http://example.com/3"]
+        );
+        feed.read();
+
+        // The next run only recaps what the last one didn't
+        feed.add_new_comics(&["http://example.com/5".to_string()]);
+        let list = feed.get_reading_list();
+        assert_eq!(list.recap, vec!["http://example.com/4"]);
+        assert_eq!(list.new, vec!["http://example.com/5"]);
+
+        // And that survives a round trip through the file
+        let mut file = io::Cursor::new(events.as_bytes().to_vec());
+        feed.write_changes(&mut file).unwrap();
+        let written = String::from_utf8(file.into_inner()).unwrap();
+        assert!(written.contains("recap <http://example.com/3>\nread "));
+        let mut feed = feed.info.read_feed(&mut written.as_bytes()).unwrap();
+        assert_eq!(feed.get_reading_list(), list);
+
+        feed.read();
+        feed.add_new_comics(&["http://example.com/6".to_string()]);
+        assert_eq!(feed.get_reading_list().recap, vec!["http://example.com/5"]);
+    }
+
+    #[test]
+    fn test_porcelain_line() {
+        let events = "<http://example.com/1>\nread 2017-07-17T03:21:21+00:00\n\
+                      <http://example.com/2>\n<http://example.com/3>\n";
+        let mut feed = make_feed(events, vec![UpdateSpec::Overlap(1)]);
+        assert_eq!(
+            feed.porcelain_line(),
+            "Test Comic\thttp://example.com/feed\t2"
+        );
+
+        feed.info.name = "Tabs\tIn\tName".into();
+        assert_eq!(
+            feed.porcelain_line(),
+            "Tabs In Name\thttp://example.com/feed\t2"
+        );
+    }
+
+    #[test]
+    fn test_report_json() {
+        let events = "<http://example.com/1>\nread 2017-07-17T03:21:21+00:00\n\
+                      <http://example.com/2>\n<http://example.com/3>\n\
+                      published <http://example.com/3> 2017-07-18T12:00:00+00:00\n";
+        let mut feed = make_feed(events, vec![UpdateSpec::Overlap(1)]);
+        feed.info
+            .update_policies
+            .insert(UpdateSpec::Icon("🦊".into()));
+        let report = feed.report(true, Utc::now());
+        let urls = |comics: &[ReportComic]| -> Vec<String> {
+            comics.iter().map(|comic| comic.url.clone()).collect()
+        };
+        assert_eq!(urls(&report.reading_list.recap), ["http://example.com/1"]);
+        assert!(report.reading_list.updated.is_empty());
+        assert_eq!(
+            urls(&report.reading_list.new),
+            ["http://example.com/2", "http://example.com/3"]
+        );
+        assert_eq!(
+            serde_json::to_value(&report).unwrap(),
+            serde_json::json!({
+                "name": "Test Comic",
+                "url": "http://example.com/feed",
+                "unread_count": 2,
+                "ready": true,
+                "reading_list": {
+                    "recap": [{"url": "http://example.com/1", "published": null}],
+                    "updated": [],
+                    "new": [
+                        {"url": "http://example.com/2", "published": null},
+                        {"url": "http://example.com/3", "published": "2017-07-18T12:00:00+00:00"},
+                    ],
+                },
+                "color": null,
+                "icon": "🦊",
+            })
+        );
+
+        // With `@ pace`, the batch is the one for `now` in its timezone
+        let events =
+            "<http://example.com/1>\nread 2024-01-01T12:00:00+00:00 <http://example.com/1>\n\
+                      <http://example.com/2>\n<http://example.com/3>\n";
+        let feed = make_feed(events, vec![UpdateSpec::Pace(1)]);
+        let now = Utc.ymd(2024, 1, 1).and_hms(20, 0, 0);
+        let tokyo = chrono::FixedOffset::east(9 * 3600);
+        assert!(feed.report(true, now).reading_list.new.is_empty());
+        let urls: Vec<_> = feed
+            .report(true, now.with_timezone(&tokyo))
+            .reading_list
+            .new
+            .into_iter()
+            .map(|comic| comic.url)
+            .collect();
+        assert_eq!(urls, ["http://example.com/2"]);
+    }
+
+    #[test]
+    fn test_unread_since() {
+        let events = "<http://example.com/1>\npublished <http://example.com/1> 2024-01-01T12:00:00+00:00\n\
+                      read 2024-01-02T12:00:00+00:00\n\
+                      <http://example.com/2>\npublished <http://example.com/2> 2024-01-03T12:00:00+00:00\n\
+                      <http://example.com/3>\n\
+                      <http://example.com/4>\npublished <http://example.com/4> 2024-01-05T12:00:00+00:00\n";
+        let feed = make_feed(events, vec![]);
+        let day = |day| Utc.ymd(2024, 1, day).and_hms(12, 0, 0);
+        // 1 is read, and 3 has no date
+        assert_eq!(feed.unread_since(day(1)), 2);
+        assert_eq!(feed.unread_since(day(3)), 2);
+        assert_eq!(feed.unread_since(day(4)), 1);
+        assert_eq!(feed.unread_since(day(5)), 1);
+        assert_eq!(feed.unread_since(day(6)), 0);
+    }
+
+    #[test]
+    fn test_describe_status() {
+        let events = "<http://example.com/1>\nread 2017-07-17T03:21:21+00:00\n\
+                      <http://example.com/2>\n<http://example.com/3>\n";
+        let now = Utc.ymd(2017, 7, 20).and_hms(12, 0, 0);
+        let feed = make_feed(events, vec![UpdateSpec::Comics(2)]);
+        assert_eq!(feed.unread_count(), 2);
+        assert_eq!(
+            feed.describe_status(now),
+            "2 unread, last read 2017-07-17, ready"
+        );
+
+        let feed = make_feed(events, vec![UpdateSpec::Comics(3)]);
+        assert_eq!(
+            feed.describe_status(now),
+            "2 unread, last read 2017-07-17, waiting"
+        );
+
+        let feed = make_feed("", vec![]);
+        assert_eq!(feed.describe_status(now), "0 unread, never read, waiting");
+    }
+
+    #[test]
+    fn test_partial_read() {
+        let events = "<http://example.com/1>\n<http://example.com/2>\n<http://example.com/3>\n\
+                      <http://example.com/4>\n<http://example.com/5>\n<http://example.com/6>\n";
+        let mut feed = make_feed(events, vec![UpdateSpec::Comics(5), UpdateSpec::Overlap(1)]);
+        assert!(feed.is_ready(Local::now()));
+
+        feed.read_first(5);
+        assert_eq!(feed.new_comics, 1);
+        assert!(
+            !feed.is_ready(Local::now()),
+            "the leftover comic shouldn't re-fire"
+        );
+        let list = feed.get_reading_list();
+        assert_eq!(list.recap, vec!["http://example.com/5"]);
+        assert_eq!(list.new, vec!["http://example.com/6"]);
+
+        // The partial read survives a round trip through the file
+        let mut file = io::Cursor::new(events.as_bytes().to_vec());
+        feed.write_changes(&mut file).unwrap();
+        let written = String::from_utf8(file.into_inner()).unwrap();
+        assert!(written.ends_with(" <http://example.com/5>\n"));
+        let mut feed = feed.info.read_feed(&mut written.as_bytes()).unwrap();
+        assert_eq!(feed.new_comics, 1);
+        assert!(!feed.is_ready(Local::now()));
+
+        let more: Vec<_> = (7..11)
+            .map(|i| format!("http://example.com/{}", i))
+            .collect();
+        feed.add_new_comics(&more);
+        assert!(feed.is_ready(Local::now()));
+        assert_eq!(feed.get_reading_list().new.len(), 5);
+
+        feed.read_first(10);
+        assert_eq!(feed.new_comics, 0);
+        assert_eq!(
+            feed.new_events.last(),
+            Some(&FeedEvent::Read(feed.last_read.unwrap()))
+        );
+    }
+
+    #[test]
+    fn test_bookmarks() {
+        let events = "<http://example.com/1>\nread 2017-07-17T03:21:21+00:00\n\
+                      bookmark <http://example.com/1>\n<http://example.com/2>\n";
+        let mut feed = make_feed(events, vec![UpdateSpec::Comics(2)]);
+        assert_eq!(feed.new_comics, 1);
+        assert!(!feed.is_ready(Local::now()), "bookmarks aren't new comics");
+        assert_eq!(feed.get_reading_list().new, vec!["http://example.com/2"]);
+
+        assert!(feed.bookmark("http://example.com/2"));
+        assert!(!feed.bookmark("http://example.com/1"));
+        assert_eq!(
+            feed.bookmarks(),
+            vec!["http://example.com/1", "http://example.com/2"]
+        );
+
+        let mut file = io::Cursor::new(events.as_bytes().to_vec());
+        feed.write_changes(&mut file).unwrap();
+        let written = String::from_utf8(file.into_inner()).unwrap();
+        assert!(written.ends_with("\nbookmark <http://example.com/2>\n"));
+        let reloaded = feed.info.read_feed(&mut written.as_bytes()).unwrap();
+        assert_eq!(reloaded.bookmarks(), feed.bookmarks());
+        assert_eq!(reloaded.get_reading_list(), feed.get_reading_list());
+    }
+
+    #[test]
+    fn test_migrate_old_format() {
+        let old = "\n<http://example.com/1>\n<http://example.com/2>\n\
+                   READ 2017-07-17T03:21:21.492180Z\n\n<http://example.com/3>\n";
+        let feed = make_feed(old, vec![UpdateSpec::Overlap(1)]);
+        let mut migrated = Vec::new();
+        feed.write_all(&mut migrated).unwrap();
+        let migrated = String::from_utf8(migrated).unwrap();
+        assert_eq!(
+            migrated,
+            "version 7\n<http://example.com/1>\n<http://example.com/2>\n\
+             read 2017-07-17T03:21:21.492180+00:00\n<http://example.com/3>\n",
+        );
+
+        let reloaded = feed.info.read_feed(&mut migrated.as_bytes()).unwrap();
+        assert_eq!(reloaded.get_reading_list(), feed.get_reading_list());
+        assert_eq!(reloaded.last_read, feed.last_read);
+
+        let mut again = Vec::new();
+        reloaded.write_all(&mut again).unwrap();
+        assert_eq!(String::from_utf8(again).unwrap(), migrated);
+
+        let newer = format!("version {}\n", FEED_FORMAT_VERSION + 1);
+        let newer = feed.info.read_feed(&mut newer.as_bytes());
+        assert!(newer.is_err());
+    }
+
+    #[test]
+    fn test_catch_up_ignores_schedule() {
+        let yesterday = (Utc::now() - Duration::days(1)).to_rfc3339();
+        let events = format!(
+            "<http://example.com/1>\nread {}\n<http://example.com/2>\n",
+            yesterday
+        );
+        let policies = vec![UpdateSpec::Every(7, TimeUnit::Day), UpdateSpec::Comics(2)];
+        let mut feed = make_feed(&events, policies);
+        assert!(!feed.is_ready(Local::now()));
+        assert!(!feed.has_enough_comics(), "caps still apply");
+
+        feed.add_new_comics(&["http://example.com/3".to_string()]);
+        assert!(!feed.is_ready(Local::now()), "read too recently");
+        assert!(feed.has_enough_comics());
+    }
+
+    #[test]
+    fn test_updated_comic_reappears() {
+        let mut feed = make_feed("", vec![UpdateSpec::NotifyUpdates]);
+        let urls = vec![
+            "http://example.com/1".to_string(),
+            "http://example.com/2".to_string(),
+        ];
+        feed.add_new_comics(&urls);
+        let hashes = vec![(urls[0].clone(), 1), (urls[1].clone(), 2)];
+        assert_eq!(feed.add_content_hashes(&hashes), 0);
+        feed.read();
+        assert_eq!(feed.add_content_hashes(&hashes), 0, "nothing changed");
+        assert!(feed.get_reading_list().is_empty());
+
+        // The first comic's art was fixed
+        let fixed = vec![(urls[0].clone(), 3), (urls[1].clone(), 2)];
+        assert_eq!(feed.add_content_hashes(&fixed), 1);
+        assert_eq!(feed.add_content_hashes(&fixed), 0);
+        let list = feed.get_reading_list();
+        assert_eq!(list.updated, vec!["http://example.com/1"]);
+        assert_eq!(list.describe(), "1 updated + 0 new");
+        assert_eq!(feed.new_comics, 0, "updates aren't new comics");
+
+        let mut written = Vec::new();
+        feed.write_all(&mut written).unwrap();
+        let written = String::from_utf8(written).unwrap();
+        assert!(written.ends_with("\nhash <http://example.com/1> 0000000000000003\n"));
+        let mut reloaded = feed.info.read_feed(&mut written.as_bytes()).unwrap();
+        assert_eq!(reloaded.get_reading_list(), list);
+
+        reloaded.read();
+        assert!(reloaded.get_reading_list().is_empty());
+        // Entries that were never seen, like filtered ones, aren't tracked
+        let unseen = vec![("http://example.com/guest".to_string(), 4)];
+        let events = reloaded.new_events.len();
+        assert_eq!(reloaded.add_content_hashes(&unseen), 0);
+        assert_eq!(reloaded.new_events.len(), events);
+    }
+
+    #[test]
+    fn test_preserve_order() {
+        let date = |day| Utc.ymd(2018, 1, day).and_hms(12, 0, 0);
+        let url = |name: &str| format!("http://example.com/{}", name);
+        let fetch = |feed: &mut Feed, comics: &[(&str, Option<u32>)]| {
+            let urls: Vec<_> = comics.iter().map(|&(name, _)| url(name)).collect();
+            feed.add_new_comics(&urls);
+            let dates: Vec<_> = comics
+                .iter()
+                .filter_map(|&(name, day)| day.map(|day| (url(name), date(day))))
+                .collect();
+            feed.add_publication_dates(&dates);
+        };
+        let urls = |names: &[&str]| names.iter().map(|&name| url(name)).collect::<Vec<_>>();
+
+        let mut ordered = make_feed("", vec![UpdateSpec::PreserveOrder]);
+        let mut arrival = make_feed("", vec![]);
+        for feed in [&mut ordered, &mut arrival] {
+            fetch(feed, &[("3", Some(3)), ("extra", None), ("5", Some(5))]);
+            // A backfilled comic from before the ones that are already unread
+            fetch(feed, &[("1", Some(1)), ("4", Some(4))]);
+        }
+        assert_eq!(
+            ordered.get_reading_list().new,
+            urls(&["1", "extra", "3", "4", "5"])
+        );
+        assert_eq!(
+            arrival.get_reading_list().new,
+            urls(&["3", "extra", "5", "1", "4"])
+        );
+
+        // Partial reads go by the same order, before and after reloading
+        ordered.read_first(2);
+        assert_eq!(ordered.get_reading_list().new, urls(&["3", "4", "5"]));
+        let mut written = Vec::new();
+        ordered.write_all(&mut written).unwrap();
+        let reloaded = ordered.info.read_feed(&mut &written[..]).unwrap();
+        assert_eq!(reloaded.get_reading_list().new, urls(&["3", "4", "5"]));
+        assert_eq!(reloaded.unread_count(), 3);
+    }
+
+    #[test]
+    fn test_reading_list_with_dates() {
+        let events = "<http://example.com/1>\nread 2018-01-01T00:00:00+00:00\n\
+                      <http://example.com/2>\n<http://example.com/3>\n<http://example.com/4>\n\
+                      published <http://example.com/1> 2017-12-31T12:00:00+00:00\n";
+        let mut feed = make_feed(events, vec![UpdateSpec::Overlap(1)]);
+        let date = |day| Utc.ymd(2018, 1, day).and_hms(12, 0, 0);
+        feed.add_publication_dates(&[
+            ("http://example.com/3".into(), date(3)),
+            ("http://example.com/4".into(), date(4)),
+            ("http://example.com/4".into(), date(5)),
+            ("http://example.com/guest".into(), date(5)),
+        ]);
+        assert_eq!(
+            feed.reading_list_with_dates(Utc::now()),
+            vec![
+                (
+                    "http://example.com/1".into(),
+                    Some(Utc.ymd(2017, 12, 31).and_hms(12, 0, 0))
+                ),
+                ("http://example.com/2".into(), None),
+                ("http://example.com/3".into(), Some(date(3))),
+                ("http://example.com/4".into(), Some(date(4))),
+            ]
+        );
+
+        // Only the new comics count, and undated ones are skipped
+        assert_eq!(
+            feed.describe_reading_list(date(6)),
+            "1 recap + 3 new since Wednesday"
+        );
+        assert_eq!(
+            feed.describe_reading_list(Utc.ymd(2018, 2, 1).and_hms(0, 0, 0)),
+            "1 recap + 3 new since 2018-01-03"
+        );
+        let undated = make_feed("<http://example.com/1>\n", vec![]);
+        assert_eq!(undated.describe_reading_list(date(6)), "1 comic");
+
+        let mut written = Vec::new();
+        feed.write_all(&mut written).unwrap();
+        let reloaded = feed.info.read_feed(&mut &written[..]).unwrap();
+        assert_eq!(
+            reloaded.reading_list_with_dates(Utc::now()),
+            feed.reading_list_with_dates(Utc::now())
+        );
+    }
+
+    #[test]
+    fn test_touch_updates_last_fetched() {
+        let events = "<http://example.com/1>\nfetched 2018-01-02T03:04:05+00:00\n";
+        let mut feed = make_feed(events, vec![]);
+        assert_eq!(
+            feed.last_fetched(),
+            Some(Utc.ymd(2018, 1, 2).and_hms(3, 4, 5))
+        );
+
+        let now = Utc.ymd(2018, 2, 3).and_hms(4, 5, 6);
+        feed.touch(now);
+        assert_eq!(feed.last_fetched(), Some(now));
+        assert_eq!(feed.new_comics, 1, "touching doesn't read anything");
+
+        let mut written = io::Cursor::new(events.as_bytes().to_vec());
+        feed.write_changes(&mut written).unwrap();
+        let written = String::from_utf8(written.into_inner()).unwrap();
+        assert!(written.ends_with("\nfetched 2018-02-03T04:05:06+00:00\n"));
+        let reloaded = feed.info.read_feed(&mut written.as_bytes()).unwrap();
+        assert_eq!(reloaded.last_fetched(), Some(now));
+    }
+
+    #[test]
+    fn test_check_patterns() {
+        let expr = FilterExpr::And(
+            Box::new(FilterExpr::Matches(FilterField::Title, "chapter".into())),
+            Box::new(FilterExpr::Not(Box::new(FilterExpr::NotMatches(
+                FilterField::Url,
+                "(guest".into(),
+            )))),
+        );
+        assert_eq!(expr.patterns(), ["chapter", "(guest"]);
+        let feed = make_feed(
+            "",
+            vec![
+                UpdateSpec::FilterExpr(expr),
+                UpdateSpec::Filter(FilterType::KeepUrl, "/page/".into()),
+                UpdateSpec::SlugFrom("[".into()),
+            ],
+        );
+        let problems = feed.info.check_patterns();
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].starts_with("Invalid pattern /(guest/"));
+        assert!(problems[1].starts_with("Invalid pattern /[/"));
+        assert!(make_feed("", vec![]).info.check_patterns().is_empty());
+    }
+
+    #[test]
+    fn test_filtered_entries() {
+        let events = "<http://example.com/1>\nfiltered <http://example.com/guest-1>\n";
+        let mut feed = make_feed(events, vec![UpdateSpec::RecordFiltered]);
+        assert_eq!(feed.new_comics, 1);
+        assert_eq!(feed.get_reading_list().new, vec!["http://example.com/1"]);
+
+        let filtered = vec![
+            "http://example.com/guest-1".to_string(),
+            "http://example.com/guest-2".to_string(),
+        ];
+        assert_eq!(feed.add_filtered(&filtered), 1);
+        assert_eq!(feed.add_filtered(&filtered), 0);
+        assert_eq!(
+            feed.filtered(),
+            vec!["http://example.com/guest-1", "http://example.com/guest-2"]
+        );
+        assert_eq!(feed.new_comics, 1, "filtered entries aren't new comics");
+
+        let mut file = io::Cursor::new(events.as_bytes().to_vec());
+        feed.write_changes(&mut file).unwrap();
+        let written = String::from_utf8(file.into_inner()).unwrap();
+        assert!(written.ends_with("\nfiltered <http://example.com/guest-2>\n"));
+        let reloaded = feed.info.read_feed(&mut written.as_bytes()).unwrap();
+        assert_eq!(reloaded.filtered(), feed.filtered());
+        assert_eq!(reloaded.get_reading_list(), feed.get_reading_list());
+    }
+
+    #[test]
+    fn test_repair_duplicates() {
+        let events = "<http://example.com/1>\n<http://example.com/2>\n<http://example.com/1>\n\
+                      read 2017-07-17T03:21:21+00:00\n<http://example.com/2>\n\
+                      <http://example.com/3>\nbookmark <http://example.com/1>\n\
+                      bookmark <http://example.com/1>\n";
+        let mut feed = make_feed(events, vec![]);
+        assert_eq!(feed.new_comics, 2);
+
+        assert_eq!(feed.repair(), 3);
+        assert_eq!(feed.new_comics, 1);
+        assert_eq!(feed.get_reading_list().new, vec!["http://example.com/3"]);
+        assert_eq!(
+            feed.last_read,
+            Some(Utc.ymd(2017, 7, 17).and_hms(3, 21, 21))
+        );
+
+        let mut repaired = Vec::new();
+        feed.write_all(&mut repaired).unwrap();
+        assert_eq!(
+            String::from_utf8(repaired).unwrap(),
+            format!(
+                "version {}\n<http://example.com/1>\n<http://example.com/2>\n\
+                 read 2017-07-17T03:21:21+00:00\n<http://example.com/3>\n\
+                 bookmark <http://example.com/1>\n",
+                FEED_FORMAT_VERSION
+            ),
+        );
+        assert_eq!(feed.repair(), 0, "already repaired");
+    }
+
+    #[test]
+    fn test_comic_url() {
+        let feed = make_feed("", vec![]);
+        assert_eq!(
+            feed.info
+                .comic_url("http://example.com/?p=1", Some("comic-12")),
+            "http://example.com/?p=1",
+        );
+
+        let template = UpdateSpec::UrlTemplate("http://example.com/comic/{slug}".into());
+        let feed = make_feed(
+            "",
+            vec![
+                template.clone(),
+                UpdateSpec::SlugFrom(r"comic-(\d+)".into()),
+            ],
+        );
+        assert_eq!(
+            feed.info.comic_url(
+                "http://example.com/?p=1&utm=rss",
+                Some("tag:example.com,comic-12")
+            ),
+            "http://example.com/comic/12",
+        );
+        assert_eq!(
+            feed.info
+                .comic_url("http://example.com/comic-13?utm=rss", None),
+            "http://example.com/comic/13",
+        );
+        assert_eq!(
+            feed.info
+                .comic_url("http://example.com/?p=1", Some("unrelated")),
+            "http://example.com/?p=1",
+        );
+
+        let feed = make_feed(
+            "",
+            vec![template.clone(), UpdateSpec::SlugFrom(r"\d+".into())],
+        );
+        assert_eq!(
+            feed.info
+                .comic_url("http://example.com/?p=1", Some("comic-14")),
+            "http://example.com/comic/14",
+        );
+
+        let feed = make_feed("", vec![template]);
+        assert_eq!(
+            feed.info.comic_url("http://example.com/?p=1", Some("15")),
+            "http://example.com/comic/15",
+        );
+    }
+
+    #[test]
+    fn test_ready_at_fixed_time() {
+        let events = "<http://example.com/1>\nread 2017-07-17T12:00:00+00:00\n\
+                      <http://example.com/2>\n<http://example.com/3>\n";
+        let policies = vec![UpdateSpec::Every(3, TimeUnit::Day), UpdateSpec::Comics(2)];
+        let mut feed = make_feed(events, policies);
+        let day = |day| Utc.ymd(2017, 7, day).and_hms(13, 0, 0);
+        assert!(!feed.is_ready(day(17)));
+        assert!(!feed.is_ready(day(19)));
+        assert!(feed.is_ready(day(20)));
+        assert!(feed.is_ready(day(31)));
+
+        feed.read_first(1);
+        assert!(!feed.is_ready(day(31)), "only one comic left");
+    }
+
+    #[test]
+    fn test_skip_weekends() {
+        // Read on Monday 2024-01-01, and due every 5 days, so first on Saturday the 6th
+        let events = "read 2024-01-01T12:00:00+00:00\n<http://example.com/1>\n";
+        let policies = vec![UpdateSpec::Every(5, TimeUnit::Day)];
+        // In UTC, like the read, so the days match wherever the tests run
+        let noon = |day| Utc.ymd(2024, 1, day).and_hms(12, 0, 0);
+        let feed = make_feed(events, policies.clone());
+        assert!(feed.is_ready(noon(6)));
+
+        let feed = make_feed(events, vec![policies[0].clone(), UpdateSpec::SkipWeekends]);
+        assert!(!feed.is_ready(noon(5)), "not due yet");
+        assert!(!feed.is_ready(noon(6)), "Saturday");
+        assert!(!feed.is_ready(noon(7)), "Sunday");
+        assert!(feed.is_ready(noon(8)));
+        assert_eq!(
+            feed.next_scheduled(noon(5)),
+            Some(Utc.ymd(2024, 1, 8).and_hms(0, 0, 0))
+        );
+        assert_eq!(
+            feed.next_scheduled(noon(6)),
+            Some(Utc.ymd(2024, 1, 8).and_hms(0, 0, 0))
+        );
+
+        // Even without other policies, or ever being read
+        let feed = make_feed("<http://example.com/1>\n", vec![UpdateSpec::SkipWeekends]);
+        assert!(feed.is_ready(noon(5)));
+        assert!(!feed.is_ready(noon(6)));
+        assert_eq!(
+            feed.next_scheduled(noon(7)),
+            Some(Utc.ymd(2024, 1, 8).and_hms(0, 0, 0))
+        );
+    }
+
+    #[test]
+    fn test_pace() {
+        let backlog: String = (1..=50)
+            .map(|num| format!("<http://example.com/{}>\n", num))
+            .collect();
+        let urls = |nums: ::std::ops::RangeInclusive<usize>| {
+            nums.map(|num| format!("http://example.com/{}", num))
+                .collect::<Vec<_>>()
+        };
+        let noon = |day| Local.ymd(2024, 1, day).and_hms(12, 0, 0);
+
+        // Never read, so there's one day's worth
+        let feed = make_feed(&backlog, vec![UpdateSpec::Pace(10)]);
+        assert_eq!(feed.reading_list_at(noon(1)).new, urls(1..=10));
+        assert!(feed.is_ready(noon(1)));
+
+        // Read through 10 on the 1st
+        let events = format!(
+            "{}read {} <http://example.com/10>\n",
+            backlog,
+            noon(1).to_rfc3339()
+        );
+        let feed = make_feed(&events, vec![UpdateSpec::Pace(10)]);
+        assert_eq!(feed.unread_count(), 40);
+        assert!(feed.reading_list_at(noon(1)).is_empty());
+        assert!(!feed.is_ready(Local.ymd(2024, 1, 1).and_hms(23, 59, 0)));
+        assert_eq!(
+            feed.next_scheduled(noon(1)),
+            Some(Local.ymd(2024, 1, 2).and_hms(0, 0, 0))
+        );
+        assert!(feed.is_ready(Local.ymd(2024, 1, 2).and_hms(0, 1, 0)));
+        assert_eq!(feed.reading_list_at(noon(2)).new, urls(11..=20));
+        // Missing a day makes up for it, and a long break catches up completely
+        assert_eq!(feed.reading_list_at(noon(3)).new, urls(11..=30));
+        assert_eq!(feed.reading_list_at(noon(20)).new, urls(11..=50));
+
+        // Reading only takes today's comics, with the rest left for tomorrow
+        let yesterday = (Utc::now() - Duration::days(1)).to_rfc3339();
+        let events = format!("{}read {} <http://example.com/10>\n", backlog, yesterday);
+        let mut feed = make_feed(&events, vec![UpdateSpec::Pace(10)]);
+        assert_eq!(feed.get_reading_list().new, urls(11..=20));
+        feed.read_list(Local::now());
+        assert_eq!(feed.unread_count(), 30);
+        assert!(feed.get_reading_list().is_empty());
+        assert!(!feed.is_ready(Local::now()));
+
+        // Without a pace it's all read as usual
+        let mut feed = make_feed(&events, vec![]);
+        feed.read_list(Local::now());
+        assert_eq!(feed.unread_count(), 0);
+    }
+
+    #[test]
+    fn test_comics_or_after() {
+        let events = "<http://example.com/1>\nread 2017-07-17T12:00:00+00:00\n\
+                      <http://example.com/2>\n<http://example.com/3>\n";
+        let day = |day| Utc.ymd(2017, 7, day).and_hms(13, 0, 0);
+
+        let feed = make_feed(events, vec![UpdateSpec::ComicsOrAfter(2, 7)]);
+        assert!(feed.is_ready(day(18)), "count met early");
+
+        let feed = make_feed(events, vec![UpdateSpec::ComicsOrAfter(5, 7)]);
+        assert!(!feed.is_ready(day(20)), "neither yet");
+        assert!(feed.is_ready(day(24)), "timeout reached with fewer");
+        assert!(!feed.has_enough_comics(), "catching up still waits");
+
+        let feed = make_feed(
+            "<http://example.com/1>\n",
+            vec![UpdateSpec::ComicsOrAfter(5, 7)],
+        );
+        assert!(!feed.is_ready(day(24)), "never read, so no timeout");
+
+        let events = "<http://example.com/1>\nread 2017-07-17T12:00:00+00:00\n";
+        let feed = make_feed(events, vec![UpdateSpec::ComicsOrAfter(5, 7)]);
+        assert!(!feed.is_ready(day(31)), "nothing new to show");
+    }
+
+    #[test]
+    fn test_every_from_threshold() {
+        let day = |day| Utc.ymd(2017, 7, day).and_hms(12, 0, 0);
+        let make = |policies: Vec<UpdateSpec>| {
+            let mut feed = make_feed(
+                "<http://example.com/0>\nread 2017-07-01T12:00:00+00:00\n",
+                policies,
+            );
+            // One comic on the 2nd, then the second one needed on the 10th
+            feed.add_new_comics(&["http://example.com/1".to_string()]);
+            feed.record_arrival(day(2));
+            feed.add_new_comics(&["http://example.com/2".to_string()]);
+            feed.record_arrival(day(10));
+            feed
+        };
+        let policies = vec![UpdateSpec::Comics(2), UpdateSpec::Every(3, TimeUnit::Day)];
+
+        // Counting from the last read, the timer ran out long before the batch was ready
+        let from_read = make(policies.clone());
+        assert!(
+            from_read.last_fetched().is_none(),
+            "only recorded when it's needed"
+        );
+        assert!(from_read.is_ready(day(10)));
+        assert_eq!(
+            from_read.next_scheduled(day(10).with_timezone(&Local)),
+            None
+        );
+
+        let mut from_threshold = policies;
+        from_threshold.push(UpdateSpec::EveryFromThreshold);
+        let from_threshold = make(from_threshold);
+        assert!(!from_threshold.is_ready(day(10)));
+        assert!(!from_threshold.is_ready(day(12)));
+        assert!(from_threshold.is_ready(day(13)));
+        assert_eq!(
+            from_threshold.next_scheduled(day(11).with_timezone(&Local)),
+            Some(day(13).with_timezone(&Local))
+        );
+
+        // Reloading finds the same arrival times
+        let mut written = Vec::new();
+        from_threshold.write_all(&mut written).unwrap();
+        let reloaded = from_threshold.info.read_feed(&mut &written[..]).unwrap();
+        assert!(!reloaded.is_ready(day(12)));
+        assert!(reloaded.is_ready(day(13)));
+
+        assert_eq!(
+            from_threshold.info.describe_schedule(),
+            "every 3 days after there are enough new comics, at least 2 new comics"
+        );
+    }
+
+    #[test]
+    fn test_schedule_timezone() {
+        use chrono::FixedOffset;
+        // Friday evening in UTC is already Saturday morning in Tokyo
+        let feed = make_feed(
+            "read 2017-07-14T20:00:00+00:00\n<http://example.com/1>\n",
+            vec![UpdateSpec::On(Weekday::Sat)],
+        );
+        let now = Utc.ymd(2017, 7, 15).and_hms(22, 0, 0);
+        let tokyo = FixedOffset::east(9 * 3600);
+        assert!(feed.is_scheduled(now));
+        assert!(!feed.is_scheduled(now.with_timezone(&tokyo)));
+        assert!(feed.is_ready(now));
+        assert!(!feed.is_ready(now.with_timezone(&tokyo)));
+        assert_eq!(feed.next_scheduled(now), None);
+        assert_eq!(
+            feed.next_scheduled(now.with_timezone(&tokyo)),
+            Some(tokyo.ymd(2017, 7, 22).and_hms(0, 0, 0))
+        );
+    }
+
+    /// US Eastern time in early 2024, which springs forward on March 10th
+    #[derive(Clone, Copy, Debug)]
+    struct Eastern;
+
+    impl Eastern {
+        fn offset(dst: bool) -> chrono::FixedOffset {
+            chrono::FixedOffset::west(if dst { 4 * 3600 } else { 5 * 3600 })
+        }
+    }
+
+    impl TimeZone for Eastern {
+        type Offset = chrono::FixedOffset;
+
+        fn from_offset(_: &Self::Offset) -> Self {
+            Eastern
+        }
+
+        fn offset_from_local_date(&self, local: &NaiveDate) -> chrono::LocalResult<Self::Offset> {
+            self.offset_from_local_datetime(&local.and_hms(0, 0, 0))
+        }
+
+        fn offset_from_local_datetime(
+            &self,
+            local: &chrono::NaiveDateTime,
+        ) -> chrono::LocalResult<Self::Offset> {
+            let change = NaiveDate::from_ymd(2024, 3, 10).and_hms(2, 0, 0);
+            chrono::LocalResult::Single(Eastern::offset(*local >= change))
+        }
+
+        fn offset_from_utc_date(&self, utc: &NaiveDate) -> Self::Offset {
+            self.offset_from_utc_datetime(&utc.and_hms(0, 0, 0))
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &chrono::NaiveDateTime) -> Self::Offset {
+            Eastern::offset(*utc >= NaiveDate::from_ymd(2024, 3, 10).and_hms(7, 0, 0))
+        }
+    }
+
+    #[test]
+    fn test_on_several_days() {
+        let config = "\"Test Comic\" <http://example.com/feed> @ on Monday and Friday\n";
+        let info = crate::parser::parse_config(config).unwrap().remove(0);
+        let noon = |day| Utc.ymd(2024, 1, day).and_hms(12, 0, 0);
+
+        // Read on Tuesday the 2nd
+        let events = "read 2024-01-02T12:00:00+00:00\n<http://example.com/1>\n";
+        let feed = info.read_feed(&mut events.as_bytes()).unwrap();
+        assert!(!feed.is_scheduled(noon(4)));
+        assert!(feed.is_scheduled(noon(5)), "Friday has passed");
+        assert!(feed.is_scheduled(noon(8)));
+
+        // Read on Saturday the 6th
+        let events = "read 2024-01-06T12:00:00+00:00\n<http://example.com/1>\n";
+        let feed = info.read_feed(&mut events.as_bytes()).unwrap();
+        assert!(!feed.is_scheduled(noon(7)));
+        assert!(feed.is_scheduled(noon(8)), "Monday has passed");
+    }
+
+    #[test]
+    fn test_on_across_dst() {
+        // Read at noon on Saturday, but only 47.5 hours pass before Monday 12:30
+        let feed = make_feed(
+            "read 2024-03-09T17:00:00+00:00\n<http://example.com/1>\n",
+            vec![UpdateSpec::On(Weekday::Mon)],
+        );
+        assert!(!feed.is_scheduled(Eastern.ymd(2024, 3, 10).and_hms(23, 59, 0)));
+        assert!(feed.is_scheduled(Eastern.ymd(2024, 3, 11).and_hms(0, 30, 0)));
+        assert!(feed.is_scheduled(Eastern.ymd(2024, 3, 11).and_hms(12, 30, 0)));
+    }
+
+    #[test]
+    fn test_every_from_anchor() {
+        let anchor = NaiveDate::from_ymd(2024, 1, 6);
+        let every = vec![UpdateSpec::EveryFrom(14, anchor)];
+        let noon = |month, day| Local.ymd(2024, month, day).and_hms(12, 0, 0);
+
+        let feed = make_feed("<http://example.com/1>\n", every.clone());
+        assert!(feed.is_scheduled(noon(1, 2)), "never read");
+
+        let feed = make_feed(
+            "read 2024-01-10T12:00:00+00:00\n<http://example.com/1>\n",
+            every.clone(),
+        );
+        assert!(!feed.is_scheduled(noon(1, 19)));
+        assert!(feed.is_scheduled(noon(1, 20)));
+        assert!(feed.is_scheduled(noon(1, 27)));
+
+        // Reading late doesn't shift the cadence
+        let feed = make_feed(
+            "read 2024-01-23T12:00:00+00:00\n<http://example.com/1>\n",
+            every.clone(),
+        );
+        assert!(!feed.is_scheduled(noon(2, 2)));
+        assert!(feed.is_scheduled(noon(2, 3)));
+
+        let feed = make_feed(
+            "read 2023-12-01T12:00:00+00:00\n<http://example.com/1>\n",
+            vec![UpdateSpec::EveryFrom(14, NaiveDate::from_ymd(2024, 3, 1))],
+        );
+        assert!(!feed.is_scheduled(noon(2, 1)), "before the anchor");
+        assert!(feed.is_scheduled(noon(3, 1)));
+    }
+
+    #[test]
+    fn test_display_name() {
+        let mut info = FeedInfo {
+            name: "Witchy".into(),
+            url: "http://witchycomic.com/feed".into(),
+            update_policies: HashSet::new(),
+            root: None,
+            command: None,
+            source: None,
+        };
+        assert_eq!(info.display_name(true), "Witchy");
+
+        info.update_policies.insert(UpdateSpec::Color(Color::Red));
+        assert_eq!(info.display_name(false), "Witchy");
+        assert_eq!(info.display_name(true), "\x1b[31mWitchy\x1b[0m");
+
+        info.update_policies.insert(UpdateSpec::Icon("🦊".into()));
+        assert_eq!(info.display_name(false), "🦊 Witchy");
+        assert_eq!(info.display_name(true), "🦊 \x1b[31mWitchy\x1b[0m");
+    }
+
+    #[test]
+    fn test_next_scheduled() {
+        let at = |month, day, hour| Local.ymd(2024, month, day).and_hms(hour, 0, 0);
+        let read = format!(
+            "read {}\n<http://example.com/1>\n",
+            at(1, 10, 12).to_rfc3339() // A Wednesday
+        );
+
+        let weekly = make_feed(&read, vec![UpdateSpec::Every(7, TimeUnit::Day)]);
+        assert_eq!(weekly.next_scheduled(at(1, 11, 9)), Some(at(1, 17, 12)));
+        assert_eq!(weekly.next_scheduled(at(1, 17, 12)), None, "already due");
+
+        let mondays = make_feed(&read, vec![UpdateSpec::On(Weekday::Mon)]);
+        assert_eq!(mondays.next_scheduled(at(1, 11, 9)), Some(at(1, 15, 0)));
+        assert_eq!(mondays.next_scheduled(at(1, 16, 9)), None);
+
+        // Both have to allow it, and either day will do
+        let both = make_feed(
+            &read,
+            vec![
+                UpdateSpec::Every(7, TimeUnit::Day),
+                UpdateSpec::On(Weekday::Mon),
+                UpdateSpec::On(Weekday::Fri),
+            ],
+        );
+        assert_eq!(both.next_scheduled(at(1, 11, 9)), Some(at(1, 17, 12)));
+        assert!(both.is_scheduled(at(1, 17, 12)));
+
+        let unscheduled = make_feed(&read, vec![UpdateSpec::Comics(3)]);
+        assert_eq!(unscheduled.next_scheduled(at(1, 11, 9)), None);
+        let unread = make_feed(
+            "<http://example.com/1>\n",
+            vec![UpdateSpec::On(Weekday::Mon)],
+        );
+        assert_eq!(unread.next_scheduled(at(1, 11, 9)), None);
+
+        assert_eq!(describe_day(at(1, 11, 20), at(1, 11, 9)), "today");
+        assert_eq!(describe_day(at(1, 12, 0), at(1, 11, 9)), "tomorrow");
+        assert_eq!(describe_day(at(1, 17, 12), at(1, 11, 9)), "Wednesday");
+        assert_eq!(describe_day(at(2, 1, 0), at(1, 11, 9)), "2024-02-01");
+    }
+
+    #[test]
+    fn test_every_hours() {
+        let feed = make_feed(
+            "read 2024-01-10T09:30:00+00:00\n<http://example.com/1>\n",
+            vec![UpdateSpec::Every(6, TimeUnit::Hour)],
+        );
+        assert!(!feed.is_scheduled(Utc.ymd(2024, 1, 10).and_hms(12, 0, 0)));
+        assert!(!feed.is_scheduled(Utc.ymd(2024, 1, 10).and_hms(15, 29, 0)));
+        assert!(feed.is_scheduled(Utc.ymd(2024, 1, 10).and_hms(15, 30, 0)));
+        assert!(feed.is_scheduled(Utc.ymd(2024, 1, 11).and_hms(1, 0, 0)));
+    }
+
+    #[test]
+    fn test_every_weeks_and_months() {
+        // In UTC, like the reads, so the days match wherever the tests run
+        let noon = |year, month, day| Utc.ymd(year, month, day).and_hms(12, 0, 0);
+        let read_on = |date: &str, policy| {
+            make_feed(
+                &format!("read {}T12:00:00+00:00\n<http://example.com/1>\n", date),
+                vec![policy],
+            )
+        };
+
+        let feed = read_on("2024-01-10", UpdateSpec::Every(2, TimeUnit::Week));
+        assert!(!feed.is_scheduled(noon(2024, 1, 23)));
+        assert!(feed.is_scheduled(noon(2024, 1, 25)));
+
+        let feed = read_on("2024-01-15", UpdateSpec::Every(1, TimeUnit::Month));
+        assert!(!feed.is_scheduled(noon(2024, 2, 14)));
+        assert!(feed.is_scheduled(noon(2024, 2, 15)));
+
+        // Clamped to the end of a shorter month
+        let feed = read_on("2024-01-31", UpdateSpec::Every(1, TimeUnit::Month));
+        assert!(!feed.is_scheduled(noon(2024, 2, 28)));
+        assert!(feed.is_scheduled(noon(2024, 2, 29)));
+        let feed = read_on("2023-01-31", UpdateSpec::Every(1, TimeUnit::Month));
+        assert!(feed.is_scheduled(noon(2023, 2, 28)));
+
+        // Across the end of the year
+        let feed = read_on("2023-12-15", UpdateSpec::Every(2, TimeUnit::Month));
+        assert!(!feed.is_scheduled(noon(2024, 2, 14)));
+        assert!(feed.is_scheduled(noon(2024, 2, 15)));
+    }
+
+    #[test]
+    fn test_open_list() {
+        let events = "<http://example.com/1>\n<http://example.com/2>\n";
+        let feed = make_feed(events, vec![]);
+        assert_eq!(
+            feed.get_open_list(OpenMode::Policy),
+            vec!["http://example.com/1"]
+        );
+
+        let feed = make_feed(events, vec![UpdateSpec::OpenAll]);
+        assert_eq!(
+            feed.get_open_list(OpenMode::Policy),
+            vec!["http://example.com/1", "http://example.com/2"],
+        );
+
+        let feed = make_feed(
+            events,
+            vec![UpdateSpec::OpenPage("http://example.com/latest".into())],
+        );
+        assert_eq!(
+            feed.get_open_list(OpenMode::Policy),
+            vec!["http://example.com/latest"]
+        );
+
+        let feed = make_feed(events, vec![UpdateSpec::OpenHomepage]);
+        assert_eq!(
+            feed.get_open_list(OpenMode::Policy),
+            vec!["http://example.com/"]
+        );
+
+        let feed = make_feed("", vec![UpdateSpec::OpenHomepage]);
+        assert!(feed.get_open_list(OpenMode::Policy).is_empty());
+    }
+
+    #[test]
+    fn test_describe_schedule() {
+        let describe = |policies: Vec<UpdateSpec>| {
+            let info = FeedInfo {
+                name: "Test Comic".into(),
+                url: "http://example.com/feed".into(),
+                update_policies: HashSet::from_iter(policies),
+                root: None,
+                command: None,
+                source: None,
+            };
+            info.describe_schedule()
+        };
+        assert_eq!(describe(vec![]), "as soon as available");
+        assert_eq!(
+            describe(vec![UpdateSpec::OpenAll, UpdateSpec::Overlap(1)]),
+            "as soon as available"
+        );
+        assert_eq!(
+            describe(vec![
+                UpdateSpec::Comics(3),
+                UpdateSpec::On(Weekday::Sat),
+                UpdateSpec::Every(7, TimeUnit::Day),
+            ]),
+            "every 7 days, on Saturdays, at least 3 new comics"
+        );
+        assert_eq!(
+            describe(vec![UpdateSpec::SkipWeekends, UpdateSpec::Comics(2)]),
+            "not on weekends, at least 2 new comics"
+        );
+        assert_eq!(
+            describe(vec![
+                UpdateSpec::Pace(1),
+                UpdateSpec::Every(1, TimeUnit::Day)
+            ]),
+            "every 1 day, 1 comic a day"
+        );
+        assert_eq!(
+            describe(vec![UpdateSpec::Every(1, TimeUnit::Month)]),
+            "every 1 month"
+        );
+        assert_eq!(
+            describe(vec![
+                UpdateSpec::On(Weekday::Sun),
+                UpdateSpec::On(Weekday::Wed),
+                UpdateSpec::Comics(1),
+            ]),
+            "on Wednesdays or Sundays, at least 1 new comic"
+        );
+        assert_eq!(
+            describe(vec![
+                UpdateSpec::ComicsOrAfter(5, 1),
+                UpdateSpec::EveryFrom(14, NaiveDate::from_ymd(2024, 1, 6)),
+            ]),
+            "every 14 days from 2024-01-06, at least 5 new comics or 1 day since the last read"
+        );
+    }
+
+    #[test]
+    fn test_open_all_backlog() {
+        let config = "\"Test Comic\" <http://example.com/feed> @ open all\n";
+        let info = crate::parser::parse_config(config).unwrap().remove(0);
+        let events = "<http://example.com/1>\nread 2017-07-17T03:21:21+00:00\n\
+                      <http://example.com/2>\n<http://example.com/3>\n<http://example.com/4>\n";
+        let feed = info.read_feed(&mut events.as_bytes()).unwrap();
+        let pending = vec![
+            "http://example.com/2",
+            "http://example.com/3",
+            "http://example.com/4",
+        ];
+        assert_eq!(feed.get_reading_list().into_urls(), pending);
+        assert_eq!(feed.get_open_list(OpenMode::Policy), pending);
+    }
+
+    #[test]
+    fn test_open_mode_overrides_policy() {
+        let events = "<http://example.com/1>\n<http://example.com/2>\n";
+        let both = vec!["http://example.com/1", "http://example.com/2"];
+
+        let feed = make_feed(events, vec![UpdateSpec::OpenAll]);
+        assert_eq!(
+            feed.get_open_list(OpenMode::FirstOnly),
+            vec!["http://example.com/1"]
+        );
+        assert_eq!(feed.get_open_list(OpenMode::All), both);
+
+        let feed = make_feed(events, vec![]);
+        assert_eq!(feed.get_open_list(OpenMode::All), both);
+
+        let feed = make_feed(events, vec![UpdateSpec::OpenHomepage]);
+        assert_eq!(
+            feed.get_open_list(OpenMode::All),
+            vec!["http://example.com/"]
+        );
+    }
+
+    #[test]
+    fn test_invariants() {
+        let mut feed = make_feed(
+            "<http://example.com/1>\nread 2017-07-17T03:21:21+00:00\n<http://example.com/2>\n",
+            vec![],
+        );
+        assert_eq!(feed.check_invariants(), Ok(()));
+        feed.add_new_comics(&["http://example.com/3".to_string()]);
+        assert_eq!(feed.check_invariants(), Ok(()));
+        feed.read();
+        assert_eq!(feed.check_invariants(), Ok(()));
+        assert_eq!(feed.new_comics, 0);
+        feed.write_changes(&mut io::Cursor::new(Vec::new()))
+            .unwrap();
+        assert_eq!(feed.check_invariants(), Ok(()));
+
+        let mut broken = feed.clone();
+        broken.new_comics = 3;
+        assert!(broken.check_invariants().is_err());
+
+        let mut broken = feed.clone();
+        broken.seen_comics.remove("http://example.com/2");
+        assert!(broken.check_invariants().is_err());
+
+        let mut broken = feed;
+        broken
+            .new_events
+            .push(FeedEvent::ComicUrl("http://example.com/1".into()));
+        assert!(broken.check_invariants().is_err());
+    }
 }
@@ -0,0 +1,1986 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::header::{
+    HeaderMap, HeaderValue, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+    LOCATION, SERVER, USER_AGENT,
+};
+use reqwest::{Client, ClientBuilder, RedirectPolicy, RequestBuilder, Response, StatusCode, Url};
+use serde_json::Value;
+
+use crate::cache::Cache;
+use crate::config::{stable_hash, Clock, SystemClock};
+use crate::error::Error;
+use crate::feed::{Feed, FeedInfo};
+
+/// The parts of a feed entry that feedburst cares about
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Entry {
+    title: Option<String>,
+    link: Option<String>,
+    guid: Option<String>,
+    /// The entry's description or content, for `@ notify-updates`
+    content: Option<String>,
+    /// When the entry was published, as written in the feed
+    published: Option<String>,
+    /// When the entry was last changed, which Atom and JSON Feed have
+    updated: Option<String>,
+}
+
+/// Split the feeds between `threads` fetching threads. Feeds marked `@ serial`
+/// all go to the first thread, so they're never fetched at the same time.
+pub fn partition_feeds(feeds: Vec<Feed>, threads: usize) -> Vec<Vec<Feed>> {
+    let mut groups: Vec<Vec<Feed>> = vec![vec![]; threads];
+    for (i, feed) in feeds.into_iter().enumerate() {
+        let group = if feed.info.is_serial() {
+            0
+        } else {
+            i % threads
+        };
+        groups[group].push(feed);
+    }
+    groups
+}
+
+/// Run `work` on each group's items in order, with a thread per group, sending
+/// back the results that aren't `None`
+pub fn spawn_groups<T, R, F>(groups: Vec<Vec<T>>, work: F) -> mpsc::Receiver<R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> Option<R> + Send + Sync + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    let work = Arc::new(work);
+    for group in groups {
+        let tx = tx.clone();
+        let work = work.clone();
+        thread::spawn(move || {
+            for item in group {
+                if let Some(result) = work(item) {
+                    tx.send(result).unwrap();
+                }
+            }
+        });
+    }
+    rx
+}
+
+/// How long to wait for a feed without `--timeout`
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How many times to try downloading a feed without `--attempts`
+pub const DEFAULT_ATTEMPTS: usize = 3;
+
+/// How long to wait before the first retry, which doubles after each one
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Sent without `--user-agent`, since some hosts turn away reqwest's default
+pub const DEFAULT_USER_AGENT: &str = concat!("feedburst/", env!("CARGO_PKG_VERSION"));
+
+pub fn user_agent_header(user_agent: &str) -> Result<HeaderValue, Error> {
+    HeaderValue::from_str(user_agent)
+        .map_err(|_| Error::Msg(format!("Invalid user agent {:?}", user_agent)))
+}
+
+/// How `build_client` sets up the HTTP client for a feed
+#[derive(Clone, Debug, PartialEq)]
+struct ClientSettings {
+    timeout: Option<Duration>,
+    user_agent: String,
+    /// Only ever set for the one feed that asked for it with `@ insecure`
+    accept_invalid_certs: bool,
+}
+
+fn client_settings(info: &FeedInfo, timeout: Option<Duration>, user_agent: &str) -> ClientSettings {
+    ClientSettings {
+        timeout,
+        user_agent: user_agent.into(),
+        accept_invalid_certs: info.is_insecure(),
+    }
+}
+
+/// The HTTP client to fetch a feed with, giving up after `timeout` if there is
+/// one. Certificate checks are only ever turned off for the one feed that asked
+/// for it with `@ insecure`.
+pub fn build_client(
+    info: &FeedInfo,
+    timeout: Option<Duration>,
+    user_agent: &str,
+) -> Result<Client, Error> {
+    let settings = client_settings(info, timeout, user_agent);
+    let mut headers = HeaderMap::new();
+    headers.insert(USER_AGENT, user_agent_header(&settings.user_agent)?);
+    let mut builder = ClientBuilder::new()
+        .timeout(settings.timeout)
+        .default_headers(headers)
+        // `send_request` follows them itself
+        .redirect(RedirectPolicy::none());
+    if settings.accept_invalid_certs {
+        eprintln!(
+            "Warning: not verifying the TLS certificate for \"{}\" <{}>",
+            info.name, info.url,
+        );
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    Ok(builder.build()?)
+}
+
+/// The request for a feed, with its headers from the secrets file
+pub fn build_request(client: &Client, url: &str, headers: &[(String, String)]) -> RequestBuilder {
+    let mut request = client.get(url);
+    for (name, value) in headers {
+        request = request.header(&name[..], &value[..]);
+    }
+    request
+}
+
+const MAX_REDIRECTS: usize = 10;
+
+/// Download a feed, following its redirects. `headers` are only sent to the
+/// feed's own scheme, host, and port, so a redirect can't hand its secrets to
+/// another site. With `@ follow-redirects`, if every redirect was permanent the
+/// feed's new URL is returned too.
+pub fn send_request(
+    client: &Client,
+    info: &FeedInfo,
+    headers: &[(String, String)],
+) -> Result<(Response, Option<Url>), Error> {
+    let mut url = Url::parse(&info.url)
+        .map_err(|err| Error::Msg(format!("{} (Invalid URL: {})", info.name, err)))?;
+    let origin = url.origin();
+    let mut redirected = false;
+    let mut permanent = true;
+    for _ in 0..MAX_REDIRECTS {
+        let sent = if url.origin() == origin {
+            headers
+        } else {
+            debug!("Not sending \"{}\"'s headers to <{}>", info.name, url);
+            &[]
+        };
+        let resp = build_request(client, url.as_str(), sent).send()?;
+        let status = resp.status();
+        let location = resp
+            .headers()
+            .get(LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .and_then(|location| url.join(location).ok());
+        match location {
+            Some(next) if status.is_redirection() => {
+                debug!("\"{}\" redirected ({}) to <{}>", info.name, status, next);
+                redirected = true;
+                permanent &= status == StatusCode::MOVED_PERMANENTLY
+                    || status == StatusCode::PERMANENT_REDIRECT;
+                url = next;
+            }
+            _ => {
+                let moved = if redirected && permanent && info.follows_redirects() {
+                    Some(url)
+                } else {
+                    None
+                };
+                return Ok((resp, moved));
+            }
+        }
+    }
+    Err(Error::Msg(format!("{} (Too many redirects)", info.name)))
+}
+
+/// Whether a failed request might work if it's tried again: it couldn't
+/// connect or timed out, rather than being refused by the server
+fn is_transient(err: &Error) -> bool {
+    match *err {
+        Error::Request(ref err) => err.is_http() || err.is_timeout(),
+        Error::Msg(_) | Error::Io(_) => false,
+    }
+}
+
+/// Like `send_request`, but tries up to `attempts` times while the connection
+/// fails, the server has an error (5xx), or it successfully sends nothing,
+/// waiting 1s, 2s, 4s, and so on in between. Other responses, like 404, are
+/// returned after the first try. The whitespace the body starts with is skipped.
+pub fn fetch_with_retry<C: Clock>(
+    client: &Client,
+    info: &FeedInfo,
+    headers: &[(String, String)],
+    attempts: usize,
+    clock: &mut C,
+) -> Result<(BufReader<Response>, Option<Url>), Error> {
+    let mut backoff = RETRY_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        let mut result = send_request(client, info, headers)
+            .map(|(resp, moved_to)| (BufReader::new(resp), moved_to));
+        let retry = match result {
+            Ok((ref mut body, _)) => {
+                let status = body.get_ref().status();
+                if status.is_success() {
+                    // An error reading it turns up again when it's parsed
+                    skip_blank(body).unwrap_or(false)
+                } else {
+                    // A Cloudflare challenge won't go away by itself
+                    status.is_server_error() && !is_challenged(body.get_ref().headers())
+                }
+            }
+            Err(ref err) => is_transient(err),
+        };
+        if !retry || attempt >= attempts {
+            return result;
+        }
+        debug!(
+            "Retrying \"{}\" in {:?} (attempt {} of {})",
+            info.name,
+            backoff,
+            attempt + 1,
+            attempts
+        );
+        clock.sleep(backoff);
+        backoff *= 2;
+        attempt += 1;
+    }
+}
+
+/// A downloaded page of a feed
+pub struct Body {
+    /// Its entries, oldest first
+    entries: Vec<Entry>,
+    /// An Atom feed's `<link rel="next">`, to its next page of older entries
+    next: Option<String>,
+    /// Where it was served from, after any redirects
+    pub url: Url,
+    /// Where the feed permanently redirected to, with `@ follow-redirects`
+    pub moved_to: Option<Url>,
+}
+
+/// Download a feed, reusing the cached copy when the server says it hasn't
+/// changed, and trying up to `attempts` times. The feed is parsed as it's read,
+/// and copied into the cache at the same time if it can be revalidated later.
+pub fn fetch_body(
+    client: &Client,
+    info: &FeedInfo,
+    headers: &[(String, String)],
+    cache: &Mutex<Cache>,
+    attempts: usize,
+) -> Result<Body, Error> {
+    let mut headers = headers.to_vec();
+    {
+        let cache = cache.lock().unwrap();
+        if let Some(etag) = cache.etag(&info.url) {
+            headers.push((IF_NONE_MATCH.as_str().into(), etag.into()));
+        }
+        if let Some(date) = cache.last_modified(&info.url) {
+            headers.push((IF_MODIFIED_SINCE.as_str().into(), date.into()));
+        }
+    }
+    let (mut body, moved_to) =
+        fetch_with_retry(client, info, &headers, attempts, &mut SystemClock)?;
+    let status = body.get_ref().status();
+    if is_challenged(body.get_ref().headers()) || status == StatusCode::SERVICE_UNAVAILABLE {
+        let mut page = String::new();
+        let _ = body.read_to_string(&mut page);
+        if is_cloudflare_challenge(status, body.get_ref().headers(), &page) {
+            return Err(Error::Msg(format!(
+                "{} (Blocked by a Cloudflare browser check, which feedburst can't pass. \
+                 This isn't an outage: ask the site to let feed readers through, or look for another feed URL)",
+                info.name
+            )));
+        }
+    }
+    let url = body.get_ref().url().clone();
+    let (entries, next) = if status == StatusCode::NOT_MODIFIED {
+        debug!("\"{}\" hasn't changed, using the cached copy", info.name);
+        let (cached, content_type) = {
+            let mut cache = cache.lock().unwrap();
+            let content_type = cache.content_type(&info.url).map(String::from);
+            (cache.get(&info.url, Utc::now()), content_type)
+        };
+        let cached = cached.ok_or_else(|| {
+            Error::Msg(format!(
+                "{} (Not modified, but the cached copy is missing)",
+                info.name
+            ))
+        })?;
+        read_page(BufReader::new(cached), content_type.as_deref(), info)?
+    } else if status.is_success() {
+        let header = |name| {
+            body.get_ref()
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(String::from)
+        };
+        let (etag, last_modified) = (header(ETAG), header(LAST_MODIFIED));
+        let content_type = header(CONTENT_TYPE);
+        if skip_blank(&mut body)? {
+            // Not cached, so the next fetch gets a fresh copy
+            return Err(Error::Msg(format!(
+                "{} returned an empty response",
+                info.name
+            )));
+        }
+        if etag.is_none() && last_modified.is_none() {
+            read_page(body, content_type.as_deref(), info)?
+        } else {
+            let temp = cache.lock().unwrap().temp_path(&info.url);
+            let copy = File::create(&temp);
+            let mut body = BufReader::new(Tee { reader: body, copy });
+            let page = read_page(&mut body, content_type.as_deref(), info);
+            let copy = body.into_inner().copy;
+            if page.is_ok() {
+                // The feed is fine without it, it just can't be revalidated next time
+                let cached = copy.map(drop).map_err(Error::from).and_then(|()| {
+                    cache.lock().unwrap().insert(
+                        &info.url,
+                        etag.as_deref(),
+                        last_modified.as_deref(),
+                        content_type.as_deref(),
+                        Utc::now(),
+                    )
+                });
+                if let Err(err) = cached {
+                    eprintln!("Warning: couldn't cache \"{}\": {}", info.name, err);
+                }
+            }
+            // Only left behind if it wasn't cached
+            let _ = fs::remove_file(&temp);
+            page?
+        }
+    } else {
+        debug!(
+            "Error \"{}\" fetching feed {} from {}",
+            status, info.name, info.url,
+        );
+        return Err(Error::Msg(format!(
+            "{} (Failed to download: \"{}\")",
+            info.name, status,
+        )));
+    };
+    Ok(Body::new((entries, next), url, moved_to))
+}
+
+impl Body {
+    /// A page served from `url`, with its links made absolute against it before
+    /// anything else, like the filters, sees them
+    fn new(
+        (mut entries, next): (Vec<Entry>, Option<String>),
+        url: Url,
+        moved_to: Option<Url>,
+    ) -> Self {
+        for entry in &mut entries {
+            entry.link = entry.link.take().map(|link| resolve_link(&url, link));
+        }
+        Body {
+            entries,
+            next: next.map(|next| resolve_link(&url, next)),
+            url,
+            moved_to,
+        }
+    }
+}
+
+/// Copies everything read through it into `copy`, until writing to it fails
+struct Tee<R, W> {
+    reader: R,
+    copy: io::Result<W>,
+}
+
+impl<R: Read, W: Write> Read for Tee<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.reader.read(buf)?;
+        if let Ok(ref mut copy) = self.copy {
+            if let Err(err) = copy.write_all(&buf[..len]) {
+                self.copy = Err(err);
+            }
+        }
+        Ok(len)
+    }
+}
+
+/// Cloudflare says when it answered with a challenge instead of the page
+fn is_challenged(headers: &HeaderMap) -> bool {
+    match headers
+        .get("cf-mitigated")
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(value) => value.trim().eq_ignore_ascii_case("challenge"),
+        None => false,
+    }
+}
+
+/// Text that only shows up on Cloudflare's "checking your browser" pages
+const CHALLENGE_MARKERS: &[&str] = &[
+    "cf-browser-verification",
+    "cf_chl_",
+    "challenge-platform",
+    "jschl",
+];
+
+/// Whether a response is Cloudflare's JavaScript challenge rather than the
+/// feed or a real outage
+fn is_cloudflare_challenge(status: StatusCode, headers: &HeaderMap, page: &str) -> bool {
+    if is_challenged(headers) {
+        return true;
+    }
+    let from_cloudflare = match headers.get(SERVER).and_then(|value| value.to_str().ok()) {
+        Some(server) => server.eq_ignore_ascii_case("cloudflare"),
+        None => false,
+    };
+    status == StatusCode::SERVICE_UNAVAILABLE
+        && from_cloudflare
+        && CHALLENGE_MARKERS.iter().any(|marker| page.contains(marker))
+}
+
+/// Skip the whitespace at the start of `body`, returning whether that was all
+/// of it. Some hosts occasionally send a successful response with nothing in it,
+/// which is a hiccup on their end rather than a malformed feed.
+fn skip_blank<R: BufRead>(body: &mut R) -> io::Result<bool> {
+    loop {
+        let buf = body.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(true);
+        }
+        let blank = buf
+            .iter()
+            .take_while(|byte| byte.is_ascii_whitespace())
+            .count();
+        if blank < buf.len() {
+            body.consume(blank);
+            return Ok(false);
+        }
+        body.consume(blank);
+    }
+}
+
+/// The `https://` version of an `http://` URL
+pub fn https_url(url: &str) -> Option<String> {
+    let rest = url
+        .get(..7)
+        .filter(|scheme| scheme.eq_ignore_ascii_case("http://"))?;
+    Some(format!("https://{}", &url[rest.len()..]))
+}
+
+/// Fetch an `http://` feed over HTTPS with `fetch`, falling back to the
+/// original URL if that fails. Also returns the HTTPS URL if it worked.
+pub fn fetch_upgraded<F>(info: &FeedInfo, fetch: F) -> Result<(Body, Option<String>), Error>
+where
+    F: Fn(&FeedInfo) -> Result<Body, Error>,
+{
+    let url = match https_url(&info.url) {
+        Some(url) => url,
+        None => return Ok((fetch(info)?, None)),
+    };
+    let upgraded = FeedInfo {
+        url: url.clone(),
+        ..info.clone()
+    };
+    match fetch(&upgraded) {
+        Ok(body) => Ok((body, Some(url))),
+        Err(err) => {
+            debug!("\"{}\" failed over HTTPS, falling back: {}", info.name, err);
+            Ok((fetch(info)?, None))
+        }
+    }
+}
+
+/// The links of `entries` that the feed's filters keep, and the ones they leave
+/// out. The filters see each entry's own link, before `@ url-template` rewrites it.
+fn partition_entries(entries: &[Entry], info: &FeedInfo) -> (Vec<String>, Vec<String>) {
+    let mut kept = Vec::new();
+    let mut filtered = Vec::new();
+    for entry in entries {
+        let link = match entry.link {
+            Some(ref link) => link,
+            None => continue,
+        };
+        let guid = entry.guid.as_ref().map(|guid| &guid[..]);
+        let url = info.comic_url(link, guid);
+        let title = entry.title.as_ref().map(|x| &x[..]).unwrap_or("");
+        if !info.filter_title(title) {
+            debug!("skipping by title: {:?}", entry.title);
+            filtered.push(url);
+        } else if !info.filter_url(link) {
+            filtered.push(url);
+        } else if !info.filter_entry(title, link) {
+            debug!("skipping by filter expression: {:?}", entry.title);
+            filtered.push(url);
+        } else {
+            kept.push(url);
+        }
+    }
+    (kept, filtered)
+}
+
+/// What's known about a comic besides its link
+#[derive(Clone, Debug, PartialEq)]
+pub struct ComicDetails {
+    pub url: String,
+    /// A hash of the comic's title and content, so comics that are edited in
+    /// place can be noticed with `@ notify-updates`
+    pub hash: u64,
+    /// When it was published, or updated if the feed doesn't say
+    pub published: Option<DateTime<Utc>>,
+    pub updated: Option<DateTime<Utc>>,
+}
+
+impl ComicDetails {
+    /// The date to order and date the comic by, which `@ order-by` chooses
+    pub fn date(&self, info: &FeedInfo) -> Option<DateTime<Utc>> {
+        if info.orders_by_updated() {
+            self.updated.or(self.published)
+        } else {
+            self.published
+        }
+    }
+}
+
+fn entry_details(entries: &[Entry], info: &FeedInfo) -> Vec<ComicDetails> {
+    let mut details = Vec::new();
+    for entry in entries {
+        let link = match entry.link {
+            Some(ref link) => link,
+            None => continue,
+        };
+        let guid = entry.guid.as_ref().map(|guid| &guid[..]);
+        let title = entry.title.as_ref().map(|x| &x[..]).unwrap_or("");
+        let body = entry.content.as_ref().map(|x| &x[..]).unwrap_or("");
+        details.push(ComicDetails {
+            url: info.comic_url(link, guid),
+            hash: stable_hash(&format!("{}\n{}", title, body)),
+            published: entry.published.as_ref().and_then(|date| parse_date(date)),
+            updated: entry.updated.as_ref().and_then(|date| parse_date(date)),
+        });
+    }
+    details
+}
+
+/// Feeds use RFC 3339 dates (Atom and JSON Feed) or RFC 2822 ones (RSS)
+fn parse_date(text: &str) -> Option<DateTime<Utc>> {
+    let text = text.trim();
+    let date = DateTime::parse_from_rfc3339(text).or_else(|_| DateTime::parse_from_rfc2822(text));
+    match date {
+        Ok(date) => Some(date.with_timezone(&Utc)),
+        Err(err) => {
+            debug!("Ignoring unreadable date {:?}: {}", text, err);
+            None
+        }
+    }
+}
+
+/// Read a page of a JSON, RSS, or Atom feed as it arrives, returning its entries
+/// oldest first and the link to its next page, if it has one
+fn read_page<R: BufRead>(
+    mut reader: R,
+    content_type: Option<&str>,
+    info: &FeedInfo,
+) -> Result<(Vec<Entry>, Option<String>), Error> {
+    skip_blank(&mut reader)?;
+    if is_json_content_type(content_type) || reader.fill_buf()?.first() == Some(&b'{') {
+        // The JSON is all parsed into a `Value` anyway, so it isn't streamed
+        let mut content = String::new();
+        reader.read_to_string(&mut content)?;
+        if is_json_content_type(content_type) || is_json_feed(&content) {
+            debug!("Parsed feed <{}> as JSON Feed", info.url);
+            return Ok((parse_json_feed(&content)?, None));
+        }
+        return stream_page(content.as_bytes(), info);
+    }
+    stream_page(reader, info)
+}
+
+fn stream_page<R: Read>(reader: R, info: &FeedInfo) -> Result<(Vec<Entry>, Option<String>), Error> {
+    let stream = stream_feed(reader)?;
+    debug!(
+        "Parsed feed <{}> as {}",
+        info.url,
+        if stream.atom == Some(true) {
+            "Atom"
+        } else {
+            "RSS"
+        }
+    );
+    Ok((stream.entries, stream.next))
+}
+
+/// Make a relative link absolute against `base`, the URL the feed was actually
+/// served from
+fn resolve_link(base: &Url, link: String) -> String {
+    match base.join(&link) {
+        Ok(url) => url.into_string(),
+        Err(err) => {
+            debug!("Couldn't resolve <{}> against <{}>: {}", link, base, err);
+            link
+        }
+    }
+}
+
+/// Which entry field the text being read belongs to
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Field {
+    Title,
+    Link,
+    Guid,
+    Content,
+    /// Atom's `<summary>`, which is only used when there's no `<content>`
+    Summary,
+    Published,
+    /// Atom's `<updated>`, which also stands in for a missing `<published>`
+    Updated,
+}
+
+/// Picks the entries out of a feed's XML events as they arrive
+#[derive(Debug, Default)]
+struct EntryStream {
+    /// Whether the feed is Atom rather than RSS, once the root element is seen
+    atom: Option<bool>,
+    depth: usize,
+    /// The entry being read, and the depth of its element
+    entry: Option<(Entry, usize)>,
+    field: Option<Field>,
+    text: String,
+    entries: Vec<Entry>,
+    /// An Atom feed's `<link rel="next">`, to its next page of older entries
+    next: Option<String>,
+}
+
+impl EntryStream {
+    fn handle(&mut self, event: xml::Event) -> Result<(), Error> {
+        match event {
+            xml::Event::ElementStart(tag) => {
+                self.depth += 1;
+                let atom = match self.atom {
+                    Some(atom) => atom,
+                    None => {
+                        let atom = match &tag.name[..] {
+                            "feed" => true,
+                            "rss" => false,
+                            _ => return Err(Error::Msg("Not an RSS or Atom feed".into())),
+                        };
+                        self.atom = Some(atom);
+                        return Ok(());
+                    }
+                };
+                // Only plain elements, not ones from extensions like `<media:title>`
+                if tag.prefix.is_some() {
+                    return Ok(());
+                }
+                let entry_name = if atom { "entry" } else { "item" };
+                let attribute = |name: &str| tag.attributes.get(&(name.to_string(), None));
+                match self.entry {
+                    None if tag.name == entry_name => {
+                        self.entry = Some((Entry::default(), self.depth));
+                    }
+                    None if atom
+                        && tag.name == "link"
+                        && self.depth == 2
+                        && attribute("rel").map(|rel| &rel[..]) == Some("next") =>
+                    {
+                        self.next = attribute("href").cloned();
+                    }
+                    Some((ref mut entry, depth)) if self.depth == depth + 1 => {
+                        self.field = match (&tag.name[..], atom) {
+                            ("title", _) => Some(Field::Title),
+                            ("link", false) => Some(Field::Link),
+                            ("guid", false) | ("id", true) => Some(Field::Guid),
+                            ("description", false) | ("content", true) => Some(Field::Content),
+                            ("summary", true) => Some(Field::Summary),
+                            ("pubDate", false) | ("published", true) => Some(Field::Published),
+                            ("updated", true) => Some(Field::Updated),
+                            ("link", true) => {
+                                if entry.link.is_none() {
+                                    entry.link = attribute("href").cloned();
+                                }
+                                None
+                            }
+                            _ => None,
+                        };
+                        self.text.clear();
+                    }
+                    _ => (),
+                }
+            }
+            xml::Event::ElementEnd(_) => {
+                if let Some((ref mut entry, depth)) = self.entry {
+                    if self.depth == depth {
+                        if self.atom == Some(true) && entry.title.is_none() {
+                            entry.title = Some(String::new());
+                        }
+                        self.entries.push(entry.clone());
+                        self.entry = None;
+                    } else if self.depth == depth + 1 {
+                        // Markup inside a field, like XHTML content, is part of its text
+                        if let Some(field) = self.field.take() {
+                            let text = Some(self.text.split_off(0));
+                            match field {
+                                Field::Title => entry.title = text,
+                                Field::Link => entry.link = text,
+                                Field::Guid => entry.guid = text,
+                                Field::Content => entry.content = text,
+                                Field::Summary => {
+                                    if entry.content.is_none() {
+                                        entry.content = text;
+                                    }
+                                }
+                                Field::Published => entry.published = text,
+                                Field::Updated => {
+                                    if entry.published.is_none() {
+                                        entry.published = text.clone();
+                                    }
+                                    entry.updated = text;
+                                }
+                            }
+                        }
+                    }
+                }
+                self.depth -= 1;
+            }
+            xml::Event::Characters(text) | xml::Event::CDATA(text) => {
+                if self.field.is_some() {
+                    self.text.push_str(&text);
+                }
+            }
+            xml::Event::PI(_) | xml::Event::Comment(_) => (),
+        }
+        Ok(())
+    }
+}
+
+/// Read an RSS or Atom feed a chunk at a time, keeping only the fields
+/// feedburst needs rather than the whole document, with the entries oldest first
+fn stream_feed<R: Read>(mut reader: R) -> Result<EntryStream, Error> {
+    let mut parser = xml::Parser::new();
+    let mut stream = EntryStream::default();
+    let mut chunk = [0; 8192];
+    let mut pending = Vec::new();
+    loop {
+        let len = reader.read(&mut chunk)?;
+        if len == 0 {
+            break;
+        }
+        pending.extend_from_slice(&chunk[..len]);
+        // A character can be split between chunks, so hold on to its start
+        let valid = match std::str::from_utf8(&pending) {
+            Ok(text) => text.len(),
+            Err(err) if err.error_len().is_none() => err.valid_up_to(),
+            Err(err) => return Err(Error::Msg(format!("Invalid feed: {}", err))),
+        };
+        parser.feed_str(std::str::from_utf8(&pending[..valid]).unwrap());
+        pending.drain(..valid);
+        for event in &mut parser {
+            let event = event.map_err(|err| Error::Msg(format!("Invalid feed: {}", err)))?;
+            stream.handle(event)?;
+        }
+    }
+    if !pending.is_empty() {
+        return Err(Error::Msg(
+            "Invalid feed: it ends partway through a character".into(),
+        ));
+    }
+    if stream.atom.is_none() {
+        return Err(Error::Msg("Invalid feed: it's empty".into()));
+    } else if stream.depth > 0 {
+        return Err(Error::Msg("Invalid feed: it ends early".into()));
+    }
+    stream.entries.reverse();
+    Ok(stream)
+}
+
+/// The most pages to fetch after the first when following `rel="next"` links
+pub const MAX_NEXT_PAGES: usize = 5;
+
+/// Follow an Atom feed's `rel="next"` links from its first page, getting each
+/// page with `fetch`, until a page has a comic that `is_seen`, there's no next
+/// page, or `MAX_NEXT_PAGES` more have been fetched. A page that can't be
+/// fetched stops there, with the pages so far. Returns the pages newest first.
+pub fn follow_next_pages<S, F>(info: &FeedInfo, first: Body, is_seen: S, mut fetch: F) -> Vec<Body>
+where
+    S: Fn(&str) -> bool,
+    F: FnMut(&str) -> Result<Body, Error>,
+{
+    let mut pages = vec![first];
+    while pages.len() <= MAX_NEXT_PAGES {
+        let page = &pages[pages.len() - 1];
+        let next = match page.next {
+            Some(ref next) => next.clone(),
+            None => break,
+        };
+        let (kept, filtered) = partition_entries(&page.entries, info);
+        let overlaps = kept.iter().chain(&filtered).any(|link| is_seen(link));
+        if overlaps || pages.iter().any(|page| page.url.as_str() == next) {
+            break;
+        }
+        debug!(
+            "Fetching the next page of \"{}\" from <{}>",
+            info.name, next
+        );
+        match fetch(&next) {
+            Ok(body) => pages.push(body),
+            Err(err) => {
+                debug!("Stopped paging \"{}\": {}", info.name, err);
+                break;
+            }
+        }
+    }
+    pages
+}
+
+/// The headers to fetch a page of a feed at `url` with. A `rel="next"` link can
+/// point anywhere, and `headers` from the secrets file are only for the feed's site.
+pub fn page_headers<'a>(
+    info: &FeedInfo,
+    url: &str,
+    headers: &'a [(String, String)],
+) -> &'a [(String, String)] {
+    match (Url::parse(&info.url), Url::parse(url)) {
+        (Ok(feed), Ok(page)) if feed.origin() == page.origin() => headers,
+        _ => &[],
+    }
+}
+
+/// The kept and filtered links of every page of a feed, newest page first,
+/// with the oldest first overall
+pub fn partition_page_links(pages: &[Body], info: &FeedInfo) -> (Vec<String>, Vec<String>) {
+    let (mut kept, mut filtered) = (Vec::new(), Vec::new());
+    for page in pages.iter().rev() {
+        let (page_kept, page_filtered) = partition_entries(&page.entries, info);
+        kept.extend(page_kept);
+        filtered.extend(page_filtered);
+    }
+    (kept, filtered)
+}
+
+/// The details of every comic on every page of a feed, like `partition_page_links`
+pub fn page_comic_details(pages: &[Body], info: &FeedInfo) -> Vec<ComicDetails> {
+    let mut details = Vec::new();
+    for page in pages.iter().rev() {
+        details.extend(entry_details(&page.entries, info));
+    }
+    details
+}
+
+const JSON_FEED_TYPE: &str = "application/feed+json";
+
+/// Whether the server said it's a JSON Feed, ignoring parameters like the charset
+fn is_json_content_type(content_type: Option<&str>) -> bool {
+    match content_type.and_then(|content_type| content_type.split(';').next()) {
+        Some(media_type) => media_type.trim().eq_ignore_ascii_case(JSON_FEED_TYPE),
+        None => false,
+    }
+}
+
+/// JSON Feeds are JSON objects declaring a jsonfeed.org version
+fn is_json_feed(content: &str) -> bool {
+    content.trim_start().starts_with('{') && content.contains("jsonfeed.org/version/")
+}
+
+fn parse_json_feed(content: &str) -> Result<Vec<Entry>, Error> {
+    let feed: Value = serde_json::from_str(content)
+        .map_err(|err| Error::Msg(format!("Invalid JSON Feed: {}", err)))?;
+    let items = feed["items"]
+        .as_array()
+        .ok_or_else(|| Error::Msg("Invalid JSON Feed: missing \"items\"".into()))?;
+    let string = |value: &Value| value.as_str().map(String::from);
+    Ok(items
+        .iter()
+        .rev()
+        .map(|item| Entry {
+            title: string(&item["title"]),
+            link: string(&item["url"]),
+            guid: string(&item["id"]),
+            content: string(&item["content_html"]).or_else(|| string(&item["content_text"])),
+            published: string(&item["date_published"]),
+            updated: string(&item["date_modified"]),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::feed::{DateField, FilterType, UpdateSpec};
+    use crate::scratch::ScratchDir;
+    use chrono::TimeZone;
+    use std::collections::HashSet;
+
+    fn make_info(policies: Vec<UpdateSpec>) -> FeedInfo {
+        FeedInfo {
+            name: "Test Comic".into(),
+            url: "http://example.com/feed".into(),
+            update_policies: policies.into_iter().collect::<HashSet<_>>(),
+            root: None,
+            command: None,
+            source: None,
+        }
+    }
+
+    /// The entries of a JSON, RSS, or Atom feed, oldest first
+    fn parse_entries(
+        content: &str,
+        content_type: Option<&str>,
+        info: &FeedInfo,
+    ) -> Result<Vec<Entry>, Error> {
+        Ok(read_page(content.as_bytes(), content_type, info)?.0)
+    }
+
+    /// Extract the comic links from a downloaded feed, oldest first, with the
+    /// feed's filters and URL rewriting applied. `content_type` is the response's
+    /// `Content-Type`, if it had one.
+    fn parse_feed_links(
+        content: &str,
+        content_type: Option<&str>,
+        info: &FeedInfo,
+    ) -> Result<Vec<String>, Error> {
+        Ok(partition_links(content, content_type, info)?.0)
+    }
+
+    /// Like `parse_feed_links`, but also returns the links the filters left out.
+    fn partition_links(
+        content: &str,
+        content_type: Option<&str>,
+        info: &FeedInfo,
+    ) -> Result<(Vec<String>, Vec<String>), Error> {
+        Ok(partition_entries(
+            &parse_entries(content, content_type, info)?,
+            info,
+        ))
+    }
+
+    /// The details of every comic in a feed, filtered or not, oldest first
+    fn comic_details(
+        content: &str,
+        content_type: Option<&str>,
+        info: &FeedInfo,
+    ) -> Result<Vec<ComicDetails>, Error> {
+        Ok(entry_details(
+            &parse_entries(content, content_type, info)?,
+            info,
+        ))
+    }
+
+    const JSON_FEED: &str = r#"{
+        "version": "https://jsonfeed.org/version/1",
+        "title": "Test Comic",
+        "items": [
+            {"id": "3", "url": "http://example.com/3", "title": "Page 3"},
+            {"id": "guest-1", "url": "http://example.com/guest-1", "title": "Guest Comic"},
+            {"id": "2", "title": "Announcement with no link"},
+            {"id": "1", "url": "http://example.com/1", "title": "Page 1"}
+        ]
+    }"#;
+
+    fn json_entries() -> Vec<Entry> {
+        parse_entries(JSON_FEED, None, &make_info(vec![])).unwrap()
+    }
+
+    #[test]
+    fn test_json_feed() {
+        assert_eq!(
+            parse_feed_links(JSON_FEED, None, &make_info(vec![])).unwrap(),
+            vec![
+                "http://example.com/1",
+                "http://example.com/guest-1",
+                "http://example.com/3",
+            ],
+        );
+
+        let info = make_info(vec![UpdateSpec::Filter(
+            FilterType::IgnoreTitle,
+            "Guest".into(),
+        )]);
+        assert_eq!(
+            parse_feed_links(JSON_FEED, None, &info).unwrap(),
+            vec!["http://example.com/1", "http://example.com/3"],
+        );
+
+        let not_a_feed = r#"{"version": "https://jsonfeed.org/version/1"}"#;
+        assert!(parse_feed_links(not_a_feed, None, &info).is_err());
+    }
+
+    #[test]
+    fn test_filters_before_url_template() {
+        let info = make_info(vec![
+            UpdateSpec::UrlTemplate("https://m.example.com/comic/{slug}".into()),
+            UpdateSpec::SlugFrom(r"^(\d+)$".into()),
+            UpdateSpec::Filter(FilterType::IgnoreUrl, r"^http://example\.com/1$".into()),
+        ]);
+        let (kept, filtered) = partition_links(JSON_FEED, None, &info).unwrap();
+        assert_eq!(
+            kept,
+            vec![
+                "http://example.com/guest-1",
+                "https://m.example.com/comic/3"
+            ]
+        );
+        assert_eq!(filtered, vec!["https://m.example.com/comic/1"]);
+    }
+
+    /// A JSON Feed that's only recognizable from its `Content-Type`
+    const UNVERSIONED_FEED: &str = r#"{"items": [{"id": "1", "url": "http://example.com/1"}]}"#;
+
+    #[test]
+    fn test_json_feed_content_type() {
+        let unversioned = UNVERSIONED_FEED;
+        let info = make_info(vec![]);
+        for content_type in &[
+            "application/feed+json",
+            "Application/Feed+JSON; charset=utf-8",
+        ] {
+            assert_eq!(
+                parse_feed_links(unversioned, Some(content_type), &info).unwrap(),
+                vec!["http://example.com/1"],
+            );
+        }
+        assert!(parse_feed_links(unversioned, None, &info).is_err());
+        assert!(parse_feed_links(unversioned, Some("application/json"), &info).is_err());
+
+        // A JSON Feed served as something else is still sniffed out
+        assert_eq!(
+            parse_feed_links(JSON_FEED, Some("text/plain"), &info)
+                .unwrap()
+                .len(),
+            3
+        );
+        assert!(parse_feed_links("<rss/>", Some("application/feed+json"), &info).is_err());
+    }
+
+    #[test]
+    fn test_serial_feeds_one_at_a_time() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let feeds: Vec<_> = (0..8)
+            .map(|i| {
+                let policies = if i % 3 == 0 {
+                    vec![UpdateSpec::Serial]
+                } else {
+                    vec![]
+                };
+                let info = FeedInfo {
+                    name: format!("Comic {}", i),
+                    ..make_info(policies)
+                };
+                info.read_feed(&mut "".as_bytes()).unwrap()
+            })
+            .collect();
+        let groups = partition_feeds(feeds, 4);
+        assert_eq!(groups.len(), 4);
+        let serial = groups[0].iter().filter(|feed| feed.info.is_serial());
+        assert_eq!(serial.count(), 3);
+
+        // A mock fetcher that tracks how many serial feeds are in flight at once
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+        let (counter, peak) = (in_flight.clone(), most.clone());
+        let rx = spawn_groups(groups, move |feed: Feed| {
+            if feed.info.is_serial() {
+                let now = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+            }
+            thread::sleep(std::time::Duration::from_millis(20));
+            if feed.info.is_serial() {
+                counter.fetch_sub(1, Ordering::SeqCst);
+            }
+            Some(feed.info.name)
+        });
+        let mut names: Vec<_> = rx.iter().collect();
+        names.sort();
+        assert_eq!(names.len(), 8);
+        assert_eq!(most.load(Ordering::SeqCst), 1);
+    }
+
+    /// Hands out at most a few bytes at a time, to split characters between reads
+    struct Trickle<'a>(&'a [u8]);
+
+    impl<'a> Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(self.0.len()).min(7);
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn test_stream_large_feed() {
+        let mut rss = String::from(
+            "<?xml version=\"1.0\"?>\n<rss version=\"2.0\" xmlns:media=\"http://search.yahoo.com/mrss/\">\
+             <channel><title>Test Comic</title><link>http://example.com/</link>\
+             <description>Comics</description>",
+        );
+        for i in 0..2000 {
+            rss.push_str(&format!(
+                "<item><title>漫画 #{0} &amp; more</title><link>http://example.com/{0}</link>\
+                 <guid isPermaLink=\"false\">comic-{0}</guid><media:title>Ignored</media:title>\
+                 <description><![CDATA[<p>Page {0}</p>]]></description></item>",
+                i
+            ));
+        }
+        rss.push_str("</channel></rss>");
+
+        let mut atom = String::from(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\
+             <title>Test Comic</title><id>urn:feed</id><updated>2018-01-02T03:04:05Z</updated>",
+        );
+        for i in 0..2000 {
+            atom.push_str(&format!(
+                "<entry><title>Page {0}</title><id>urn:comic:{0}</id>\
+                 <updated>2018-01-02T03:04:05Z</updated>\
+                 <link href=\"http://example.com/{0}\"/><link rel=\"edit\" href=\"http://example.com/edit\"/>\
+                 </entry>",
+                i
+            ));
+        }
+        atom.push_str("</feed>");
+
+        let oldest = [
+            Entry {
+                title: Some("漫画 #1999 & more".into()),
+                link: Some("http://example.com/1999".into()),
+                guid: Some("comic-1999".into()),
+                content: Some("<p>Page 1999</p>".into()),
+                published: None,
+                updated: None,
+            },
+            Entry {
+                title: Some("Page 1999".into()),
+                link: Some("http://example.com/1999".into()),
+                guid: Some("urn:comic:1999".into()),
+                content: None,
+                published: Some("2018-01-02T03:04:05Z".into()),
+                updated: Some("2018-01-02T03:04:05Z".into()),
+            },
+        ];
+        for (feed, oldest) in [rss, atom].iter().zip(&oldest) {
+            let whole = stream_feed(feed.as_bytes()).unwrap().entries;
+            assert_eq!(whole.len(), 2000);
+            assert_eq!(&whole[0], oldest);
+            assert_eq!(
+                stream_feed(Trickle(feed.as_bytes())).unwrap().entries,
+                whole
+            );
+        }
+        assert_eq!(
+            stream_feed(Trickle(
+                "<rss><channel><item><title>漫画</title></item></channel></rss>".as_bytes()
+            ))
+            .unwrap()
+            .entries[0]
+                .title,
+            Some("漫画".into())
+        );
+
+        assert!(stream_feed("<html><body>Oops</body></html>".as_bytes()).is_err());
+        assert!(stream_feed("".as_bytes()).is_err());
+        assert!(stream_feed("<rss><channel><item>".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_content_hashes() {
+        let info = make_info(vec![UpdateSpec::NotifyUpdates]);
+        let feed = |art: &str| {
+            format!(
+                r#"{{"version": "https://jsonfeed.org/version/1", "items": [
+                    {{"id": "2", "url": "http://example.com/2", "content_html": "<img src='2.png'>"}},
+                    {{"id": "1", "url": "http://example.com/1", "content_html": "<img src='{}'>"}}
+                ]}}"#,
+                art
+            )
+        };
+        let before = comic_details(&feed("1.png"), None, &info).unwrap();
+        let after = comic_details(&feed("1-fixed.png"), None, &info).unwrap();
+        assert_eq!(before.len(), 2);
+        assert_eq!(before[0].url, "http://example.com/1");
+        assert_eq!(after[0].url, before[0].url);
+        assert_ne!(after[0].hash, before[0].hash);
+        assert_eq!(after[1], before[1]);
+    }
+
+    #[test]
+    fn test_publication_dates() {
+        let rss = r#"<rss version="2.0"><channel><title>Test Comic</title>
+<item><link>http://example.com/2</link><pubDate>Tue, 02 Jan 2018 03:04:05 -0500</pubDate></item>
+<item><link>http://example.com/1</link><pubDate>sometime</pubDate></item>
+</channel></rss>"#;
+        let atom = r#"<feed xmlns="http://www.w3.org/2005/Atom"><title>Test Comic</title>
+<entry><id>urn:2</id><published>2018-01-02T08:04:05Z</published>
+<updated>2018-02-01T00:00:00Z</updated><link href="http://example.com/2"/></entry>
+<entry><id>urn:1</id><link href="http://example.com/1"/></entry>
+</feed>"#;
+        let expected = vec![None, Some(Utc.ymd(2018, 1, 2).and_hms(8, 4, 5))];
+        for feed in &[rss, atom] {
+            let dates: Vec<_> = comic_details(feed, None, &make_info(vec![]))
+                .unwrap()
+                .into_iter()
+                .map(|comic| comic.published)
+                .collect();
+            assert_eq!(dates, expected);
+        }
+    }
+
+    #[test]
+    fn test_order_by_updated() {
+        // Page 1 was published first but corrected most recently
+        let atom = r#"<feed xmlns="http://www.w3.org/2005/Atom"><title>Test Comic</title>
+<entry><id>urn:3</id><updated>2018-01-05T00:00:00Z</updated>
+<link href="http://example.com/3"/></entry>
+<entry><id>urn:2</id><published>2018-01-02T00:00:00Z</published>
+<updated>2018-01-03T00:00:00Z</updated><link href="http://example.com/2"/></entry>
+<entry><id>urn:1</id><published>2018-01-01T00:00:00Z</published>
+<updated>2018-01-04T00:00:00Z</updated><link href="http://example.com/1"/></entry>
+</feed>"#;
+        let day = |day| Some(Utc.ymd(2018, 1, day).and_hms(0, 0, 0));
+        let by_published = make_info(vec![UpdateSpec::PreserveOrder]);
+        let by_updated = make_info(vec![
+            UpdateSpec::PreserveOrder,
+            UpdateSpec::OrderBy(DateField::Updated),
+        ]);
+        let details = comic_details(atom, None, &by_published).unwrap();
+        let dates = |info: &FeedInfo| {
+            details
+                .iter()
+                .map(|comic| comic.date(info))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(dates(&by_published), vec![day(1), day(2), day(5)]);
+        assert_eq!(dates(&by_updated), vec![day(4), day(3), day(5)]);
+        let explicit = make_info(vec![UpdateSpec::OrderBy(DateField::Published)]);
+        assert_eq!(dates(&explicit), dates(&by_published));
+
+        let order = |info: &FeedInfo| {
+            let mut feed = info.read_feed(&mut &b""[..]).unwrap();
+            let urls: Vec<_> = details.iter().map(|comic| comic.url.clone()).collect();
+            feed.add_new_comics(&urls);
+            let dates: Vec<_> = details
+                .iter()
+                .filter_map(|comic| comic.date(info).map(|date| (comic.url.clone(), date)))
+                .collect();
+            feed.add_publication_dates(&dates);
+            feed.get_reading_list().new
+        };
+        let urls = |pages: &[u32]| {
+            pages
+                .iter()
+                .map(|page| format!("http://example.com/{}", page))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(order(&by_published), urls(&[1, 2, 3]));
+        assert_eq!(order(&by_updated), urls(&[2, 1, 3]));
+    }
+
+    #[test]
+    fn test_partition_links() {
+        let info = make_info(vec![UpdateSpec::Filter(
+            FilterType::IgnoreTitle,
+            "Guest".into(),
+        )]);
+        let (kept, filtered) = partition_links(JSON_FEED, None, &info).unwrap();
+        assert_eq!(kept, vec!["http://example.com/1", "http://example.com/3"]);
+        assert_eq!(filtered, vec!["http://example.com/guest-1"]);
+    }
+
+    /// Page `page` of an Atom feed with `last` pages of two comics, linking to the next one
+    fn atom_page(page: usize, last: usize) -> Body {
+        let mut content =
+            String::from(r#"<feed xmlns="http://www.w3.org/2005/Atom"><title>Test Comic</title>"#);
+        if page < last {
+            content.push_str(&format!(
+                r#"<link rel="self" href="/feed?page={}"/><link rel="next" href="/feed?page={}"/>"#,
+                page,
+                page + 1
+            ));
+        }
+        // Newest first, two comics a page
+        for comic in &[2 * (last - page) + 2, 2 * (last - page) + 1] {
+            content.push_str(&format!(
+                r#"<entry><id>urn:{0}</id><link href="/{0}"/></entry>"#,
+                comic
+            ));
+        }
+        content.push_str("</feed>");
+        Body::new(
+            read_page(content.as_bytes(), None, &make_info(vec![])).unwrap(),
+            Url::parse(&format!("http://example.com/feed?page={}", page)).unwrap(),
+            None,
+        )
+    }
+
+    #[test]
+    fn test_follow_next_pages() {
+        let info = make_info(vec![]);
+        let page_number = |url: &str| url.rsplit('=').next().unwrap().parse().unwrap();
+        let follow = |last: usize, seen: &[&str]| {
+            let mut fetched = Vec::new();
+            let pages = follow_next_pages(
+                &info,
+                atom_page(1, last),
+                |url| seen.contains(&url),
+                |url| {
+                    fetched.push(page_number(url));
+                    Ok(atom_page(page_number(url), last))
+                },
+            );
+            let (links, _) = partition_page_links(&pages, &info);
+            (fetched, links)
+        };
+        let comics = |numbers: &[usize]| {
+            numbers
+                .iter()
+                .map(|number| format!("http://example.com/{}", number))
+                .collect::<Vec<_>>()
+        };
+
+        // Two linked pages, oldest comic first
+        assert_eq!(follow(2, &[]), (vec![2], comics(&[1, 2, 3, 4])));
+        // The first page overlaps, so there's no need for the second
+        assert_eq!(
+            follow(2, &["http://example.com/3"]),
+            (vec![], comics(&[3, 4]))
+        );
+        // The second one does, so the third isn't fetched
+        assert_eq!(
+            follow(3, &["http://example.com/3"]),
+            (vec![2], comics(&[3, 4, 5, 6]))
+        );
+        // At most a few more pages
+        let (fetched, links) = follow(100, &[]);
+        assert_eq!(fetched, (2..=MAX_NEXT_PAGES + 1).collect::<Vec<_>>());
+        assert_eq!(links.len(), 2 * (MAX_NEXT_PAGES + 1));
+
+        // A page that can't be fetched keeps the ones before it
+        let pages = follow_next_pages(
+            &info,
+            atom_page(1, 3),
+            |_| false,
+            |_| Err(Error::Msg("Failed to download".into())),
+        );
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].next, Some("http://example.com/feed?page=2".into()));
+        assert_eq!(
+            read_page(JSON_FEED.as_bytes(), None, &info).unwrap().1,
+            None
+        );
+    }
+
+    #[test]
+    fn test_page_headers() {
+        let info = make_info(vec![]);
+        let headers = [("X-Api-Key".to_string(), "hunter2".to_string())];
+        assert_eq!(
+            page_headers(&info, "http://example.com/feed?page=2", &headers),
+            &headers
+        );
+        for url in &[
+            "http://elsewhere.com/feed?page=2",
+            "https://example.com/feed?page=2",
+            "http://example.com:8080/feed?page=2",
+            "/feed?page=2",
+        ] {
+            assert!(page_headers(&info, url, &headers).is_empty(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_rss_and_atom() {
+        let rss = r#"<?xml version="1.0"?>
+<rss version="2.0"><channel>
+<title>Test Comic</title><link>http://example.com/</link><description>A comic</description>
+<item><title>Page 2</title><link>http://example.com/2</link></item>
+<item><title>Page 1</title><link>http://example.com/1</link></item>
+</channel></rss>"#;
+        assert_eq!(
+            parse_feed_links(rss, None, &make_info(vec![])).unwrap(),
+            vec!["http://example.com/1", "http://example.com/2"],
+        );
+
+        let atom = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+<title>Test Comic</title><id>urn:test</id><updated>2017-07-17T03:21:21Z</updated>
+<entry><title>Page 2</title><id>urn:2</id><updated>2017-07-17T03:21:21Z</updated>
+<link href="http://example.com/2"/></entry>
+<entry><title>Page 1</title><id>urn:1</id><updated>2017-07-16T03:21:21Z</updated>
+<link href="http://example.com/1"/></entry>
+</feed>"#;
+        assert_eq!(
+            parse_feed_links(atom, None, &make_info(vec![])).unwrap(),
+            vec!["http://example.com/1", "http://example.com/2"],
+        );
+
+        assert!(parse_feed_links("<html>Oops</html>", None, &make_info(vec![])).is_err());
+    }
+
+    #[test]
+    fn test_insecure_is_per_feed() {
+        let feeds = crate::parser::parse_config(
+            r#"
+"Expired" <https://expired.example.com/feed> @ insecure
+"Fine" <https://example.com/feed>
+"#,
+        )
+        .unwrap();
+        assert!(feeds[0].is_insecure());
+        assert!(!feeds[1].is_insecure());
+        let settings = |info| client_settings(info, Some(DEFAULT_TIMEOUT), DEFAULT_USER_AGENT);
+        assert_eq!(
+            settings(&feeds[0]),
+            ClientSettings {
+                timeout: Some(DEFAULT_TIMEOUT),
+                user_agent: DEFAULT_USER_AGENT.into(),
+                accept_invalid_certs: true,
+            }
+        );
+        // The other feed in the same config still checks certificates
+        assert_eq!(
+            settings(&feeds[1]),
+            ClientSettings {
+                accept_invalid_certs: false,
+                ..settings(&feeds[0])
+            }
+        );
+        for info in &feeds {
+            assert!(build_client(info, Some(DEFAULT_TIMEOUT), DEFAULT_USER_AGENT).is_ok());
+        }
+    }
+
+    /// Serves the test routes: `/new` as a feed, with `/old` permanently and
+    /// `/temp` temporarily redirecting to it, along with the cache, retry,
+    /// user agent and empty body cases used by the fetch tests.
+    fn mock_server() -> String {
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let mut flaky_requests = 0;
+            let mut blank_requests = 0;
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let len = stream.read(&mut buf).unwrap();
+                    if len == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..len]);
+                }
+                let request = String::from_utf8_lossy(&request);
+                let lower = request.to_lowercase();
+                let since = format!("if-modified-since: {}", LAST_MODIFIED_DATE.to_lowercase());
+                let response = if request.starts_with("GET /old ") {
+                    "HTTP/1.1 301 Moved Permanently\r\nLocation: /new\r\n".to_string()
+                } else if request.starts_with("GET /temp ") {
+                    "HTTP/1.1 302 Found\r\nLocation: /new\r\n".to_string()
+                } else if lower.contains("if-none-match: \"v1\"") || lower.contains(&since) {
+                    "HTTP/1.1 304 Not Modified\r\n".to_string()
+                } else if request.starts_with("GET /dated ") {
+                    format!(
+                        "HTTP/1.1 200 OK\r\nLast-Modified: {}\r\nContent-Length: {}\r\n\r\n{}",
+                        LAST_MODIFIED_DATE,
+                        JSON_FEED.len(),
+                        JSON_FEED
+                    )
+                } else if request.starts_with("GET /agent ")
+                    && !lower.contains("user-agent: feedburst/")
+                {
+                    "HTTP/1.1 403 Forbidden\r\n".to_string()
+                } else if request.starts_with("GET /flaky ") {
+                    flaky_requests += 1;
+                    if flaky_requests <= 2 {
+                        "HTTP/1.1 503 Service Unavailable\r\n".to_string()
+                    } else {
+                        format!(
+                            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                            JSON_FEED.len(),
+                            JSON_FEED
+                        )
+                    }
+                } else if request.starts_with("GET /blank-once ") {
+                    blank_requests += 1;
+                    if blank_requests == 1 {
+                        "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n \n".to_string()
+                    } else {
+                        format!(
+                            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                            JSON_FEED.len(),
+                            JSON_FEED
+                        )
+                    }
+                } else if request.starts_with("GET /gone ") {
+                    "HTTP/1.1 404 Not Found\r\n".to_string()
+                } else if request.starts_with("GET /empty ") {
+                    "HTTP/1.1 200 OK\r\nETag: \"v2\"\r\nContent-Length: 3\r\n\r\n\n  ".to_string()
+                } else if request.starts_with("GET /typed ") {
+                    format!(
+                        "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Type: application/feed+json\r\n\
+                         Content-Length: {}\r\n\r\n{}",
+                        UNVERSIONED_FEED.len(),
+                        UNVERSIONED_FEED
+                    )
+                } else if request.starts_with("GET /etag ") {
+                    format!(
+                        "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: {}\r\n\r\n{}",
+                        JSON_FEED.len(),
+                        JSON_FEED
+                    )
+                } else {
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                        JSON_FEED.len(),
+                        JSON_FEED
+                    )
+                };
+                let response = if response.ends_with("\r\n") {
+                    format!("{}Content-Length: 0\r\n\r\n", response)
+                } else {
+                    response
+                };
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        base
+    }
+
+    /// A feed at `path` on a fresh mock server, with an empty cache in its own
+    /// scratch directory and a client with the default settings.
+    fn fetch_fixture(path: &str, scratch: &str) -> (FeedInfo, ScratchDir, Mutex<Cache>, Client) {
+        let info = FeedInfo {
+            url: format!("{}{}", mock_server(), path),
+            ..make_info(vec![])
+        };
+        let dir = ScratchDir::new(scratch);
+        let cache =
+            Mutex::new(Cache::open(dir.to_path_buf(), crate::cache::MAX_CACHE_BYTES).unwrap());
+        let client = build_client(&info, Some(DEFAULT_TIMEOUT), DEFAULT_USER_AGENT).unwrap();
+        (info, dir, cache, client)
+    }
+
+    #[test]
+    fn test_not_modified_uses_cache() {
+        let (info, _dir, cache, client) = fetch_fixture("/etag", "fetch-cache");
+
+        let body = fetch_body(&client, &info, &[], &cache, DEFAULT_ATTEMPTS).unwrap();
+        assert_eq!(body.entries, json_entries());
+        assert_eq!(cache.lock().unwrap().etag(&info.url), Some("\"v1\""));
+
+        // Only the cache could have this
+        let cached = JSON_FEED.replace("Page 3", "Page Three");
+        {
+            let mut cache = cache.lock().unwrap();
+            std::fs::write(cache.temp_path(&info.url), &cached).unwrap();
+            cache
+                .insert(&info.url, Some("\"v1\""), None, None, Utc::now())
+                .unwrap();
+        }
+        let body = fetch_body(&client, &info, &[], &cache, DEFAULT_ATTEMPTS).unwrap();
+        assert_eq!(body.entries, parse_entries(&cached, None, &info).unwrap());
+    }
+
+    #[test]
+    fn test_not_modified_keeps_content_type() {
+        let (info, _dir, cache, client) = fetch_fixture("/typed", "fetch-typed");
+
+        let expected = parse_entries(UNVERSIONED_FEED, Some(JSON_FEED_TYPE), &info).unwrap();
+        let body = fetch_body(&client, &info, &[], &cache, DEFAULT_ATTEMPTS).unwrap();
+        assert_eq!(body.entries, expected);
+        // The second time is a 304, so the type has to come from the cache
+        let body = fetch_body(&client, &info, &[], &cache, DEFAULT_ATTEMPTS).unwrap();
+        assert_eq!(body.entries, expected);
+    }
+
+    /// Records how long it was asked to sleep, instead of sleeping
+    struct FakeClock(Vec<Duration>);
+
+    impl Clock for FakeClock {
+        fn now(&self) -> std::time::Instant {
+            std::time::Instant::now()
+        }
+
+        fn sleep(&mut self, duration: Duration) {
+            self.0.push(duration);
+        }
+    }
+
+    #[test]
+    fn test_fetch_with_retry() {
+        let base = mock_server();
+        let info = |path: &str| FeedInfo {
+            url: format!("{}{}", base, path),
+            ..make_info(vec![])
+        };
+        let client = build_client(&info(""), Some(DEFAULT_TIMEOUT), DEFAULT_USER_AGENT).unwrap();
+        let secs = Duration::from_secs;
+
+        // Fails twice, then works
+        let mut clock = FakeClock(vec![]);
+        let (mut body, _) = fetch_with_retry(&client, &info("/flaky"), &[], 3, &mut clock).unwrap();
+        assert_eq!(body.get_ref().status(), StatusCode::OK);
+        let mut content = String::new();
+        body.read_to_string(&mut content).unwrap();
+        assert_eq!(content, JSON_FEED);
+        assert_eq!(clock.0, vec![secs(1), secs(2)]);
+
+        // Empty once, then a feed
+        let mut clock = FakeClock(vec![]);
+        let (mut body, _) =
+            fetch_with_retry(&client, &info("/blank-once"), &[], 3, &mut clock).unwrap();
+        let mut content = String::new();
+        body.read_to_string(&mut content).unwrap();
+        assert_eq!(content, JSON_FEED);
+        assert_eq!(clock.0, vec![secs(1)]);
+
+        // Still empty after every try
+        let mut clock = FakeClock(vec![]);
+        let (mut body, _) = fetch_with_retry(&client, &info("/empty"), &[], 3, &mut clock).unwrap();
+        assert!(skip_blank(&mut body).unwrap());
+        assert_eq!(clock.0, vec![secs(1), secs(2)]);
+
+        // Not found won't change by trying again
+        let mut clock = FakeClock(vec![]);
+        let (body, _) = fetch_with_retry(&client, &info("/gone"), &[], 3, &mut clock).unwrap();
+        assert_eq!(body.get_ref().status(), StatusCode::NOT_FOUND);
+        assert!(clock.0.is_empty());
+
+        // Nothing listening, so every attempt fails to connect
+        let closed = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            format!("http://{}/feed", listener.local_addr().unwrap())
+        };
+        let closed = FeedInfo {
+            url: closed,
+            ..make_info(vec![])
+        };
+        let mut clock = FakeClock(vec![]);
+        assert!(fetch_with_retry(&client, &closed, &[], 4, &mut clock).is_err());
+        assert_eq!(clock.0, vec![secs(1), secs(2), secs(4)]);
+        let mut clock = FakeClock(vec![]);
+        assert!(fetch_with_retry(&client, &closed, &[], 1, &mut clock).is_err());
+        assert!(clock.0.is_empty());
+    }
+
+    #[test]
+    fn test_cache_failure_still_fetches() {
+        let (info, dir, cache, client) = fetch_fixture("/etag", "fetch-uncached");
+        fs::remove_dir(&*dir).unwrap();
+
+        let body = fetch_body(&client, &info, &[], &cache, DEFAULT_ATTEMPTS).unwrap();
+        assert_eq!(body.entries, json_entries());
+        assert_eq!(cache.lock().unwrap().etag(&info.url), None);
+    }
+
+    #[test]
+    fn test_empty_body() {
+        let is_empty = |body: &str| skip_blank(&mut body.as_bytes()).unwrap();
+        assert!(is_empty(""));
+        assert!(is_empty(" \r\n\t"));
+        assert!(!is_empty("<rss/>"));
+        assert!(!is_empty(JSON_FEED));
+
+        let (info, _dir, cache, client) = fetch_fixture("/empty", "fetch-empty");
+        // Retrying is covered by `test_fetch_with_retry`, so this doesn't wait for it
+        match fetch_body(&client, &info, &[], &cache, 1) {
+            Err(Error::Msg(msg)) => assert_eq!(msg, "Test Comic returned an empty response"),
+            Err(err) => panic!("Unexpected error {}", err),
+            Ok(_) => panic!("An empty body shouldn't be returned as a feed"),
+        }
+        // Otherwise its ETag would keep the next fetch from getting a real copy
+        assert_eq!(cache.lock().unwrap().etag(&info.url), None);
+    }
+
+    #[test]
+    fn test_cloudflare_challenge() {
+        let headers = |pairs: &[(&'static str, &'static str)]| {
+            let mut headers = HeaderMap::new();
+            for &(name, value) in pairs {
+                headers.insert(name, HeaderValue::from_static(value));
+            }
+            headers
+        };
+        let unavailable = StatusCode::SERVICE_UNAVAILABLE;
+        let cloudflare = headers(&[("server", "cloudflare")]);
+        let page = r#"<html><title>Just a moment...</title>
+<script src="/cdn-cgi/challenge-platform/h/b/orchestrate/jsch/v1"></script></html>"#;
+        assert!(is_cloudflare_challenge(unavailable, &cloudflare, page));
+
+        // A normal outage, whether or not it's behind Cloudflare
+        let outage = "<html><h1>503 Service Temporarily Unavailable</h1></html>";
+        assert!(!is_cloudflare_challenge(unavailable, &cloudflare, outage));
+        let nginx = headers(&[("server", "nginx")]);
+        assert!(!is_cloudflare_challenge(unavailable, &nginx, page));
+        assert!(!is_cloudflare_challenge(StatusCode::OK, &cloudflare, page));
+
+        // Cloudflare's own header is enough, whatever the status
+        let mitigated = headers(&[("server", "cloudflare"), ("cf-mitigated", "challenge")]);
+        assert!(is_cloudflare_challenge(
+            StatusCode::FORBIDDEN,
+            &mitigated,
+            ""
+        ));
+        assert!(is_cloudflare_challenge(StatusCode::OK, &mitigated, ""));
+    }
+
+    #[test]
+    fn test_user_agent() {
+        assert!(DEFAULT_USER_AGENT.starts_with("feedburst/"));
+        assert!(DEFAULT_USER_AGENT.ends_with(env!("CARGO_PKG_VERSION")));
+        assert!(user_agent_header("bad\nagent").is_err());
+
+        let (info, _dir, cache, _) = fetch_fixture("/agent", "fetch-agent");
+        let fetch = |user_agent| {
+            let client = build_client(&info, Some(DEFAULT_TIMEOUT), user_agent).unwrap();
+            fetch_body(&client, &info, &[], &cache, DEFAULT_ATTEMPTS)
+        };
+        assert_eq!(fetch(DEFAULT_USER_AGENT).unwrap().entries, json_entries());
+        assert!(fetch("Mozilla/5.0").is_err());
+    }
+
+    const LAST_MODIFIED_DATE: &str = "Sat, 01 Jan 2000 00:00:00 GMT";
+
+    #[test]
+    fn test_not_modified_since_leaves_feed_alone() {
+        use std::io::Cursor;
+
+        let (info, _dir, cache, client) = fetch_fixture("/dated", "fetch-dated");
+        let mut feed = info.read_feed(&mut "".as_bytes()).unwrap();
+        let mut feed_file = Cursor::new(Vec::new());
+        let fetch = |feed: &mut Feed, feed_file: &mut Cursor<Vec<u8>>| {
+            let body = fetch_body(&client, &info, &[], &cache, DEFAULT_ATTEMPTS).unwrap();
+            let (links, _) = partition_entries(&body.entries, &info);
+            let arrived = feed.add_new_comics(&links);
+            feed.write_changes(feed_file).unwrap();
+            (body.entries, arrived)
+        };
+
+        assert_eq!(fetch(&mut feed, &mut feed_file), (json_entries(), 3));
+        assert_eq!(
+            cache.lock().unwrap().last_modified(&info.url),
+            Some(LAST_MODIFIED_DATE)
+        );
+        let written = feed_file.get_ref().clone();
+
+        // Only the cache could have this, so it's only seen if the server says 304
+        let cached = JSON_FEED.replace("Page 3", "Page Three");
+        {
+            let mut cache = cache.lock().unwrap();
+            std::fs::write(cache.temp_path(&info.url), &cached).unwrap();
+            cache
+                .insert(&info.url, None, Some(LAST_MODIFIED_DATE), None, Utc::now())
+                .unwrap();
+        }
+        assert_eq!(
+            fetch(&mut feed, &mut feed_file),
+            (parse_entries(&cached, None, &info).unwrap(), 0)
+        );
+        assert_eq!(feed_file.get_ref(), &written);
+    }
+
+    #[test]
+    fn test_https_upgrade() {
+        assert_eq!(
+            https_url("HTTP://example.com/feed"),
+            Some("https://example.com/feed".into())
+        );
+        assert_eq!(https_url("https://example.com/feed"), None);
+        assert_eq!(https_url("ftp://x"), None);
+
+        let serve = |info: &FeedInfo| {
+            Body::new(
+                read_page(JSON_FEED.as_bytes(), None, info).unwrap(),
+                Url::parse(&info.url).unwrap(),
+                None,
+            )
+        };
+        let refuse = |info: &FeedInfo| Err(Error::Msg(format!("{} (Refused)", info.name)));
+
+        let info = make_info(vec![]);
+        let https_only = |info: &FeedInfo| match &info.url[..5] {
+            "https" => Ok(serve(info)),
+            _ => refuse(info),
+        };
+        let (body, upgraded) = fetch_upgraded(&info, https_only).unwrap();
+        assert_eq!(upgraded, Some("https://example.com/feed".into()));
+        assert_eq!(body.url.as_str(), "https://example.com/feed");
+        assert_eq!(partition_page_links(&[body], &info).0.len(), 3);
+
+        // Falls back when HTTPS doesn't work
+        let http_only = |info: &FeedInfo| match &info.url[..5] {
+            "https" => refuse(info),
+            _ => Ok(serve(info)),
+        };
+        let (_, upgraded) = fetch_upgraded(&info, http_only).unwrap();
+        assert_eq!(upgraded, None);
+        assert!(fetch_upgraded(&info, |_| Err(Error::Msg("down".into()))).is_err());
+    }
+
+    /// Serves a feed, except that `/moved` permanently redirects to `location`.
+    /// Returns its URL and the requests it gets, lowercased.
+    fn recording_server(location: Option<String>) -> (String, Arc<Mutex<Vec<String>>>) {
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let location = location.unwrap_or_else(|| format!("{}/feed", base));
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let len = stream.read(&mut buf).unwrap();
+                    if len == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..len]);
+                }
+                let request = String::from_utf8_lossy(&request).to_lowercase();
+                let response = if request.starts_with("get /moved ") {
+                    format!(
+                        "HTTP/1.1 301 Moved Permanently\r\nLocation: {}\r\nContent-Length: 0\r\n\r\n",
+                        location
+                    )
+                } else {
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                        JSON_FEED.len(),
+                        JSON_FEED
+                    )
+                };
+                recorded.lock().unwrap().push(request);
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        (base, requests)
+    }
+
+    #[test]
+    fn test_redirect_keeps_secrets() {
+        let (other, other_requests) = recording_server(None);
+        let (same, same_requests) = recording_server(None);
+        let (cross, cross_requests) = recording_server(Some(format!("{}/feed", other)));
+        let headers = [("X-Api-Key".to_string(), "hunter2".to_string())];
+        let secret = |request: &String| request.contains("x-api-key: hunter2");
+
+        for policies in &[vec![], vec![UpdateSpec::FollowRedirects]] {
+            let info = |url: &str| FeedInfo {
+                url: format!("{}/moved", url),
+                ..make_info(policies.clone())
+            };
+            let client =
+                build_client(&info(""), Some(DEFAULT_TIMEOUT), DEFAULT_USER_AGENT).unwrap();
+
+            // The same site gets them after the redirect too
+            let (resp, _) = send_request(&client, &info(&same), &headers).unwrap();
+            assert!(resp.status().is_success());
+            let requests = same_requests.lock().unwrap().split_off(0);
+            assert_eq!(requests.len(), 2);
+            assert!(requests.iter().all(secret));
+
+            // Another site doesn't
+            let (resp, _) = send_request(&client, &info(&cross), &headers).unwrap();
+            assert!(resp.status().is_success());
+            let requests = cross_requests.lock().unwrap().split_off(0);
+            assert_eq!(requests.len(), 1);
+            assert!(secret(&requests[0]));
+            let requests = other_requests.lock().unwrap().split_off(0);
+            assert_eq!(requests.len(), 1);
+            assert!(!secret(&requests[0]));
+        }
+    }
+
+    #[test]
+    fn test_permanent_redirect() {
+        let base = mock_server();
+        let config = format!(
+            "# Comics\n\"Moved\" <{0}/old> @ follow-redirects\n\"Temp\" <{0}/temp> @ follow-redirects\n",
+            base
+        );
+        let feeds = crate::parser::parse_config(&config).unwrap();
+
+        let client = build_client(&feeds[0], Some(DEFAULT_TIMEOUT), DEFAULT_USER_AGENT).unwrap();
+        let (mut resp, moved) = send_request(&client, &feeds[0], &[]).unwrap();
+        let new_url = format!("{}/new", base);
+        assert_eq!(moved.as_ref().map(Url::as_str), Some(&new_url[..]));
+        let mut content = String::new();
+        std::io::Read::read_to_string(&mut resp, &mut content).unwrap();
+        assert_eq!(
+            parse_feed_links(&content, None, &feeds[0]).unwrap().len(),
+            3
+        );
+
+        let updated = crate::parser::replace_feed_url(&config, "Moved", &new_url).unwrap();
+        let updated = crate::parser::parse_config(&updated).unwrap();
+        assert_eq!(updated[0].url, new_url);
+        assert_eq!(updated[0].update_policies, feeds[0].update_policies);
+        assert_eq!(updated[1], feeds[1]);
+
+        // Temporary redirects are followed, but don't move the feed
+        let client = build_client(&feeds[1], Some(DEFAULT_TIMEOUT), DEFAULT_USER_AGENT).unwrap();
+        let (resp, moved) = send_request(&client, &feeds[1], &[]).unwrap();
+        assert!(resp.status().is_success());
+        assert_eq!(moved, None);
+
+        // Without the policy, reqwest follows redirects on its own
+        let info = make_info(vec![]);
+        let info = FeedInfo {
+            url: format!("{}/old", base),
+            ..info
+        };
+        let client = build_client(&info, Some(DEFAULT_TIMEOUT), DEFAULT_USER_AGENT).unwrap();
+        let (resp, moved) = send_request(&client, &info, &[]).unwrap();
+        assert_eq!(resp.url().as_str(), new_url);
+        assert_eq!(moved, None);
+    }
+
+    #[test]
+    fn test_relative_links() {
+        let rss = r#"<?xml version="1.0"?>
+<rss version="2.0"><channel>
+<title>Test Comic</title><link>http://example.com/</link><description>A comic</description>
+<item><title>Page 4</title><link>//mirror.example.org/4</link></item>
+<item><title>Page 3</title><link>https://cdn.example.net/3</link></item>
+<item><title>Page 2</title><link>2</link></item>
+<item><title>Page 1</title><link>/comic/1</link></item>
+</channel></rss>"#;
+        let info = make_info(vec![]);
+        let base = Url::parse("http://example.com/comic/feed.xml").unwrap();
+        let page = Body::new(read_page(rss.as_bytes(), None, &info).unwrap(), base, None);
+        assert_eq!(
+            partition_page_links(&[page], &info).0,
+            vec![
+                "http://example.com/comic/1",
+                "http://example.com/comic/2",
+                "https://cdn.example.net/3",
+                "http://mirror.example.org/4",
+            ],
+        );
+
+        // The filters see the resolved links, like they do for absolute ones
+        let info = make_info(vec![UpdateSpec::Filter(
+            FilterType::KeepUrl,
+            r"^http://example\.com/comic/".into(),
+        )]);
+        let base = Url::parse("http://example.com/comic/feed.xml").unwrap();
+        let page = Body::new(read_page(rss.as_bytes(), None, &info).unwrap(), base, None);
+        let (kept, filtered) = partition_page_links(&[page], &info);
+        assert_eq!(
+            kept,
+            vec!["http://example.com/comic/1", "http://example.com/comic/2"]
+        );
+        assert_eq!(
+            filtered,
+            vec!["https://cdn.example.net/3", "http://mirror.example.org/4"]
+        );
+
+        // Scheme-relative links keep the scheme the feed was served over
+        let base = Url::parse("https://example.com/feed").unwrap();
+        assert_eq!(
+            resolve_link(&base, "//mirror.example.org/4".into()),
+            "https://mirror.example.org/4"
+        );
+    }
+}
@@ -1,21 +1,34 @@
 #[macro_use]
 extern crate log;
 
-use std::io::Read;
-use std::str::FromStr;
+use std::cmp::min;
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Read};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
-use chrono::Local;
-use clap::{App, Arg};
+use chrono::{Local, Utc};
+use clap::{App, Arg, SubCommand};
 
+mod backup;
+mod cache;
 mod config;
 mod error;
 mod feed;
+mod fetch;
 mod parse_util;
 mod parser;
 mod platform;
+#[cfg(test)]
+mod scratch;
+mod state;
+mod stats;
 
-use crate::error::{Error, ParseError, Span};
-use crate::feed::Feed;
+use crate::cache::Cache;
+use crate::error::{Error, ParseError};
+use crate::feed::{Feed, FeedInfo};
+use crate::parser::ConfigError;
 
 const APP_NAME: &str = env!("CARGO_PKG_NAME");
 
@@ -26,8 +39,24 @@ fn main() {
     }
 }
 
+/// Show the levels chosen with `-v`, on top of whatever `RUST_LOG` asks for
+fn init_logger(verbosity: u64) {
+    let mut builder = match pretty_env_logger::formatted_builder() {
+        Ok(builder) => builder,
+        Err(_) => return,
+    };
+    let rust_log = env::var("RUST_LOG");
+    if let Ok(ref filters) = rust_log {
+        builder.parse(filters);
+    }
+    // Added last so it wins over RUST_LOG's default level, but not its per-module ones
+    if verbosity > 0 || rust_log.is_err() {
+        builder.filter_level(config::log_level(verbosity));
+    }
+    builder.init();
+}
+
 fn run() -> Result<(), Error> {
-    pretty_env_logger::init();
     let matches = App::new(APP_NAME)
         .version(env!("CARGO_PKG_VERSION"))
         .author(env!("CARGO_PKG_AUTHORS"))
@@ -39,11 +68,40 @@ fn run() -> Result<(), Error> {
                 .help("The config file to load feeds from")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("config-dir")
+                .long("config-dir")
+                .value_name("PATH")
+                .help("The folder to keep the config and secrets files in")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("data-dir")
+                .long("data-dir")
+                .value_name("PATH")
+                .help(
+                    "The folder to keep feeds and other state in, instead of the platform default",
+                )
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("feeds")
                 .long("feeds")
                 .value_name("PATH")
-                .help("The folder where feeds are stored")
+                .help(concat!(
+                    "The folder where feeds are stored, overriding any root in the config. ",
+                    "Give it more than once to search several folders in order; new feeds ",
+                    "go in the first one that's writable",
+                ))
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("default-feeds")
+                .long("default-feeds")
+                .value_name("PATH")
+                .help("The folder where feeds without a root in the config are stored")
                 .takes_value(true),
         )
         .arg(
@@ -53,37 +111,314 @@ fn run() -> Result<(), Error> {
                 .help(concat!(
                     "The command to open the comic with. Any instance of @URL ",
                     "will be replaced with the comic URL, and if @URL isn't ",
-                    "mentioned, the URL will be placed at the end of the command.",
+                    "mentioned, the URL will be placed at the end of the command. ",
+                    "Use @URLENC inside a larger argument for the percent-encoded URL.",
                 ))
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("open-with-for")
+                .long("open-with-for")
+                .value_names(&["NAME", "COMMAND"])
+                .number_of_values(2)
+                .multiple(true)
+                .help("Like --open-with, but only for the named feed"),
+        )
+        .arg(
+            Arg::with_name("only")
+                .long("only")
+                .value_name("NAME")
+                .help("Only fetch and read the named feed")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("check-command")
+                .long("check-command")
+                .value_name("COMMAND")
+                .help("Check how an open command would be parsed and run, then exit")
+                .takes_value(true),
+        )
+        .arg(Arg::with_name("shell").long("shell").help(concat!(
+            "Run the open command through the shell (sh -c, or cmd /C on Windows) so it ",
+            "can use pipes and &&. The URL is quoted, but the rest of the command is ",
+            "interpreted by the shell, so only use commands you trust.",
+        )))
         .arg(
             Arg::with_name("fetch")
                 .long("fetch")
                 .help("Only download feeds, don't view them"),
         )
+        .arg(
+            Arg::with_name("open-first-only")
+                .long("open-first-only")
+                .conflicts_with("open-all")
+                .help("Only open the first new comic of each feed, even with @ open all"),
+        )
+        .arg(
+            Arg::with_name("open-all")
+                .long("open-all")
+                .help("Open every new comic of each feed, even without @ open all"),
+        )
+        .arg(
+            Arg::with_name("max-tabs")
+                .long("max-tabs")
+                .value_name("N")
+                .help("Open at most N comics in all, shared between the ready feeds, leaving the rest unread")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("queue-file")
+                .long("queue-file")
+                .value_name("PATH")
+                .help(concat!(
+                    "Instead of opening comics, append their URLs to this file (or FIFO), ",
+                    "one per line, for another program to read",
+                ))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("mark-read")
+                .long("mark-read")
+                .value_name("NAME")
+                .min_values(0)
+                .max_values(1)
+                .help("Mark ready feeds read without opening anything, or just the named feed"),
+        )
+        .arg(
+            Arg::with_name("open-stdin")
+                .long("open-stdin")
+                .help(concat!(
+                    "Run the open command once per feed and write the comics' URLs to its ",
+                    "stdin, one per line, instead of passing each one as an argument",
+                )),
+        )
+        .arg(
+            Arg::with_name("open-delay")
+                .long("open-delay")
+                .value_name("MS")
+                .help("Wait this many milliseconds between opening comics, for launchers that drop some")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("https-upgrade")
+                .long("https-upgrade")
+                .help(concat!(
+                    "Fetch http:// feeds over https:// instead, falling back to http:// if that ",
+                    "fails, and suggest updating your config when it works",
+                )),
+        )
+        .arg(Arg::with_name("porcelain").long("porcelain").help(concat!(
+            "Instead of opening ready feeds, print a line for each: its name, URL, and ",
+            "number of unread comics, separated by tabs. This format won't change.",
+        )))
+        .arg(Arg::with_name("json").long("json").help(concat!(
+            "Instead of opening ready feeds, print a JSON array describing every feed: its ",
+            "name, url, unread_count, whether it's ready, and its reading_list",
+        )))
+        .arg(Arg::with_name("catch-up").long("catch-up").help(concat!(
+            "Ignore @ every and @ on for this run, so any feed with enough new ",
+            "comics is shown",
+        )))
+        .arg(
+            Arg::with_name("jobs")
+                .long("jobs")
+                .short("j")
+                .value_name("N")
+                .help("How many feeds to download at once, 4 by default (also FEEDBURST_JOBS)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("timeout")
+                .long("timeout")
+                .value_name("SECONDS")
+                .help("How long to wait for each feed to download, 5 by default, or 0 to wait forever")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("attempts")
+                .long("attempts")
+                .value_name("N")
+                .help(concat!(
+                    "How many times to try downloading each feed when it can't connect or ",
+                    "the server has an error, 3 by default",
+                ))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("user-agent")
+                .long("user-agent")
+                .value_name("AGENT")
+                .help("The User-Agent header to download feeds with (also FEEDBURST_USER_AGENT)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("tz")
+                .long("tz")
+                .value_name("OFFSET")
+                .help(concat!(
+                    "The timezone to count days in for scheduling, as UTC or an offset ",
+                    "like +09:00, instead of the system's",
+                ))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("since-last-run")
+                .long("since-last-run")
+                .help("Report how many comics arrived since the last time feedburst ran"),
+        )
+        .arg(
+            Arg::with_name("no-backup")
+                .long("no-backup")
+                .help("Don't back up feed files before rewriting them"),
+        )
+        .arg(
+            Arg::with_name("feed-format")
+                .long("feed-format")
+                .value_name("FORMAT")
+                .possible_values(&["lines", "json"])
+                .help("Write feed files as lines of events or as JSON, reading either (without it, new files use lines and old ones keep their format)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("keep-going")
+                .long("keep-going")
+                .help("Skip feeds whose line in the config has a mistake, instead of stopping"),
+        )
+        .arg(
+            Arg::with_name("list-config")
+                .long("list")
+                .help("Print the feeds in your config and their policies, then exit"),
+        )
+        .arg(
+            Arg::with_name("print-config-path")
+                .long("print-config-path")
+                .help("Print where your config file is, then exit"),
+        )
+        .arg(
+            Arg::with_name("print-feed-path")
+                .long("print-feed-path")
+                .value_name("NAME")
+                .help("Print where a feed's comics and reading history are kept, then exit")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("bookmark")
+                .long("bookmark")
+                .value_names(&["NAME", "URL"])
+                .help("Bookmark a comic from one of your feeds to revisit later")
+                .takes_value(true)
+                .number_of_values(2),
+        )
+        .arg(
+            Arg::with_name("touch")
+                .long("touch")
+                .value_name("NAME")
+                .help("Mark a feed as just fetched, without downloading it")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("bookmarks")
+                .long("bookmarks")
+                .help("List the comics you've bookmarked, then exit"),
+        )
+        .arg(
+            Arg::with_name("config-migrate")
+                .long("config-migrate")
+                .help("Rewrite every feed file in the current format, then exit"),
+        )
+        .arg(
+            Arg::with_name("stats")
+                .long("stats")
+                .help("Print statistics about your reading across all feeds, then exit"),
+        )
+        .arg(
+            Arg::with_name("show-filtered")
+                .long("show-filtered")
+                .value_name("NAME")
+                .help("List the entries a feed's filters have left out (with @ record-filtered)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("repair")
+                .long("repair")
+                .value_name("NAME")
+                .help("Remove duplicate entries from a feed's file, keeping your place")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("restore")
+                .long("restore")
+                .value_name("NAME")
+                .help("Restore a feed's file from its most recent backup")
+                .takes_value(true),
+        )
+        .subcommand(
+            SubCommand::with_name("validate")
+                .about("Check your config for mistakes without fetching, printing \"Config OK\" if there are none"),
+        )
+        .subcommand(
+            SubCommand::with_name("list")
+                .about("Show each feed's unread comics and whether it's ready, without fetching")
+                .arg(
+                    Arg::with_name("unread-only")
+                        .long("unread-only")
+                        .help("Only show feeds with unread comics"),
+                ),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .short("v")
+                .long("verbose")
+                .multiple(true)
+                .help("Explain what's happening, more with -vv and -vvv (also see RUST_LOG)"),
+        )
         .max_term_width(120)
         .get_matches();
+    init_logger(matches.occurrences_of("verbose"));
+
+    if let Some(command) = matches.value_of("check-command") {
+        print!(
+            "{}",
+            config::check_command(command, matches.is_present("shell"))?
+        );
+        return Ok(());
+    }
 
     let only_fetch = matches.value_of("fetch").is_some();
-    let args = config::Args::new(
-        only_fetch,
-        matches.value_of("feeds"),
-        matches.value_of("config"),
-        matches.value_of("open-with"),
-    )?;
-
-    let feeds = {
-        let mut file = args.config_file()?;
+    let catch_up = matches.is_present("catch-up");
+    let porcelain = matches.is_present("porcelain");
+    let json = matches.is_present("json");
+    let args = config::Args::new(&matches)?;
+
+    if matches.is_present("print-config-path") {
+        println!("{}", args.config_path().display());
+        return Ok(());
+    }
+
+    let validate = matches.subcommand_matches("validate").is_some();
+    // Mistakes in feed lines, which `validate` reports all of instead of stopping
+    let mut problems = 0;
+    let (feeds, included, first_run) = {
+        // Only reading, so a missing config isn't created
+        let (mut file, created) = if matches.is_present("print-feed-path") || validate {
+            (args.existing_config_file()?, false)
+        } else {
+            args.open_config()?
+        };
         let mut text = String::new();
         file.read_to_string(&mut text)?;
 
-        let make_error_message = |row: usize, span: Span, msg: &str| -> Error {
-            let mut message = format!(
-                "Line {}: Error parsing {}\n\n",
-                row,
-                args.config_path().display(),
-            );
+        let make_error_message = |err: ConfigError| -> Error {
+            let ParseError::Expected { msg, row, span } = err.error;
+            // Errors in included files show the line from that file
+            let (path, text) = match err.include {
+                Some(path) => {
+                    let included = fs::read_to_string(&path).unwrap_or_default();
+                    (path, included)
+                }
+                None => (args.config_path().to_path_buf(), text.clone()),
+            };
+            let mut message = format!("Line {}: Error parsing {}\n\n", row, path.display());
             let line = text.lines().nth(row - 1).unwrap_or_default();
             message.push_str(&format!("{}\n", line));
             match span {
@@ -98,15 +433,59 @@ fn run() -> Result<(), Error> {
             Error::Msg(message)
         };
 
-        match parser::parse_config(&text) {
-            Ok(feeds) => feeds,
-            Err(ParseError::Expected { msg, row, span }) => {
-                return Err(make_error_message(row, span, &msg));
+        let keep_going = matches.is_present("keep-going") || validate;
+        match parser::parse_config_file(args.config_path(), &text, keep_going) {
+            Ok((feeds, errors, included)) => {
+                for err in errors {
+                    if validate {
+                        eprintln!("{}\n", make_error_message(err));
+                        problems += 1;
+                    } else {
+                        eprintln!(
+                            "{}\nSkipping that feed because of --keep-going\n",
+                            make_error_message(err)
+                        );
+                    }
+                }
+                (feeds, included, created)
             }
+            Err(err) => return Err(make_error_message(err)),
         }
     };
 
-    if feeds.is_empty() {
+    if !validate {
+        args.check_feed_paths(&feeds, &included)?;
+    }
+    for (name, other) in config::shared_urls(&feeds) {
+        eprintln!(
+            "Warning: \"{}\" and \"{}\" have the same feed URL, so they'll get the same comics",
+            other, name
+        );
+    }
+
+    if validate {
+        return validate_config(&args, &feeds, &included, problems);
+    }
+
+    if let Some(name) = matches.value_of("print-feed-path") {
+        println!("{}", args.feed_path_named(&feeds, name)?.display());
+        return Ok(());
+    }
+
+    if let Some(name) = matches.value_of("restore") {
+        let info = feeds
+            .iter()
+            .find(|info| info.name == name)
+            .ok_or_else(|| Error::Msg(format!("No feed named \"{}\" in your config", name)))?;
+        let backup = args.restore_feed(info)?;
+        println!("Restored \"{}\" from {}", name, backup.display());
+        return Ok(());
+    }
+
+    if feeds.is_empty() && first_run {
+        println!("{}", config::first_run_message(args.config_path()));
+        return Ok(());
+    } else if feeds.is_empty() {
         println!(
             "You're not following any comics. Add some to your config file at {}",
             args.config_path().display(),
@@ -114,6 +493,20 @@ fn run() -> Result<(), Error> {
         return Ok(());
     }
 
+    if matches.is_present("list-config") {
+        for info in &feeds {
+            println!("{}", info.to_config_line());
+            println!("    read {}", info.describe_schedule());
+            if let Some(ref root) = info.root {
+                println!("    stored in {}", root.display());
+            }
+            if let Some(ref command) = info.command {
+                println!("    opened with {}", command.join(" "));
+            }
+        }
+        return Ok(());
+    }
+
     let mut feeds: Vec<_> = feeds
         .into_iter()
         .map(|info| {
@@ -129,144 +522,476 @@ fn run() -> Result<(), Error> {
         })
         .collect();
 
-    // Fetch the feeds that are currently scheduled, not those that are unscheduled
-    feeds.sort_by_key(|feed| !feed.is_scheduled(Local::now()));
+    if let Some(list) = matches.subcommand_matches("list") {
+        let color = io::stdout().is_terminal();
+        let now = Utc::now();
+        for feed in &feeds {
+            if list.is_present("unread-only") && feed.unread_count() == 0 {
+                continue;
+            }
+            println!("{} <{}>", feed.info.display_name(color), feed.info.url);
+            let status = match args.timezone() {
+                Some(tz) => feed.describe_status(now.with_timezone(&tz)),
+                None => feed.describe_status(now.with_timezone(&Local)),
+            };
+            println!("    {}", status);
+        }
+        return Ok(());
+    }
 
-    let rx = {
-        let (tx, rx) = std::sync::mpsc::channel();
-        const NUM_THREADS: usize = 4;
-        let mut groups: Vec<Vec<Feed>> = vec![vec![]; NUM_THREADS];
-        for (i, feed) in feeds.into_iter().enumerate() {
-            groups[i % NUM_THREADS].push(feed);
+    if let Some(name) = matches.value_of("show-filtered") {
+        let feed = feeds
+            .iter()
+            .find(|feed| feed.info.name == name)
+            .ok_or_else(|| Error::Msg(format!("No feed named \"{}\" in your config", name)))?;
+        if !feed.info.records_filtered() {
+            eprintln!(
+                "\"{}\" doesn't have @ record-filtered, so nothing is recorded",
+                name
+            );
+        }
+        for url in feed.filtered() {
+            println!("{}", url);
         }
+        return Ok(());
+    }
 
-        for group in groups {
-            let tx = tx.clone();
-            let args = args.clone();
-            std::thread::spawn(move || {
-                for feed in group {
-                    let name = feed.info.name.clone();
-                    match fetch_feed(&args, feed) {
-                        Ok(feed) => tx.send(feed).unwrap(),
-                        Err(Error::Msg(err)) => eprintln!("{}", err),
-                        Err(err) => eprintln!("Error in feed {}: {}", name, err),
-                    }
-                }
-            });
+    if let Some(name) = matches.value_of("repair") {
+        let feed = feeds
+            .iter_mut()
+            .find(|feed| feed.info.name == name)
+            .ok_or_else(|| Error::Msg(format!("No feed named \"{}\" in your config", name)))?;
+        let removed = feed.repair();
+        args.rewrite_feed(feed)?;
+        println!(
+            "Repaired \"{}\", removing {} duplicate lines",
+            name, removed
+        );
+        return Ok(());
+    }
+
+    if matches.is_present("stats") {
+        let stats = match args.timezone() {
+            Some(tz) => stats::Stats::collect(&feeds, &tz),
+            None => stats::Stats::collect(&feeds, &Local),
+        };
+        print!("{}", stats.report());
+        return Ok(());
+    }
+
+    if matches.is_present("config-migrate") {
+        for feed in &feeds {
+            args.rewrite_feed(feed)?;
+        }
+        println!("Migrated {} feed files", feeds.len());
+        return Ok(());
+    }
+
+    if let Some(mut values) = matches.values_of("bookmark") {
+        let (name, url) = (values.next().unwrap(), values.next().unwrap());
+        let feed = feeds
+            .iter_mut()
+            .find(|feed| feed.info.name == name)
+            .ok_or_else(|| Error::Msg(format!("No feed named \"{}\" in your config", name)))?;
+        if feed.bookmark(url) {
+            args.save_feed(feed)?;
+            println!("Bookmarked <{}> in \"{}\"", url, name);
+        } else {
+            println!("<{}> is already bookmarked in \"{}\"", url, name);
+        }
+        return Ok(());
+    }
+
+    if let Some(name) = matches.value_of("touch") {
+        let feed = feeds
+            .iter_mut()
+            .find(|feed| feed.info.name == name)
+            .ok_or_else(|| Error::Msg(format!("No feed named \"{}\" in your config", name)))?;
+        let previous = feed.last_fetched();
+        feed.touch(Utc::now());
+        args.save_feed(feed)?;
+        match previous {
+            Some(previous) => println!(
+                "Marked \"{}\" fetched (it was last fetched {})",
+                name,
+                previous.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+            ),
+            None => println!("Marked \"{}\" fetched", name),
         }
+        return Ok(());
+    }
+
+    if matches.is_present("bookmarks") {
+        for feed in &feeds {
+            for url in feed.bookmarks() {
+                println!("{}: {}", feed.info.name, url);
+            }
+        }
+        return Ok(());
+    }
+
+    for name in args.scoped_feeds() {
+        if !feeds.iter().any(|feed| feed.info.name == name) {
+            return Err(Error::Msg(format!(
+                "No feed named \"{}\" in your config",
+                name
+            )));
+        }
+    }
+
+    // Only the named feed is fetched, so nothing else is touched
+    let only = match args.mark_read() {
+        Some(config::MarkRead::Feed(name)) => Some(&name[..]),
+        _ => None,
+    };
+    for name in args.only().into_iter().chain(only) {
+        feeds.retain(|feed| feed.info.name == name);
+        if feeds.is_empty() {
+            return Err(Error::Msg(format!(
+                "No feed named \"{}\" in your config",
+                name
+            )));
+        }
+    }
+
+    // Only --since-last-run needs it, so otherwise a broken file doesn't stop the run
+    let since_last_run = matches.is_present("since-last-run");
+    let last_run = match args.read_last_run() {
+        Ok(last_run) => last_run,
+        Err(err) if !since_last_run => {
+            eprintln!("Warning: couldn't read when feedburst last ran: {}", err);
+            None
+        }
+        Err(err) => return Err(err),
+    };
 
-        rx
+    // Every feed is scheduled against the same time, however long fetching takes
+    let started = Utc::now();
+    let timezone = args.timezone();
+
+    // Fetch the feeds that are currently scheduled, not those that are unscheduled
+    feeds.sort_by_key(|feed| match timezone {
+        Some(tz) => !feed.is_scheduled(started.with_timezone(&tz)),
+        None => !feed.is_scheduled(started.with_timezone(&Local)),
+    });
+
+    let cache = Arc::new(Mutex::new(args.open_cache()?));
+    let rx = {
+        let groups = fetch::partition_feeds(feeds, args.jobs());
+        let args = args.clone();
+        let cache = cache.clone();
+        fetch::spawn_groups(groups, move |feed: Feed| {
+            let name = feed.info.name.clone();
+            match fetch_feed(&args, &cache, feed) {
+                Ok(fetched) => Some(fetched),
+                Err(Error::Msg(err)) => {
+                    eprintln!("{}", err);
+                    None
+                }
+                Err(err) => {
+                    eprintln!("Error in feed {}: {}", name, err);
+                    None
+                }
+            }
+        })
     };
 
     let mut num_read = 0;
-    for mut feed in rx {
-        if feed.is_ready() && !only_fetch {
-            if let Err(err) = read_feed(&args, &mut feed) {
+    let mut num_arrived = 0;
+    // Unread comics the feeds say came out since the last run, which can be
+    // fewer than arrived when an old archive is added
+    let mut num_published = 0;
+    let mut next_scheduled = None;
+    let mut reports = Vec::new();
+    let mut pacer = args.open_pacer();
+    // With --max-tabs, ready feeds wait until every feed is in so the tabs can be shared
+    let mut waiting = Vec::new();
+    for fetched in rx {
+        let Fetched {
+            mut feed,
+            arrived,
+            moved_to,
+            upgraded_to,
+        } = fetched;
+        num_arrived += arrived;
+        if let Some(last_run) = last_run {
+            num_published += feed.unread_since(last_run);
+        }
+        if let Some(url) = moved_to {
+            // Done here rather than while fetching so only one thread edits the config
+            match args.update_feed_url(&feed.info, url.as_str()) {
+                // Porcelain output has to stay one line per ready feed
+                Ok(()) if porcelain || json => {
+                    eprintln!("{} moved to <{}>, updated your config", feed.info.name, url)
+                }
+                Ok(()) => println!("{} moved to <{}>, updated your config", feed.info.name, url),
+                Err(err) => eprintln!("{}", err),
+            }
+        }
+        if let Some(url) = upgraded_to {
+            eprintln!(
+                "{} works over HTTPS, consider changing its URL in your config to <{}>",
+                feed.info.name, url
+            );
+        }
+        let ready = match timezone {
+            _ if catch_up => feed.has_enough_comics(),
+            Some(tz) => feed.is_ready(started.with_timezone(&tz)),
+            None => feed.is_ready(started.with_timezone(&Local)),
+        };
+        if !ready {
+            let next = match timezone {
+                Some(tz) => feed
+                    .next_scheduled(started.with_timezone(&tz))
+                    .map(|next| next.with_timezone(&Utc)),
+                None => feed
+                    .next_scheduled(started.with_timezone(&Local))
+                    .map(|next| next.with_timezone(&Utc)),
+            };
+            if let Some(next) = next {
+                next_scheduled = Some(next_scheduled.map_or(next, |earliest| min(earliest, next)));
+            }
+        }
+        if json {
+            reports.push(match timezone {
+                Some(tz) => feed.report(ready, started.with_timezone(&tz)),
+                None => feed.report(ready, started.with_timezone(&Local)),
+            });
+        } else if ready && porcelain {
+            println!("{}", feed.porcelain_line());
+        } else if ready && args.mark_read().is_some() {
+            match args.mark_feed_read(&mut feed) {
+                Ok(unread) => {
+                    println!(
+                        "Marked \"{}\" read, skipping {} unread",
+                        feed.info.name, unread
+                    );
+                    num_read += 1;
+                }
+                Err(err) => eprintln!("Error in feed {}: {}", feed.info.name, err),
+            }
+        } else if ready && !only_fetch && args.max_tabs().is_some() {
+            waiting.push(feed);
+        } else if ready && !only_fetch {
+            if let Err(err) = read_feed(&args, &mut feed, &mut pacer, usize::MAX) {
+                eprintln!("Error in feed {}: {}", feed.info.name, err);
+            } else {
+                num_read += 1;
+            }
+        }
+    }
+
+    if let Some(max_tabs) = args.max_tabs() {
+        waiting.sort_by(|a, b| a.info.name.cmp(&b.info.name));
+        let wanted: Vec<_> = waiting
+            .iter()
+            .map(|feed| args.open_list(feed, started).len())
+            .collect();
+        let shares = config::share_tabs(&wanted, max_tabs);
+        let mut left_out = 0;
+        for (mut feed, tabs) in waiting.into_iter().zip(shares) {
+            if tabs == 0 {
+                left_out += 1;
+            } else if let Err(err) = read_feed(&args, &mut feed, &mut pacer, tabs) {
                 eprintln!("Error in feed {}: {}", feed.info.name, err);
             } else {
                 num_read += 1;
             }
         }
+        if left_out > 0 {
+            println!(
+                "{} more feed{} ready, but --max-tabs {} was reached",
+                left_out,
+                if left_out == 1 { " is" } else { "s are" },
+                max_tabs
+            );
+        }
+    }
+
+    if json {
+        reports.sort_by(|a, b| a.name.cmp(&b.name));
+        let text = serde_json::to_string_pretty(&reports)
+            .map_err(|err| Error::Msg(format!("Error writing JSON: {}", err)))?;
+        println!("{}", text);
+    } else if num_read == 0 && !only_fetch && !porcelain {
+        let now = Utc::now();
+        let next_day = next_scheduled.map(|next| match timezone {
+            Some(tz) => feed::describe_day(next.with_timezone(&tz), now.with_timezone(&tz)),
+            None => feed::describe_day(next.with_timezone(&Local), now.with_timezone(&Local)),
+        });
+        match next_day {
+            Some(next) => println!("No new comics. Next comics expected {}.", next),
+            None => println!("No new comics. Check back tomorrow!"),
+        }
     }
 
-    if num_read == 0 && !only_fetch {
-        // @Todo: Provide a better estimate of when new comics will be available.
-        println!("No new comics. Check back tomorrow!");
+    if let Err(err) = cache.lock().unwrap().save() {
+        eprintln!("Error saving the feed cache: {}", err);
+    }
+
+    let now = Utc::now();
+    if since_last_run {
+        let mut message = state::since_last_run_message(last_run, now, num_arrived);
+        if num_published > 0 {
+            message.push_str(&format!(", {} unread published since then", num_published));
+        }
+        if porcelain || json {
+            eprintln!("{}", message);
+        } else {
+            println!("{}", message);
+        }
+    }
+    if let Err(err) = args.record_last_run(now) {
+        eprintln!("Warning: couldn't record when feedburst last ran: {}", err);
     }
 
     Ok(())
 }
 
-fn fetch_feed(args: &config::Args, mut feed: Feed) -> Result<Feed, Error> {
+/// A feed that's just been downloaded
+struct Fetched {
+    /// Holds everything that was written, so reading it doesn't need to touch the disk again
+    feed: Feed,
+    /// How many comics were new
+    arrived: usize,
+    /// Where the feed permanently redirected to, with `@ follow-redirects`
+    moved_to: Option<reqwest::Url>,
+    /// The HTTPS URL that worked for an `http://` feed, with `--https-upgrade`
+    upgraded_to: Option<String>,
+}
+
+/// Download new comics and append them to the feed's file
+fn fetch_feed(args: &config::Args, cache: &Mutex<Cache>, mut feed: Feed) -> Result<Fetched, Error> {
     debug!("Fetching \"{}\" from <{}>", feed.info.name, feed.info.url);
-    let client = reqwest::ClientBuilder::new()
-        .timeout(std::time::Duration::from_secs(5))
-        .build()?;
-    let mut resp = client.get(&feed.info.url).send()?;
-    if !resp.status().is_success() {
-        debug!(
-            "Error \"{}\" fetching feed {} from {}",
-            resp.status(),
-            feed.info.name,
-            feed.info.url,
-        );
-        return Err(Error::Msg(format!(
-            "{} (Failed to download: \"{}\")",
-            feed.info.name,
-            resp.status(),
-        )));
-    }
-    let mut content = String::new();
-    resp.read_to_string(&mut content)?;
-    let links: Vec<_> = {
-        use syndication::Feed;
-        let feed_info = &feed.info;
-        match Feed::from_str(&content).map_err(|x| Error::Msg(x.into()))? {
-            Feed::Atom(feed) => {
-                debug!("Parsed feed <{}> as Atom", feed_info.url);
-                feed.entries
-                    .into_iter()
-                    .rev()
-                    .filter(|x| {
-                        let keep = feed_info.filter_title(&x.title);
-                        if !keep {
-                            debug!("skipping by title: {}", x.title);
-                        }
-                        keep
-                    })
-                    .filter_map(|x| x.links.first().cloned())
-                    .map(|x| x.href)
-                    .filter(|url| feed_info.filter_url(&url))
-                    .collect()
-            }
-            Feed::RSS(feed) => {
-                debug!("Parsed feed <{}> as RSS", feed_info.url);
-                feed.items
-                    .into_iter()
-                    .rev()
-                    .filter(|x| {
-                        let title = &x.title;
-                        let title = title.as_ref().map(|x| &x[..]).unwrap_or("");
-                        let keep = feed_info.filter_title(&title);
-                        if !keep {
-                            debug!("skipping by title: {:?}", x.title);
-                        }
-                        keep
-                    })
-                    .filter_map(|x| x.link)
-                    .filter(|url| feed_info.filter_url(&url))
-                    .collect()
-            }
-        }
+    let client = fetch::build_client(&feed.info, args.timeout(), args.user_agent())?;
+    let headers = args.secrets().headers_for(&feed.info)?;
+    let (body, upgraded_to) = if args.https_upgrade() {
+        fetch::fetch_upgraded(&feed.info, |info| {
+            // HTTPS is only a guess, so a failure falls back to the original URL right away
+            let attempts = if info.url == feed.info.url {
+                args.attempts()
+            } else {
+                1
+            };
+            fetch::fetch_body(&client, info, headers, cache, attempts)
+        })?
+    } else {
+        (
+            fetch::fetch_body(&client, &feed.info, headers, cache, args.attempts())?,
+            None,
+        )
     };
+    let moved_to = body.moved_to.clone();
+    let pages = fetch::follow_next_pages(
+        &feed.info,
+        body,
+        |url| feed.has_seen(url),
+        |url| {
+            let info = FeedInfo {
+                url: url.into(),
+                ..feed.info.clone()
+            };
+            let headers = fetch::page_headers(&feed.info, url, headers);
+            fetch::fetch_body(&client, &info, headers, cache, args.attempts())
+        },
+    );
+    let (links, filtered) = fetch::partition_page_links(&pages, &feed.info);
+    if feed.info.records_filtered() {
+        feed.add_filtered(&filtered);
+    }
 
-    let mut feed_file = args.feed_file(&feed.info)?;
-    feed.add_new_comics(&links);
-    feed.write_changes(&mut feed_file)?;
-    Ok(feed)
+    let arrived = feed.add_new_comics(&links);
+    if arrived > 0 {
+        feed.record_arrival(Utc::now());
+    }
+    if arrived > 0 || feed.info.notifies_updates() {
+        let details = fetch::page_comic_details(&pages, &feed.info);
+        let dates: Vec<_> = details
+            .iter()
+            .filter_map(|comic| comic.date(&feed.info).map(|date| (comic.url.clone(), date)))
+            .collect();
+        feed.add_publication_dates(&dates);
+        if feed.info.notifies_updates() {
+            let hashes: Vec<_> = details
+                .iter()
+                .map(|comic| (comic.url.clone(), comic.hash))
+                .collect();
+            let updated = feed.add_content_hashes(&hashes);
+            debug!("{} comics in \"{}\" were updated", updated, feed.info.name);
+        }
+    }
+    args.save_feed(&mut feed)?;
+    Ok(Fetched {
+        feed,
+        arrived,
+        moved_to,
+        upgraded_to,
+    })
 }
 
-fn read_feed(args: &config::Args, feed: &mut Feed) -> Result<(), Error> {
-    let mut feed_file = args.feed_file(&feed.info)?;
-    let items = feed.get_reading_list();
-    if items.is_empty() {
-        return Ok(());
+/// Check what `validate` can without fetching, after `problems` mistakes
+/// were found parsing the config, and fail if there were any
+fn validate_config(
+    args: &config::Args,
+    feeds: &[FeedInfo],
+    included: &[PathBuf],
+    problems: usize,
+) -> Result<(), Error> {
+    let mut errors = Vec::new();
+    if let Err(err) = args.check_feed_paths(feeds, included) {
+        errors.push(err.to_string());
     }
-    let plural_feeds = if items.len() == 1 { "comic" } else { "comics" };
-    println!("{} ({} {})", feed.info.name, items.len(), plural_feeds);
-    if feed
-        .info
-        .update_policies
-        .contains(&feed::UpdateSpec::OpenAll)
-    {
-        // Open all the comics instead of just the earliest one
-        for item in &items {
-            args.open_url(&feed.info, item)?;
+    for info in feeds {
+        if let Err(err) = args.check_feed_dir(info) {
+            errors.push(format!("Feed \"{}\": {}", info.name, err));
         }
-    } else {
-        args.open_url(&feed.info, items.first().unwrap())?;
+        for msg in info.check_patterns() {
+            errors.push(format!("Feed \"{}\": {}", info.name, msg));
+        }
+    }
+    for err in &errors {
+        eprintln!("{}\n", err);
+    }
+    match problems + errors.len() {
+        0 => {
+            println!("Config OK");
+            Ok(())
+        }
+        1 => Err(Error::Msg("Found 1 problem in your config".into())),
+        count => Err(Error::Msg(format!(
+            "Found {} problems in your config",
+            count
+        ))),
     }
-    feed.read();
-    feed.write_changes(&mut feed_file)?;
+}
+
+/// Open and read a feed's comics, opening at most `tabs` of them
+fn read_feed(
+    args: &config::Args,
+    feed: &mut Feed,
+    pacer: &mut config::OpenPacer,
+    tabs: usize,
+) -> Result<(), Error> {
+    let now = Utc::now();
+    let opening = args.open_list(feed, now).len();
+    if opening == 0 {
+        return Ok(());
+    }
+    let limited = if tabs < opening {
+        format!(", opening {} because of --max-tabs", tabs)
+    } else {
+        String::new()
+    };
+    let described = match args.timezone() {
+        Some(tz) => feed.describe_reading_list(now.with_timezone(&tz)),
+        None => feed.describe_reading_list(now.with_timezone(&Local)),
+    };
+    println!(
+        "{} ({}{})",
+        feed.info.display_name(io::stdout().is_terminal()),
+        described,
+        limited
+    );
+    args.open_and_read(feed, pacer, tabs)?;
     Ok(())
 }
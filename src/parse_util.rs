@@ -78,7 +78,6 @@ impl<'a> Buffer<'a> {
         }
     }
 
-    #[allow(unused)]
     pub fn first_token_of(&self, tokens: &[&str]) -> ParseResult<'a, &'a str> {
         if tokens.is_empty() {
             return Ok((*self, ""));
@@ -137,6 +136,26 @@ impl<'a> Buffer<'a> {
         beginning == prefix.to_lowercase()
     }
 
+    /// Read a decimal number, along with the span of its digits for error messages
+    pub fn read_number(&self) -> ParseResult<'a, (usize, (usize, usize))> {
+        let end = self
+            .text
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(self.text.len());
+        if end == 0 {
+            return Err(self.expected("digit"));
+        }
+        let span = (self.col, self.col + end - 1);
+        match self.text[..end].parse() {
+            Ok(value) => Ok((self.advance(end), (value, span))),
+            Err(_) => Err(ParseError::expected(
+                format!("a number no bigger than {}", usize::MAX),
+                self.row,
+                span,
+            )),
+        }
+    }
+
     pub fn read_between(&self, begin: char, end: char) -> ParseResult<'a, &'a str> {
         if !self.text.starts_with(begin) {
             return Err(self.expected(format!("character '{}'", begin)));
@@ -361,6 +380,38 @@ mod test {
         assert!(input.first_token_of_no_case(&["ZOOP"]).is_err());
     }
 
+    #[test]
+    fn test_read_number() {
+        let input = Buffer {
+            row: 3,
+            col: 5,
+            text: "142 new comics",
+        };
+        assert_eq!(input.read_number(), Ok((input.advance(3), (142, (5, 7)))));
+
+        let single = Buffer { text: "7", ..input };
+        assert_eq!(single.read_number(), Ok((single.advance(1), (7, (5, 5)))));
+
+        let huge = Buffer {
+            text: "99999999999999999999999 new comics",
+            ..input
+        };
+        assert_eq!(
+            huge.read_number(),
+            Err(ParseError::expected(
+                format!("a number no bigger than {}", usize::MAX),
+                3,
+                (5, 27),
+            ))
+        );
+
+        let none = Buffer {
+            text: "new comics",
+            ..input
+        };
+        assert_eq!(none.read_number(), Err(none.expected("digit")));
+    }
+
     #[test]
     fn test_starts_with() {
         let input = Buffer {
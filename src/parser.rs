@@ -1,8 +1,13 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::fs;
 use std::iter::FromIterator;
+use std::path::{Path, PathBuf};
 
-use crate::feed::{FeedEvent, FeedInfo, FilterType, UpdateSpec};
-use chrono::Weekday;
+use crate::feed::{
+    Color, DateField, FeedEvent, FeedInfo, FilterExpr, FilterField, FilterType, TimeUnit,
+    UpdateSpec, FEED_FORMAT_VERSION,
+};
+use chrono::{NaiveDate, Weekday};
 use regex::Regex;
 
 use crate::error::ParseError;
@@ -42,10 +47,129 @@ fn parse_command_part<'a>(buf: &Buffer<'a>) -> ParseResult<'a, &'a str> {
     }
 }
 
+/// A `ParseError` in the config file or in one of the files it includes
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigError {
+    /// The included file the error is in, or `None` if it's in the config file itself
+    pub include: Option<PathBuf>,
+    pub error: ParseError,
+}
+
+impl From<ParseError> for ConfigError {
+    fn from(error: ParseError) -> Self {
+        ConfigError {
+            include: None,
+            error,
+        }
+    }
+}
+
+/// The feeds in a config file, the errors skipped with `keep_going`, and the files it includes
+type ParsedConfig = (Vec<FeedInfo>, Vec<ConfigError>, Vec<PathBuf>);
+
+/// What a config file shares with the files it includes
+struct Includes {
+    keep_going: bool,
+    /// Where each feed name is first used, as the included file and line
+    names: HashMap<String, (Option<PathBuf>, usize)>,
+    /// Every file that's been read, so none can be included twice or include itself
+    visited: HashSet<PathBuf>,
+    errors: Vec<ConfigError>,
+}
+
+/// Parse a config that isn't in a file, so any includes are relative to the current directory
+#[allow(unused)]
 pub fn parse_config(input: &str) -> Result<Vec<FeedInfo>, ParseError> {
+    parse_config_file(Path::new(""), input, false)
+        .map(|(feeds, _, _)| feeds)
+        .map_err(|err| err.error)
+}
+
+/// Parse the config file at `path`, whose text is `input`, along with the
+/// files it includes. With `keep_going`, feed lines that don't parse are
+/// skipped, and their errors returned with the other feeds, for `--keep-going`.
+/// A bad `root`, `command`, or `include` line still fails, since skipping it
+/// would change the feeds after it.
+pub fn parse_config_file(
+    path: &Path,
+    input: &str,
+    keep_going: bool,
+) -> Result<ParsedConfig, ConfigError> {
+    let mut includes = Includes {
+        keep_going,
+        names: HashMap::new(),
+        visited: HashSet::new(),
+        errors: Vec::new(),
+    };
+    let config = path.canonicalize().ok();
+    if let Some(config) = &config {
+        includes.visited.insert(config.clone());
+    }
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let feeds = parse_config_lines(input, None, dir, None, None, &mut includes)?;
+    let included = includes
+        .visited
+        .into_iter()
+        .filter(|file| Some(file) != config.as_ref())
+        .collect();
+    Ok((feeds, includes.errors, included))
+}
+
+/// Read the file named on an `include` line, resolved against `dir`, and parse
+/// its feeds with the `root` and `command` in effect where it's included.
+fn parse_include(
+    buf: &Buffer,
+    dir: &Path,
+    root_path: Option<&str>,
+    command: Option<Vec<String>>,
+    includes: &mut Includes,
+) -> Result<Vec<FeedInfo>, ConfigError> {
+    let buf = buf.trim();
+    let path = dir.join(buf.text);
+    let span = (buf.col, buf.col + buf.text.len() - 1);
+    let read = path
+        .canonicalize()
+        .and_then(|canonical| Ok((fs::read_to_string(&path)?, canonical)));
+    let (text, canonical) = match read {
+        Ok(read) => read,
+        Err(err) => {
+            let msg = format!(
+                "a file to include, couldn't read {}: {}",
+                path.display(),
+                err
+            );
+            return Err(ParseError::expected(msg, buf.row, span).into());
+        }
+    };
+    if !includes.visited.insert(canonical) {
+        let msg = format!(
+            "a file that isn't already included, {} would be read twice",
+            path.display()
+        );
+        return Err(ParseError::expected(msg, buf.row, span).into());
+    }
+    let inner_dir = path.parent().unwrap_or(dir);
+    parse_config_lines(&text, Some(&path), inner_dir, root_path, command, includes).map_err(|err| {
+        ConfigError {
+            include: err.include.or_else(|| Some(path.clone())),
+            error: err.error,
+        }
+    })
+}
+
+fn parse_config_lines(
+    input: &str,
+    file: Option<&Path>,
+    dir: &Path,
+    root: Option<&str>,
+    command: Option<Vec<String>>,
+    includes: &mut Includes,
+) -> Result<Vec<FeedInfo>, ConfigError> {
     let mut out = Vec::new();
-    let mut root_path = None;
-    let mut command = None;
+    let mut root_path = root;
+    let mut command = command;
+    // Unlike `command`, applies to every feed in the file without one of its own
+    let mut default_command = None;
     for (row, line) in input.lines().enumerate() {
         let buf = Buffer {
             row: row + 1,
@@ -65,6 +189,21 @@ pub fn parse_config(input: &str) -> Result<Vec<FeedInfo>, ParseError> {
             } else {
                 root_path = Some(buf.space()?.trim().text);
             }
+        } else if buf.starts_with("open-with") {
+            if default_command.is_some() {
+                return Err(ParseError::expected(
+                    "only one open-with line",
+                    buf.row,
+                    (buf.col, buf.col + "open-with".len() - 1),
+                )
+                .into());
+            }
+            let buf = buf.token_no_case("open-with")?.space()?;
+            default_command = Some(parse_command(buf.text)?);
+        } else if buf.starts_with("include") {
+            let buf = buf.token_no_case("include")?.space()?;
+            let included = parse_include(&buf, dir, root_path, command.clone(), includes)?;
+            out.extend(included);
         } else if buf.starts_with("command") {
             let buf = buf.token_no_case("command")?;
             if buf.trim().text.is_empty() {
@@ -73,15 +212,118 @@ pub fn parse_config(input: &str) -> Result<Vec<FeedInfo>, ParseError> {
                 command = Some(parse_command(buf.text)?);
             }
         } else {
-            let (_, mut feed) = parse_line(&buf)?;
-            feed.root = root_path.map(From::from);
-            feed.command = command.clone();
-            out.push(feed);
+            let parsed = parse_line(&buf)
+                .and_then(|(rest, feed)| {
+                    if rest.text.is_empty() {
+                        Ok(feed)
+                    } else {
+                        Err(rest.expected("a policy starting with \"@\", or a # comment"))
+                    }
+                })
+                .and_then(|feed| match includes.names.get(&feed.name) {
+                    Some((first_file, first_row)) => {
+                        let first_line = match first_file {
+                            _ if first_file.as_deref() == file => format!("line {}", first_row),
+                            Some(path) => format!("line {} of {}", first_row, path.display()),
+                            None => format!("line {} of the config file", first_row),
+                        };
+                        Err(ParseError::expected(
+                            format!(
+                                "a different name, {} already has a feed called \"{}\"",
+                                first_line, feed.name
+                            ),
+                            buf.row,
+                            (buf.col, buf.col + feed.name.len() + 1),
+                        ))
+                    }
+                    None => Ok(feed),
+                });
+            match parsed {
+                Ok(mut feed) => {
+                    let first = (file.map(PathBuf::from), buf.row);
+                    includes.names.insert(feed.name.clone(), first);
+                    feed.root = root_path.map(From::from);
+                    feed.command = command.clone();
+                    feed.source = file.map(PathBuf::from);
+                    out.push(feed);
+                }
+                Err(error) if includes.keep_going => includes.errors.push(ConfigError {
+                    include: file.map(PathBuf::from),
+                    error,
+                }),
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+    if let Some(default_command) = default_command {
+        for feed in out.iter_mut().filter(|feed| feed.command.is_none()) {
+            feed.command = Some(default_command.clone());
+        }
+    }
+    Ok(out)
+}
+
+/// Parse the secrets file, which has lines like `"Name" X-Api-Key: value`,
+/// into (feed name, header, value) triples.
+pub fn parse_secrets(input: &str) -> Result<Vec<(&str, &str, &str)>, ParseError> {
+    let mut out = Vec::new();
+    for (row, line) in input.lines().enumerate() {
+        let buf = Buffer {
+            row: row + 1,
+            col: 0,
+            text: line,
+        }
+        .trim();
+
+        if buf.starts_with("#") || buf.text.is_empty() {
+            continue;
         }
+
+        let (buf, name) = parse_name(&buf)?;
+        let buf = buf.space()?;
+        let header = match buf.text.find(':') {
+            Some(colon) if !buf.text[..colon].trim().is_empty() => colon,
+            _ => return Err(buf.expected("a header like \"X-Api-Key: value\"")),
+        };
+        out.push((
+            name,
+            buf.text[..header].trim(),
+            buf.text[header + 1..].trim(),
+        ));
     }
     Ok(out)
 }
 
+/// Change the URL of the feed called `name` in the config text, leaving
+/// everything else as it was. Returns `None` if there's no such feed.
+pub fn replace_feed_url(input: &str, name: &str, url: &str) -> Option<String> {
+    let mut lines: Vec<String> = input.split('\n').map(String::from).collect();
+    for (row, line) in lines.iter_mut().enumerate() {
+        let buf = Buffer {
+            row: row + 1,
+            col: 0,
+            text: line,
+        }
+        .trim();
+        if !buf.starts_with("\"") {
+            continue;
+        }
+        let (start, end) = match parse_name(&buf) {
+            Ok((rest, feed_name)) if feed_name == name => {
+                let start = rest.trim_start();
+                match parse_url(&start) {
+                    Ok((end, _)) => (start.col + 1, end.col - 1),
+                    Err(_) => continue,
+                }
+            }
+            _ => continue,
+        };
+        *line = format!("{}{}{}", &line[..start], url, &line[end..]);
+        return Some(lines.join("\n"));
+    }
+    None
+}
+
 fn parse_line<'a>(buf: &Buffer<'a>) -> ParseResult<'a, FeedInfo> {
     let (buf, name) = parse_name(buf)?;
     let buf = buf.trim_start();
@@ -96,6 +338,7 @@ fn parse_line<'a>(buf: &Buffer<'a>) -> ParseResult<'a, FeedInfo> {
             update_policies: HashSet::from_iter(policies),
             root: None,
             command: None,
+            source: None,
         },
     ))
 }
@@ -110,56 +353,165 @@ fn parse_url<'a>(buf: &Buffer<'a>) -> ParseResult<'a, &'a str> {
 
 fn parse_policies<'a>(buf: &Buffer<'a>) -> ParseResult<'a, Vec<UpdateSpec>> {
     let mut policies = Vec::new();
+    let mut slug_from = None;
     let mut buf = buf.trim_start();
     while buf.starts_with("@") {
+        // `@ on` can name several days, so it's the one policy that's more than one spec
+        let inp = buf.token("@")?.space()?;
+        if inp.starts_with_no_case("on") {
+            let (inp, days) = parse_weekdays(&inp.token_no_case("on")?.space()?)?;
+            policies.extend(days.into_iter().map(UpdateSpec::On));
+            buf = inp.space_or_end()?.trim_start();
+            continue;
+        }
         let (inp, policy) = parse_policy(&buf)?;
+        if let UpdateSpec::SlugFrom(_) = policy {
+            let len = buf.text[..inp.col - buf.col].trim_end().len();
+            slug_from = Some((buf.col, buf.col + len - 1));
+        }
         policies.push(policy);
         buf = inp.trim_start();
     }
+    // Otherwise it would be silently ignored
+    if let Some(span) = slug_from {
+        if !policies
+            .iter()
+            .any(|policy| matches!(policy, UpdateSpec::UrlTemplate(_)))
+        {
+            return Err(ParseError::expected(
+                "an \"@ url-template\" for \"@ slug-from\" to fill in",
+                buf.row,
+                span,
+            ));
+        }
+    }
+    // A comment can only start where another policy could, so a `#` in a name,
+    // URL, or pattern isn't one
+    if buf.starts_with("#") {
+        buf = buf.advance(buf.text.len());
+    }
     Ok((buf, policies))
 }
 
+/// The longest period `@ every` allows, about a hundred years, so the dates it
+/// lands on can always be worked out
+const MAX_EVERY_DAYS: usize = 36_525;
+
 fn parse_policy<'a>(buf: &Buffer<'a>) -> Result<(Buffer<'a>, UpdateSpec), ParseError> {
     let buf = buf.trim_start().token("@")?.space()?;
 
-    if buf.starts_with_no_case("on") {
-        let buf = buf.token_no_case("on")?.space()?;
-        let (buf, weekday) = parse_weekday(&buf)?;
-        let buf = buf.space_or_end()?;
-        Ok((buf, UpdateSpec::On(weekday)))
+    if buf.starts_with_no_case("every-from-threshold") {
+        let buf = buf.token_no_case("every-from-threshold")?;
+        Ok((buf.space_or_end()?, UpdateSpec::EveryFromThreshold))
+    } else if buf.starts_with_no_case("skip") {
+        let buf = buf.token_no_case("skip")?.space()?;
+        let (buf, _) = buf.first_token_of_no_case(&["weekends"])?;
+        Ok((buf.space_or_end()?, UpdateSpec::SkipWeekends))
     } else if buf.starts_with_no_case("every") {
         let buf = buf.token_no_case("every")?.space()?;
+        let (buf, (count, count_span)) = buf.read_number()?;
+        let unit_buf = buf.space()?;
+        let (buf, unit) = unit_buf.first_token_of_no_case(&[
+            "hours", "hour", "days", "day", "weeks", "week", "months", "month",
+        ])?;
+        let unit = match unit {
+            "hours" | "hour" => TimeUnit::Hour,
+            "days" | "day" => TimeUnit::Day,
+            "weeks" | "week" => TimeUnit::Week,
+            _ => TimeUnit::Month,
+        };
+        let unit_span = (unit_buf.col, buf.col - 1);
+        let max = match unit {
+            TimeUnit::Hour => MAX_EVERY_DAYS * 24,
+            TimeUnit::Day => MAX_EVERY_DAYS,
+            TimeUnit::Week => MAX_EVERY_DAYS / 7,
+            TimeUnit::Month => MAX_EVERY_DAYS * 12 / 365,
+        };
+        if count > max {
+            return Err(ParseError::expected(
+                format!("a number of {}s no bigger than {}", unit.name(), max),
+                buf.row,
+                count_span,
+            ));
+        }
+        let buf = buf.space_or_end()?;
+        if buf.starts_with_no_case("from") {
+            let unit_days = match unit {
+                TimeUnit::Day => 1,
+                TimeUnit::Week => 7,
+                TimeUnit::Hour | TimeUnit::Month => {
+                    return Err(ParseError::expected(
+                        "days or weeks before \"from\"",
+                        buf.row,
+                        unit_span,
+                    ))
+                }
+            };
+            if count == 0 {
+                return Err(ParseError::expected(
+                    format!("a number of {}s above 0", unit.name()),
+                    buf.row,
+                    count_span,
+                ));
+            }
+            let count = count * unit_days;
+            let buf = buf.token_no_case("from")?.space()?;
+            let (buf, anchor) = parse_date(&buf)?;
+            Ok((buf.space_or_end()?, UpdateSpec::EveryFrom(count, anchor)))
+        } else {
+            Ok((buf, UpdateSpec::Every(count, unit)))
+        }
+    } else if buf.starts_with_no_case("overlap") {
+        let buf = buf.token_no_case("overlap")?.space()?;
         let (buf, count) = parse_number(&buf)?;
         let buf = buf
             .space()?
-            .first_token_of_no_case(&["days", "day"])?
+            .first_token_of_no_case(&["comics", "comic"])?
             .0
             .space_or_end()?;
-        Ok((buf, UpdateSpec::Every(count)))
-    } else if buf.starts_with_no_case("overlap") {
-        let buf = buf.token_no_case("overlap")?.space()?;
-        let (buf, count) = parse_number(&buf)?;
+        Ok((buf, UpdateSpec::Overlap(count)))
+    } else if buf.starts_with_no_case("order-by") {
+        let buf = buf.token_no_case("order-by")?.space()?;
+        let (buf, field) = buf.first_token_of_no_case(&["published", "updated"])?;
+        let field = match field {
+            "published" => DateField::Published,
+            _ => DateField::Updated,
+        };
+        Ok((buf.space_or_end()?, UpdateSpec::OrderBy(field)))
+    } else if buf.starts_with_no_case("pace") {
+        let buf = buf.token_no_case("pace")?.space()?;
+        let (buf, (count, count_span)) = buf.trim_start().read_number()?;
+        if count == 0 {
+            return Err(ParseError::expected(
+                "a pace of at least 1 comic",
+                buf.row,
+                count_span,
+            ));
+        }
+        let buf = buf.space()?.token_no_case("per")?.space()?;
+        let (buf, _) = buf.first_token_of_no_case(&["day"])?;
+        Ok((buf.space_or_end()?, UpdateSpec::Pace(count)))
+    } else if buf.starts_with_no_case("max") {
+        let buf = buf.token_no_case("max")?.space()?;
+        let (buf, (count, count_span)) = buf.trim_start().read_number()?;
+        if count == 0 {
+            return Err(ParseError::expected(
+                "a max of at least 1 comic",
+                buf.row,
+                count_span,
+            ));
+        }
         let buf = buf
             .space()?
             .first_token_of_no_case(&["comics", "comic"])?
             .0
             .space_or_end()?;
-        Ok((buf, UpdateSpec::Overlap(count)))
+        Ok((buf, UpdateSpec::Max(count)))
     } else if buf.starts_with_no_case("keep") || buf.starts_with_no_case("ignore") {
         let (buf, act_kind) = buf.first_token_of_no_case(&["keep", "ignore"])?;
         let buf = buf.space()?;
         let (buf, act_target) = buf.first_token_of_no_case(&["url", "title"])?;
-        let buf = buf.space()?;
-        let c = buf
-            .text
-            .chars()
-            .next()
-            .ok_or_else(|| buf.expected("a pattern"))?;
-        let (buf, pat) = buf.read_between(c, c)?;
-        if let Err(err) = Regex::new(pat) {
-            // @Todo: Get the span right
-            return Err(buf.expected(format!("/{}/ to be a valid pattern: {}", pat, err)));
-        }
+        let (buf, pat) = parse_pattern(&buf.space()?)?;
         Ok((
             buf,
             UpdateSpec::Filter(
@@ -170,16 +522,93 @@ fn parse_policy<'a>(buf: &Buffer<'a>) -> Result<(Buffer<'a>, UpdateSpec), ParseE
                     ("ignore", "url") => FilterType::IgnoreUrl,
                     _ => unreachable!("invalid filter type"),
                 },
-                pat.into(),
+                pat,
             ),
         ))
+    } else if buf.starts_with_no_case("filter") {
+        let buf = buf.token_no_case("filter")?.space()?;
+        let (next, text) = buf.read_between('"', '"')?;
+        let inner = Buffer {
+            text,
+            row: buf.row,
+            col: buf.col + 1,
+        };
+        let (rest, expr) = parse_filter_or(&inner)?;
+        let rest = rest.trim_start();
+        if !rest.text.is_empty() {
+            return Err(rest.expected("\"and\", \"or\", or the end of the filter"));
+        }
+        Ok((next.space_or_end()?, UpdateSpec::FilterExpr(expr)))
     } else if buf.starts_with_no_case("open") {
-        let buf = buf
-            .token_no_case("open")?
-            .space()?
-            .token_no_case("all")?
-            .space_or_end()?;
-        Ok((buf, UpdateSpec::OpenAll))
+        let buf = buf.token_no_case("open")?.space()?;
+        let (buf, target) = buf.first_token_of_no_case(&["all", "feed-page", "homepage"])?;
+        match target {
+            "all" => Ok((buf.space_or_end()?, UpdateSpec::OpenAll)),
+            "homepage" => Ok((buf.space_or_end()?, UpdateSpec::OpenHomepage)),
+            _ => {
+                let (buf, url) = buf.space()?.read_between('"', '"')?;
+                Ok((buf.space_or_end()?, UpdateSpec::OpenPage(url.into())))
+            }
+        }
+    } else if buf.starts_with_no_case("color") {
+        let buf = buf.token_no_case("color")?.space()?;
+        let names: Vec<_> = Color::ALL.iter().map(|color| color.name()).collect();
+        let (buf, name) = buf.first_token_of_no_case(&names)?;
+        let color = Color::ALL[names.iter().position(|&n| n == name).unwrap_or(0)];
+        Ok((buf.space_or_end()?, UpdateSpec::Color(color)))
+    } else if buf.starts_with_no_case("icon") {
+        let buf = buf.token_no_case("icon")?.space()?;
+        let (buf, icon) = buf.read_between('"', '"')?;
+        Ok((buf.space_or_end()?, UpdateSpec::Icon(icon.into())))
+    } else if buf.starts_with_no_case("url-template") {
+        let buf = buf.token_no_case("url-template")?.space()?;
+        let (next, template) = buf.read_between('"', '"')?;
+        if !template.contains("{slug}") {
+            return Err(ParseError::expected(
+                "a template containing \"{slug}\"",
+                buf.row,
+                (buf.col, next.col - 1),
+            ));
+        }
+        Ok((
+            next.space_or_end()?,
+            UpdateSpec::UrlTemplate(template.into()),
+        ))
+    } else if buf.starts_with_no_case("slug-from") {
+        let buf = buf.token_no_case("slug-from")?.space()?;
+        let (next, pat) = buf.read_between('"', '"')?;
+        if let Err(err) = Regex::new(pat) {
+            return Err(ParseError::expected(
+                format!("\"{}\" to be a valid pattern: {}", pat, err),
+                buf.row,
+                (buf.col, next.col - 1),
+            ));
+        }
+        Ok((next.space_or_end()?, UpdateSpec::SlugFrom(pat.into())))
+    } else if buf.starts_with_no_case("silent") || buf.starts_with_no_case("mark-read-on-fetch") {
+        let (buf, _) = buf.first_token_of_no_case(&["silent", "mark-read-on-fetch"])?;
+        Ok((buf.space_or_end()?, UpdateSpec::Silent))
+    } else if buf.starts_with_no_case("secrets") {
+        let buf = buf.token_no_case("secrets")?;
+        Ok((buf.space_or_end()?, UpdateSpec::Secrets))
+    } else if buf.starts_with_no_case("follow-redirects") {
+        let buf = buf.token_no_case("follow-redirects")?;
+        Ok((buf.space_or_end()?, UpdateSpec::FollowRedirects))
+    } else if buf.starts_with_no_case("record-filtered") {
+        let buf = buf.token_no_case("record-filtered")?;
+        Ok((buf.space_or_end()?, UpdateSpec::RecordFiltered))
+    } else if buf.starts_with_no_case("notify-updates") {
+        let buf = buf.token_no_case("notify-updates")?;
+        Ok((buf.space_or_end()?, UpdateSpec::NotifyUpdates))
+    } else if buf.starts_with_no_case("serial") {
+        let buf = buf.token_no_case("serial")?;
+        Ok((buf.space_or_end()?, UpdateSpec::Serial))
+    } else if buf.starts_with_no_case("preserve-order") {
+        let buf = buf.token_no_case("preserve-order")?;
+        Ok((buf.space_or_end()?, UpdateSpec::PreserveOrder))
+    } else if buf.starts_with_no_case("insecure") {
+        let buf = buf.token_no_case("insecure")?;
+        Ok((buf.space_or_end()?, UpdateSpec::Insecure))
     } else if buf
         .text
         .chars()
@@ -194,17 +623,54 @@ fn parse_policy<'a>(buf: &Buffer<'a>) -> Result<(Buffer<'a>, UpdateSpec), ParseE
             .space()?
             .first_token_of_no_case(&["comics", "comic"])?
             .0;
-        Ok((buf, UpdateSpec::Comics(count)))
+        let rest = buf.trim_start();
+        if !rest.starts_with_no_case("or") {
+            return Ok((buf, UpdateSpec::Comics(count)));
+        }
+        let buf = rest
+            .token_no_case("or")?
+            .space()?
+            .token_no_case("after")?
+            .space()?;
+        let (buf, days) = parse_number(&buf)?;
+        let buf = buf
+            .space()?
+            .first_token_of_no_case(&["days", "day"])?
+            .0
+            .space_or_end()?;
+        Ok((buf, UpdateSpec::ComicsOrAfter(count, days)))
     } else {
         let error = ParseError::expected(
             r#"a policy definition. One of:
- - "@ on WEEKDAY"
- - "@ every # day(s)"
+ - "@ on WEEKDAY" (or "@ on WEEKDAY, WEEKDAY, and WEEKDAY")
+ - "@ every # day(s)" (or hour(s), week(s), month(s))
+ - "@ every # day(s) from YYYY-MM-DD"
  - "@ # new comic(s)"
+ - "@ # new comic(s) or after # day(s)"
  - "@ overlap # comic(s)"
- - "@ keep pattern /pattern/"
- - "@ ignore pattern /pattern/"
- - "@ open all""#,
+ - "@ max # comic(s)"
+ - "@ pace # per day"
+ - "@ order-by updated" (or published)
+ - "@ keep title \"pattern\"" (or url)
+ - "@ ignore title \"pattern\"" (or url)
+ - "@ filter \"title ~ /pattern/ and not url ~ /pattern/\""
+ - "@ open all"
+ - "@ open feed-page \"url\""
+ - "@ open homepage"
+ - "@ silent"
+ - "@ insecure"
+ - "@ record-filtered"
+ - "@ notify-updates"
+ - "@ secrets"
+ - "@ follow-redirects"
+ - "@ serial"
+ - "@ preserve-order"
+ - "@ every-from-threshold"
+ - "@ skip weekends"
+ - "@ url-template \"https://example.com/{slug}\""
+ - "@ slug-from \"pattern\""
+ - "@ color red" (or black, green, yellow, blue, magenta, cyan, white)
+ - "@ icon \"text\"""#,
             buf.row,
             (buf.col, buf.col + buf.text.len()),
         );
@@ -212,20 +678,152 @@ fn parse_policy<'a>(buf: &Buffer<'a>) -> Result<(Buffer<'a>, UpdateSpec), ParseE
     }
 }
 
-fn parse_number<'a>(buf: &Buffer<'a>) -> ParseResult<'a, usize> {
+/// Whether the buffer starts with a filter keyword that stands on its own,
+/// so `or` doesn't match the start of `order`
+fn starts_with_keyword(buf: &Buffer<'_>, keyword: &str) -> bool {
+    if !buf.starts_with_no_case(keyword) {
+        return false;
+    }
+    match buf.text[keyword.len()..].chars().next() {
+        Some(c) => c.is_whitespace() || c == '(',
+        None => true,
+    }
+}
+
+/// `or := and ("or" and)*`
+fn parse_filter_or<'a>(buf: &Buffer<'a>) -> ParseResult<'a, FilterExpr> {
+    let (mut buf, mut expr) = parse_filter_and(buf)?;
+    loop {
+        let rest = buf.trim_start();
+        if !starts_with_keyword(&rest, "or") {
+            return Ok((buf, expr));
+        }
+        let (next, rhs) = parse_filter_and(&rest.token_no_case("or")?)?;
+        buf = next;
+        expr = FilterExpr::Or(Box::new(expr), Box::new(rhs));
+    }
+}
+
+/// `and := unary ("and" unary)*`
+fn parse_filter_and<'a>(buf: &Buffer<'a>) -> ParseResult<'a, FilterExpr> {
+    let (mut buf, mut expr) = parse_filter_unary(buf)?;
+    loop {
+        let rest = buf.trim_start();
+        if !starts_with_keyword(&rest, "and") {
+            return Ok((buf, expr));
+        }
+        let (next, rhs) = parse_filter_unary(&rest.token_no_case("and")?)?;
+        buf = next;
+        expr = FilterExpr::And(Box::new(expr), Box::new(rhs));
+    }
+}
+
+/// `unary := "not" unary | "(" or ")" | (title | url) ("~" | "!~") /pattern/`
+fn parse_filter_unary<'a>(buf: &Buffer<'a>) -> ParseResult<'a, FilterExpr> {
     let buf = buf.trim_start();
+    if starts_with_keyword(&buf, "not") {
+        let (buf, inner) = parse_filter_unary(&buf.token_no_case("not")?)?;
+        return Ok((buf, FilterExpr::Not(Box::new(inner))));
+    }
+    if buf.starts_with("(") {
+        let (buf, inner) = parse_filter_or(&buf.token("(")?)?;
+        let buf = buf.trim_start().token(")")?;
+        return Ok((buf, inner));
+    }
+    let (buf, field) = buf.first_token_of_no_case(&["title", "url"])?;
+    let field = match field {
+        "title" => FilterField::Title,
+        _ => FilterField::Url,
+    };
+    let (buf, op) = buf.trim_start().first_token_of(&["~", "!~"])?;
+    let (buf, pat) = parse_pattern(&buf.trim_start())?;
+    match op {
+        "~" => Ok((buf, FilterExpr::Matches(field, pat))),
+        _ => Ok((buf, FilterExpr::NotMatches(field, pat))),
+    }
+}
+
+/// A regex between any delimiter, like `/pattern/` or `"pattern"`, followed by
+/// its flags. Invalid patterns are errors over the whole pattern.
+fn parse_pattern<'a>(buf: &Buffer<'a>) -> ParseResult<'a, String> {
+    let c = buf
+        .text
+        .chars()
+        .next()
+        .ok_or_else(|| buf.expected("a pattern"))?;
+    let (next, pat) = buf.read_between(c, c)?;
+    let (next, flags) = parse_pattern_flags(&next)?;
+    let pat = if flags.is_empty() {
+        pat.to_string()
+    } else {
+        format!("(?{}){}", flags, pat)
+    };
+    if let Err(err) = Regex::new(&pat) {
+        return Err(ParseError::expected(
+            format!("/{}/ to be a valid pattern: {}", pat, err),
+            buf.row,
+            (buf.col, next.col - 1),
+        ));
+    }
+    Ok((next, pat))
+}
+
+/// Regex flags written right after a pattern, like the `i` in `/chapter/i`
+fn parse_pattern_flags<'a>(buf: &Buffer<'a>) -> ParseResult<'a, &'a str> {
     let end = buf
         .text
-        .find(|c: char| !c.is_digit(10))
-        .unwrap_or_else(|| buf.text.len());
-    if end == 0 {
-        return Err(buf.expected("digit"));
+        .find(|c: char| !c.is_alphanumeric())
+        .unwrap_or(buf.text.len());
+    let flags = &buf.text[..end];
+    if let Some(offset) = flags.find(|c: char| !"imsx".contains(c)) {
+        return Err(buf
+            .advance(offset)
+            .expected("a pattern flag (i, m, s, or x)"));
     }
-    let value = buf.text[..end].parse().expect("Should only contain digits");
-    let buf = buf.advance(end);
+    Ok((buf.advance(end), flags))
+}
+
+fn parse_number<'a>(buf: &Buffer<'a>) -> ParseResult<'a, usize> {
+    let (buf, (value, _)) = buf.trim_start().read_number()?;
     Ok((buf, value))
 }
 
+fn parse_date<'a>(buf: &Buffer<'a>) -> ParseResult<'a, NaiveDate> {
+    let buf = buf.trim_start();
+    let end = buf.text.find(char::is_whitespace).unwrap_or(buf.text.len());
+    match NaiveDate::parse_from_str(&buf.text[..end], "%Y-%m-%d") {
+        Ok(date) => Ok((buf.advance(end), date)),
+        Err(_) => Err(ParseError::expected(
+            "a date like 2024-01-06",
+            buf.row,
+            (buf.col, buf.col + end.saturating_sub(1)),
+        )),
+    }
+}
+
+/// One or more weekdays, like `Monday and Friday` or `Monday, Wednesday, and Friday`
+fn parse_weekdays<'a>(buf: &Buffer<'a>) -> ParseResult<'a, Vec<Weekday>> {
+    let (mut buf, day) = parse_weekday(buf)?;
+    let mut days = vec![day];
+    loop {
+        let mut rest = buf.trim_start();
+        let comma = rest.starts_with(",");
+        if comma {
+            rest = rest.token(",")?.trim_start();
+        }
+        if starts_with_keyword(&rest, "and") {
+            rest = rest.token_no_case("and")?.space()?;
+        } else if !comma {
+            return Ok((buf, days));
+        }
+        let (next, day) = parse_weekday(&rest)?;
+        if !days.contains(&day) {
+            days.push(day);
+        }
+        buf = next;
+    }
+}
+
 fn parse_weekday<'a>(buf: &Buffer<'a>) -> ParseResult<'a, Weekday> {
     if buf.starts_with_no_case("sunday") {
         let buf = buf.advance("sunday".len());
@@ -266,15 +864,99 @@ pub fn parse_events(input: &str) -> Result<Vec<FeedEvent>, ParseError> {
             continue;
         }
 
-        if line.starts_with_no_case("read") {
+        if line.starts_with_no_case("version") {
+            let line = line.token_no_case("version")?.space()?;
+            let (rest, (version, span)) = line.read_number()?;
+            rest.space_or_end()?;
+            if version > FEED_FORMAT_VERSION {
+                return Err(ParseError::expected(
+                    format!(
+                        "a feed format version of at most {} (was this file written by a newer feedburst?)",
+                        FEED_FORMAT_VERSION,
+                    ),
+                    line.row,
+                    span,
+                ));
+            }
+        } else if line.starts_with_no_case("read") {
             let line = line.token_no_case("read")?.space()?;
-            let date = match line.text.parse() {
+            let end = line
+                .text
+                .find(char::is_whitespace)
+                .unwrap_or(line.text.len());
+            let date = match line.text[..end].parse() {
                 Ok(date) => date,
                 Err(_) => {
                     return Err(line.expected("a valid date"));
                 }
             };
-            result.push(FeedEvent::Read(date))
+            let line = line.advance(end).trim_start();
+            if line.text.is_empty() {
+                result.push(FeedEvent::Read(date))
+            } else {
+                let (line, url) = line.read_between('<', '>')?;
+                line.space_or_end()?;
+                result.push(FeedEvent::ReadThrough(date, url.into()));
+            }
+        } else if line.starts_with_no_case("bookmark") {
+            let line = line.token_no_case("bookmark")?.space()?;
+            let (line, url) = line.read_between('<', '>')?;
+            line.space_or_end()?;
+            result.push(FeedEvent::Bookmark(url.into()));
+        } else if line.starts_with_no_case("recap") {
+            let line = line.token_no_case("recap")?.space()?;
+            let (line, url) = line.read_between('<', '>')?;
+            line.space_or_end()?;
+            result.push(FeedEvent::Recapped(url.into()));
+        } else if line.starts_with_no_case("published") {
+            let line = line.token_no_case("published")?.space()?;
+            let (line, url) = line.read_between('<', '>')?;
+            let line = line.space()?;
+            let end = line
+                .text
+                .find(char::is_whitespace)
+                .unwrap_or(line.text.len());
+            let date = match line.text[..end].parse() {
+                Ok(date) => date,
+                Err(_) => {
+                    return Err(line.expected("a valid date"));
+                }
+            };
+            line.advance(end).space_or_end()?;
+            result.push(FeedEvent::Published(url.into(), date));
+        } else if line.starts_with_no_case("hash") {
+            let line = line.token_no_case("hash")?.space()?;
+            let (line, url) = line.read_between('<', '>')?;
+            let line = line.space()?;
+            let end = line
+                .text
+                .find(char::is_whitespace)
+                .unwrap_or(line.text.len());
+            let hash = match u64::from_str_radix(&line.text[..end], 16) {
+                Ok(hash) => hash,
+                Err(_) => return Err(line.expected("a hexadecimal hash")),
+            };
+            line.advance(end).space_or_end()?;
+            result.push(FeedEvent::ContentHash(url.into(), hash));
+        } else if line.starts_with_no_case("fetched") {
+            let line = line.token_no_case("fetched")?.space()?;
+            let end = line
+                .text
+                .find(char::is_whitespace)
+                .unwrap_or(line.text.len());
+            let date = match line.text[..end].parse() {
+                Ok(date) => date,
+                Err(_) => {
+                    return Err(line.expected("a valid date"));
+                }
+            };
+            line.advance(end).space_or_end()?;
+            result.push(FeedEvent::Fetched(date));
+        } else if line.starts_with_no_case("filtered") {
+            let line = line.token_no_case("filtered")?.space()?;
+            let (line, url) = line.read_between('<', '>')?;
+            line.space_or_end()?;
+            result.push(FeedEvent::Filtered(url.into()));
         } else if line.starts_with("<") {
             let (line, url) = line.read_between('<', '>')?;
             line.space_or_end()?;
@@ -283,7 +965,15 @@ pub fn parse_events(input: &str) -> Result<Vec<FeedEvent>, ParseError> {
             return Err(ParseError::expected(
                 r#"a feed event. One of:
  - "<url>"
- - "read DATE""#,
+ - "read DATE"
+ - "version #"
+ - "read DATE <url>"
+ - "bookmark <url>"
+ - "filtered <url>"
+ - "recap <url>"
+ - "fetched DATE"
+ - "hash <url> HASH"
+ - "published <url> DATE""#,
                 row,
                 None,
             ));
@@ -295,6 +985,14 @@ pub fn parse_events(input: &str) -> Result<Vec<FeedEvent>, ParseError> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::scratch::ScratchDir;
+
+    fn parse_config_keep_going(
+        input: &str,
+    ) -> Result<(Vec<FeedInfo>, Vec<ParseError>), ConfigError> {
+        let (feeds, errors, _) = parse_config_file(Path::new(""), input, true)?;
+        Ok((feeds, errors.into_iter().map(|err| err.error).collect()))
+    }
 
     #[test]
     fn test_config_parser() {
@@ -308,10 +1006,11 @@ mod test {
                 url: "http://questionablecontent.net/QCRSS.xml".into(),
                 update_policies: HashSet::from_iter(vec![
                     UpdateSpec::On(Weekday::Sat),
-                    UpdateSpec::Every(10),
+                    UpdateSpec::Every(10, TimeUnit::Day),
                 ]),
                 root: None,
                 command: None,
+                source: None,
             }])
         );
     }
@@ -343,6 +1042,7 @@ mod test {
                     ]),
                     root: None,
                     command: None,
+                    source: None,
                 },
                 FeedInfo {
                     name: "Electrum".into(),
@@ -353,6 +1053,7 @@ mod test {
                     ]),
                     root: None,
                     command: None,
+                    source: None,
                 },
                 FeedInfo {
                     name: "Gunnerkrigg Court".into(),
@@ -363,6 +1064,7 @@ mod test {
                     ]),
                     root: None,
                     command: None,
+                    source: None,
                 },
                 FeedInfo {
                     name: "GQutie!".into(),
@@ -370,6 +1072,7 @@ mod test {
                     update_policies: HashSet::from_iter(vec![UpdateSpec::OpenAll]),
                     root: None,
                     command: None,
+                    source: None,
                 },
             ])
         )
@@ -404,6 +1107,7 @@ root "#,
                     update_policies: HashSet::new(),
                     root: None,
                     command: None,
+                    source: None,
                 },
                 FeedInfo {
                     name: "Witchy".into(),
@@ -411,6 +1115,7 @@ root "#,
                     update_policies: HashSet::from_iter(vec![UpdateSpec::On(Weekday::Wed)]),
                     root: Some("/hello/world".into()),
                     command: None,
+                    source: None,
                 },
                 FeedInfo {
                     name: "Cucumber Quest".into(),
@@ -418,6 +1123,7 @@ root "#,
                     update_policies: HashSet::from_iter(vec![UpdateSpec::On(Weekday::Sun)]),
                     root: Some("/hello/world".into()),
                     command: None,
+                    source: None,
                 },
                 FeedInfo {
                     name: "Imogen Quest".into(),
@@ -425,6 +1131,7 @@ root "#,
                     update_policies: HashSet::from_iter(vec![UpdateSpec::On(Weekday::Fri)]),
                     root: Some("/oops/this/is/another/path".into()),
                     command: None,
+                    source: None,
                 },
                 FeedInfo {
                     name: "Balderdash".into(),
@@ -432,6 +1139,7 @@ root "#,
                     update_policies: HashSet::new(),
                     root: None,
                     command: None,
+                    source: None,
                 },
             ])
         )
@@ -456,6 +1164,32 @@ root "#,
         assert_eq!(row, 2);
     }
 
+    #[test]
+    fn test_default_open_command() {
+        let input = r#"
+"Eth's Skin" <http://www.eths-skin.com/rss>
+command w3m
+"Witchy" <http://feeds.feedburner.com/WitchyComic?format=xml>
+command
+open-with firefox --private-window @URL
+"Imogen Quest" <http://imogenquest.net/?feed=rss2>
+"#;
+        let feeds = parse_config(input).unwrap();
+        let firefox = ["firefox", "--private-window", "@URL"];
+        assert_eq!(
+            feeds[0].command,
+            Some(firefox.iter().map(|&x| x.into()).collect())
+        );
+        assert_eq!(feeds[1].command, Some(vec!["w3m".into()]));
+        assert_eq!(feeds[2].command, feeds[0].command);
+
+        let twice = "open-with firefox\nopen-with chromium\n";
+        assert_eq!(
+            parse_config(twice),
+            Err(ParseError::expected("only one open-with line", 2, (0, 8)))
+        );
+    }
+
     #[test]
     fn test_feed_commands() {
         let input = r#"
@@ -484,6 +1218,7 @@ command
                     update_policies: HashSet::new(),
                     root: None,
                     command: None,
+                    source: None,
                 },
                 FeedInfo {
                     name: "Witchy".into(),
@@ -491,6 +1226,7 @@ command
                     update_policies: HashSet::new(),
                     root: None,
                     command: command_vec.clone(),
+                    source: None,
                 },
                 FeedInfo {
                     name: "Cucumber Quest".into(),
@@ -498,6 +1234,7 @@ command
                     update_policies: HashSet::new(),
                     root: None,
                     command: command_vec,
+                    source: None,
                 },
                 FeedInfo {
                     name: "Imogen Quest".into(),
@@ -505,6 +1242,7 @@ command
                     update_policies: HashSet::new(),
                     root: None,
                     command: None,
+                    source: None,
                 },
             ])
         )
@@ -535,6 +1273,698 @@ read 2017-07-18T23:41:58.130248+00:00
         assert!(parse_events("invalid").is_err());
     }
 
+    #[test]
+    fn test_open_pages() {
+        let input = r#"
+"Witchy" <http://feeds.feedburner.com/WitchyComic?format=xml> @ open feed-page "http://witchycomic.com/latest"
+"Electrum" <https://electrum.cubemelon.net/feed> @ Open Homepage @ on Thursday
+"#;
+        assert_eq!(
+            parse_config(input),
+            Ok(vec![
+                FeedInfo {
+                    name: "Witchy".into(),
+                    url: "http://feeds.feedburner.com/WitchyComic?format=xml".into(),
+                    update_policies: HashSet::from_iter(vec![UpdateSpec::OpenPage(
+                        "http://witchycomic.com/latest".into()
+                    )]),
+                    root: None,
+                    command: None,
+                    source: None,
+                },
+                FeedInfo {
+                    name: "Electrum".into(),
+                    url: "https://electrum.cubemelon.net/feed".into(),
+                    update_policies: HashSet::from_iter(vec![
+                        UpdateSpec::OpenHomepage,
+                        UpdateSpec::On(Weekday::Thu),
+                    ]),
+                    root: None,
+                    command: None,
+                    source: None,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_silent() {
+        let input = r#"
+"Witchy" <http://feeds.feedburner.com/WitchyComic?format=xml> @ silent
+"Electrum" <https://electrum.cubemelon.net/feed> @ Mark-Read-On-Fetch
+"#;
+        let feeds = parse_config(input).unwrap();
+        assert_eq!(feeds.len(), 2);
+        for feed in feeds {
+            assert_eq!(
+                feed.update_policies,
+                HashSet::from_iter(vec![UpdateSpec::Silent])
+            );
+        }
+    }
+
+    #[test]
+    fn test_url_templates() {
+        let input = r#"
+"Witchy" <http://feeds.feedburner.com/WitchyComic?format=xml> @ url-template "http://witchycomic.com/comic/{slug}" @ slug-from "page-(\d+)"
+"#;
+        assert_eq!(
+            parse_config(input),
+            Ok(vec![FeedInfo {
+                name: "Witchy".into(),
+                url: "http://feeds.feedburner.com/WitchyComic?format=xml".into(),
+                update_policies: HashSet::from_iter(vec![
+                    UpdateSpec::UrlTemplate("http://witchycomic.com/comic/{slug}".into()),
+                    UpdateSpec::SlugFrom(r"page-(\d+)".into()),
+                ]),
+                root: None,
+                command: None,
+                source: None,
+            }])
+        );
+
+        let no_slug =
+            r#""Witchy" <http://witchycomic.com/feed> @ url-template "http://witchycomic.com/""#;
+        assert_eq!(
+            parse_config(no_slug),
+            Err(ParseError::expected(
+                "a template containing \"{slug}\"",
+                1,
+                (54, 78)
+            ))
+        );
+
+        let no_template =
+            r#""Witchy" <http://witchycomic.com/feed> @ slug-from "page-(\d+)" @ skip weekends"#;
+        assert_eq!(
+            parse_config(no_template),
+            Err(ParseError::expected(
+                "an \"@ url-template\" for \"@ slug-from\" to fill in",
+                1,
+                (39, 62)
+            ))
+        );
+
+        let bad_pattern = r#""Witchy" <http://witchycomic.com/feed> @ slug-from "page-(\d+""#;
+        let ParseError::Expected { msg, row, span } = parse_config(bad_pattern).unwrap_err();
+        assert!(msg.starts_with(r#""page-(\d+" to be a valid pattern"#));
+        assert_eq!((row, span), (1, Some((51, 61))));
+    }
+
+    #[test]
+    fn test_pattern_flags() {
+        let input = r#"
+"Witchy" <http://feeds.feedburner.com/WitchyComic?format=xml> @ keep title "chapter"i @ ignore url /guest/ix
+"#;
+        assert_eq!(
+            parse_config(input),
+            Ok(vec![FeedInfo {
+                name: "Witchy".into(),
+                url: "http://feeds.feedburner.com/WitchyComic?format=xml".into(),
+                update_policies: HashSet::from_iter(vec![
+                    UpdateSpec::Filter(FilterType::KeepTitle, "(?i)chapter".into()),
+                    UpdateSpec::Filter(FilterType::IgnoreUrl, "(?ix)guest".into()),
+                ]),
+                root: None,
+                command: None,
+                source: None,
+            }])
+        );
+
+        let feed = &parse_config(input).unwrap()[0];
+        assert!(feed.filter_title("Chapter 3, Page 1"));
+        assert!(feed.filter_title("CHAPTER 3 cover"));
+        assert!(!feed.filter_title("Sketchbook"));
+        assert!(!feed.filter_url("http://witchycomic.com/GUEST-comic"));
+
+        let bad_flag = r#""Witchy" <http://witchycomic.com/feed> @ keep title "chapter"iq"#;
+        assert_eq!(
+            parse_config(bad_flag),
+            Err(ParseError::expected(
+                "a pattern flag (i, m, s, or x)",
+                1,
+                62
+            ))
+        );
+    }
+
+    #[test]
+    fn test_trailing_comments() {
+        let input = r#"
+"Gunnerkrigg Court" <http://gunnerkrigg.com/rss.xml> @ 4 new comics # my favorite
+"Witchy" <http://witchycomic.com/feed#latest> @ keep title #a/b|c# @ silent #@ open all
+"Electrum" <http://electrum.cubemelon.net/feed> # no policies yet
+"#;
+        let feeds = parse_config(input).unwrap();
+        assert_eq!(
+            feeds[0].update_policies,
+            HashSet::from_iter(vec![UpdateSpec::Comics(4)])
+        );
+        assert_eq!(feeds[1].url, "http://witchycomic.com/feed#latest");
+        assert_eq!(
+            feeds[1].update_policies,
+            HashSet::from_iter(vec![
+                UpdateSpec::Filter(FilterType::KeepTitle, "a/b|c".into()),
+                UpdateSpec::Silent,
+            ])
+        );
+        assert!(feeds[2].update_policies.is_empty());
+
+        let trailing = r#""Electrum" <http://electrum.cubemelon.net/feed> @ 4 new comics please"#;
+        assert_eq!(
+            parse_config(trailing),
+            Err(ParseError::expected(
+                "a policy starting with \"@\", or a # comment",
+                1,
+                63
+            ))
+        );
+    }
+
+    #[test]
+    fn test_several_weekdays() {
+        let input = r#"
+"Witchy" <http://witchycomic.com/feed> @ on Monday, Wednesday, and Friday @ 2 new comics
+"Electrum" <http://electrum.cubemelon.net/feed> @ on tuesday AND thursday @ on Saturday,Sunday
+"#;
+        let feeds = parse_config(input).unwrap();
+        assert_eq!(
+            feeds[0].update_policies,
+            HashSet::from_iter(vec![
+                UpdateSpec::On(Weekday::Mon),
+                UpdateSpec::On(Weekday::Wed),
+                UpdateSpec::On(Weekday::Fri),
+                UpdateSpec::Comics(2),
+            ])
+        );
+        assert_eq!(
+            feeds[1].update_policies,
+            HashSet::from_iter(vec![
+                UpdateSpec::On(Weekday::Tue),
+                UpdateSpec::On(Weekday::Thu),
+                UpdateSpec::On(Weekday::Sat),
+                UpdateSpec::On(Weekday::Sun),
+            ])
+        );
+
+        let dangling = r#""Witchy" <http://witchycomic.com/feed> @ on Monday, @ silent"#;
+        assert_eq!(
+            parse_config(dangling),
+            Err(ParseError::expected("a weekday", 1, 52))
+        );
+        let unseparated = r#""Witchy" <http://witchycomic.com/feed> @ on Monday Friday"#;
+        assert!(parse_config(unseparated).is_err());
+    }
+
+    #[test]
+    fn test_filter_parser() {
+        let buf = r#"
+"Witchy" <http://witchycomic.com/feed> @ keep title "Chapter \d+" @ ignore title "guest art"
+"Electrum" <https://electrum.cubemelon.net/feed> @ keep url "/comic/" @ ignore url "\?preview"
+"#;
+        let feeds = parse_config(buf).unwrap();
+        assert_eq!(
+            feeds,
+            vec![
+                FeedInfo {
+                    name: "Witchy".into(),
+                    url: "http://witchycomic.com/feed".into(),
+                    update_policies: HashSet::from_iter(vec![
+                        UpdateSpec::Filter(FilterType::KeepTitle, "Chapter \\d+".into()),
+                        UpdateSpec::Filter(FilterType::IgnoreTitle, "guest art".into()),
+                    ]),
+                    root: None,
+                    command: None,
+                    source: None,
+                },
+                FeedInfo {
+                    name: "Electrum".into(),
+                    url: "https://electrum.cubemelon.net/feed".into(),
+                    update_policies: HashSet::from_iter(vec![
+                        UpdateSpec::Filter(FilterType::KeepUrl, "/comic/".into()),
+                        UpdateSpec::Filter(FilterType::IgnoreUrl, "\\?preview".into()),
+                    ]),
+                    root: None,
+                    command: None,
+                    source: None,
+                },
+            ]
+        );
+        for feed in &feeds {
+            let line = feed.to_config_line();
+            assert_eq!(parse_config(&line), Ok(vec![feed.clone()]), "{}", line);
+        }
+
+        let bad_pattern =
+            r#""Witchy" <http://witchycomic.com/feed> @ keep title "Chapter (\d+"i @ silent"#;
+        match parse_config(bad_pattern) {
+            Err(ParseError::Expected { row, span, .. }) => {
+                assert_eq!((row, span), (1, Some((52, 66))))
+            }
+            other => panic!("expected a pattern error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_duplicate_names() {
+        let input = r#"
+"Witchy" <http://witchycomic.com/feed> @ on Sunday
+"Electrum" <http://electrum.cubemelon.net/feed>
+  "Witchy" <http://witchycomic.com/other-feed>
+"#;
+        assert_eq!(
+            parse_config(input),
+            Err(ParseError::expected(
+                "a different name, line 2 already has a feed called \"Witchy\"",
+                4,
+                (2, 9)
+            ))
+        );
+
+        // The first one is kept
+        let (feeds, errors) = parse_config_keep_going(input).unwrap();
+        assert_eq!(feeds.len(), 2);
+        assert!(feeds[0]
+            .update_policies
+            .contains(&UpdateSpec::On(Weekday::Sun)));
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn test_keep_going() {
+        let input = r#"
+root /comics
+"Witchy" <http://witchycomic.com/feed> @ on Sunday
+"Broken" <http://broken.example.com/feed> @ on Caturday
+"Electrum" <http://electrum.cubemelon.net/feed> @ 2 new comics
+"Gunnerkrigg" <http://gunnerkrigg.com/feed>
+"#;
+        assert!(parse_config(input).is_err());
+        let (feeds, errors) = parse_config_keep_going(input).unwrap();
+        let names: Vec<_> = feeds.iter().map(|feed| &feed.name[..]).collect();
+        assert_eq!(names, ["Witchy", "Electrum", "Gunnerkrigg"]);
+        assert!(feeds.iter().all(|feed| feed.root == Some("/comics".into())));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(Err(errors[0].clone()), parse_config(input));
+
+        // Skipping these would change the feeds after them
+        let bad_command = "command 'unclosed\n\"Witchy\" <http://witchycomic.com/feed>\n";
+        assert!(parse_config_keep_going(bad_command).is_err());
+    }
+
+    #[test]
+    fn test_include() {
+        let dir = ScratchDir::new("include");
+        fs::create_dir_all(dir.join("genres")).unwrap();
+        let config =
+            "root /comics\ninclude genres/fantasy.feeds\n\"XKCD\" <https://xkcd.com/atom.xml>\n";
+        fs::write(dir.join("feedburst.feeds"), config).unwrap();
+        // Relative to the included file, not the config file
+        fs::write(
+            dir.join("genres/fantasy.feeds"),
+            "\"Witchy\" <http://witchycomic.com/feed>\ninclude more.feeds\nroot /fantasy\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("genres/more.feeds"),
+            "\"Gunnerkrigg\" <http://gunnerkrigg.com/feed>\n",
+        )
+        .unwrap();
+
+        let (feeds, errors, mut included) =
+            parse_config_file(&dir.join("feedburst.feeds"), config, false).unwrap();
+        assert!(errors.is_empty());
+        let names: Vec<_> = feeds.iter().map(|feed| &feed.name[..]).collect();
+        assert_eq!(names, ["Witchy", "Gunnerkrigg", "XKCD"]);
+        // A `root` in an included file doesn't leak out of it
+        assert!(feeds.iter().all(|feed| feed.root == Some("/comics".into())));
+        let sources: Vec<_> = feeds.iter().map(|feed| feed.source.clone()).collect();
+        assert_eq!(
+            sources,
+            [
+                Some(dir.join("genres/fantasy.feeds")),
+                Some(dir.join("genres/more.feeds")),
+                None
+            ]
+        );
+        included.sort();
+        let canonical = |path: &str| dir.join(path).canonicalize().unwrap();
+        assert_eq!(
+            included,
+            [
+                canonical("genres/fantasy.feeds"),
+                canonical("genres/more.feeds")
+            ]
+        );
+
+        // Errors are found in the file they're in
+        let duplicate = format!("{}\"Witchy\" <http://example.com/feed>\n", config);
+        assert_eq!(
+            parse_config_file(&dir.join("feedburst.feeds"), &duplicate, false),
+            Err(ConfigError {
+                include: None,
+                error: ParseError::expected(
+                    format!(
+                        "a different name, line 1 of {} already has a feed called \"Witchy\"",
+                        dir.join("genres/fantasy.feeds").display()
+                    ),
+                    4,
+                    (0, 7)
+                ),
+            })
+        );
+        fs::write(dir.join("genres/more.feeds"), "\"Broken\" <oops\n").unwrap();
+        let err = parse_config_file(&dir.join("feedburst.feeds"), config, false).unwrap_err();
+        assert_eq!(err.include, Some(dir.join("genres/more.feeds")));
+        let (feeds, errors, _) =
+            parse_config_file(&dir.join("feedburst.feeds"), config, true).unwrap();
+        assert_eq!(feeds.len(), 2);
+        assert_eq!(errors, vec![err]);
+
+        let missing = "include missing.feeds\n";
+        let err = parse_config_file(&dir.join("feedburst.feeds"), missing, false).unwrap_err();
+        assert_eq!(err.include, None);
+    }
+
+    #[test]
+    fn test_include_cycle() {
+        let dir = ScratchDir::new("include-cycle");
+        let config = "\"XKCD\" <https://xkcd.com/atom.xml>\n  include feedburst.feeds\n";
+        fs::write(dir.join("feedburst.feeds"), config).unwrap();
+        assert_eq!(
+            parse_config_file(&dir.join("feedburst.feeds"), config, false),
+            Err(ConfigError {
+                include: None,
+                error: ParseError::expected(
+                    format!(
+                        "a file that isn't already included, {} would be read twice",
+                        dir.join("feedburst.feeds").display()
+                    ),
+                    2,
+                    (10, 24)
+                ),
+            })
+        );
+
+        // Through another file too
+        fs::write(dir.join("a.feeds"), "include b.feeds\n").unwrap();
+        fs::write(dir.join("b.feeds"), "include a.feeds\n").unwrap();
+        let err = parse_config_file(&dir.join("feedburst.feeds"), "include a.feeds\n", false)
+            .unwrap_err();
+        assert_eq!(err.include, Some(dir.join("b.feeds")));
+        let ParseError::Expected { row, span, .. } = err.error;
+        assert_eq!((row, span), (1, Some((8, 14))));
+    }
+
+    #[test]
+    fn test_config_line_round_trip() {
+        let input = r#"
+"Witchy" <http://witchycomic.com/feed> @ on Sunday @ every 14 days from 2024-01-06
+"Electrum" <http://electrum.cubemelon.net/feed> @ 2 new comics @ overlap 1 comic @ open all
+root /comics
+"Gunnerkrigg" <http://gunnerkrigg.com/feed> @ ignore title |a/b|i @ keep url /page/ @ silent
+"Paranatural" <http://paranatural.net/feed> @ open feed-page "http://paranatural.net/latest"
+"XKCD" <https://xkcd.com/atom.xml> @ order-by updated @ url-template "https://m.xkcd.com/{slug}/" @ slug-from "(\d+)/$"
+"Homestuck" <https://homestuck.com/feed> @ open homepage @ every 2 days @ preserve-order @ pace 3 per day
+"Witchy Quest" <http://witchycomic.com/quest> @ 5 new comics or after 1 day @ silent @ serial
+"Fox" <http://example.com/fox> @ color Red @ icon "🦊" @ max 10 comics
+"Batch" <https://batch.example.com/feed> @ 5 new comics @ every 2 days @ every-from-threshold @ skip weekends
+"#;
+        let feeds = parse_config(input).unwrap();
+        let listing: Vec<_> = feeds.iter().map(FeedInfo::to_config_line).collect();
+        assert_eq!(listing.len(), 9);
+        for (feed, line) in feeds.iter().zip(&listing) {
+            assert!(line.starts_with(&format!("\"{}\" <{}>", feed.name, feed.url)));
+            let (_, reparsed) = parse_line(&Buffer {
+                row: 1,
+                col: 0,
+                text: line,
+            })
+            .unwrap();
+            assert_eq!(reparsed.update_policies, feed.update_policies, "{}", line);
+        }
+        assert_eq!(
+            listing[1],
+            "\"Electrum\" <http://electrum.cubemelon.net/feed> @ 2 new comics @ open all @ overlap 1 comic",
+        );
+        assert_eq!(
+            listing[6],
+            "\"Witchy Quest\" <http://witchycomic.com/quest> @ 5 new comics or after 1 day @ serial @ silent",
+        );
+    }
+
+    #[test]
+    fn test_filter_expr() {
+        let input = r#""Witchy" <http://witchycomic.com/feed> @ filter "title ~ /chapter/i and not (url ~ |guest| or title !~ /\d+/)""#;
+        let feeds = parse_config(input).unwrap();
+        let title = |pat: &str| FilterExpr::Matches(FilterField::Title, pat.into());
+        let expected = FilterExpr::And(
+            Box::new(title("(?i)chapter")),
+            Box::new(FilterExpr::Not(Box::new(FilterExpr::Or(
+                Box::new(FilterExpr::Matches(FilterField::Url, "guest".into())),
+                Box::new(FilterExpr::NotMatches(FilterField::Title, "\\d+".into())),
+            )))),
+        );
+        assert_eq!(
+            feeds[0].update_policies,
+            HashSet::from_iter(vec![UpdateSpec::FilterExpr(expected)])
+        );
+
+        let entries = [
+            ("Chapter 3", "http://witchycomic.com/3", true),
+            ("Chapter 3", "http://witchycomic.com/guest-3", false),
+            ("Chapter Three", "http://witchycomic.com/3", false),
+            ("Cover art", "http://witchycomic.com/3", false),
+        ];
+        for &(title, url, kept) in &entries {
+            assert_eq!(feeds[0].filter_entry(title, url), kept, "{} {}", title, url);
+        }
+
+        // "and" binds tighter than "or"
+        let feeds = parse_config(
+            r#""Witchy" <http://witchycomic.com/feed> @ filter "url ~ /a/ or url ~ /b/ and url ~ /c/""#,
+        )
+        .unwrap();
+        assert!(feeds[0].filter_entry("", "a"));
+        assert!(!feeds[0].filter_entry("", "b"));
+        assert!(feeds[0].filter_entry("", "bc"));
+
+        let line = feeds[0].to_config_line();
+        assert!(line.ends_with(r#"@ filter "url ~ /a/ or url ~ /b/ and url ~ /c/""#));
+        let nested = r#""Witchy" <http://witchycomic.com/feed> @ filter "not (title ~ /a/ and (url ~ /b/ or url !~ /c/))""#;
+        let feeds = parse_config(nested).unwrap();
+        assert_eq!(feeds[0].to_config_line(), nested);
+
+        let bad_pattern =
+            r#""Witchy" <http://witchycomic.com/feed> @ filter "title ~ /(/ or url ~ /b/""#;
+        match parse_config(bad_pattern) {
+            Err(ParseError::Expected { row, span, .. }) => {
+                assert_eq!((row, span), (1, Some((57, 59))))
+            }
+            other => panic!("expected a pattern error, got {:?}", other),
+        }
+        let trailing = r#""Witchy" <http://witchycomic.com/feed> @ filter "title ~ /a/ url ~ /b/""#;
+        assert!(parse_config(trailing).is_err());
+    }
+
+    #[test]
+    fn test_color_and_icon() {
+        let input = r#""Fox" <http://example.com/fox> @ 2 new comics @ color magenta @ icon "🦊""#;
+        let feeds = parse_config(input).unwrap();
+        assert_eq!(
+            feeds[0].update_policies,
+            HashSet::from_iter(vec![
+                UpdateSpec::Comics(2),
+                UpdateSpec::Color(Color::Magenta),
+                UpdateSpec::Icon("🦊".into()),
+            ])
+        );
+        assert_eq!(feeds[0].color(), Some(Color::Magenta));
+        assert_eq!(feeds[0].icon(), Some("🦊"));
+
+        let unknown = r#""Fox" <http://example.com/fox> @ color orange"#;
+        assert!(parse_config(unknown).is_err());
+    }
+
+    #[test]
+    fn test_every_units() {
+        let input = r#"
+"Witchy" <http://witchycomic.com/feed> @ every 2 weeks
+"Electrum" <http://electrum.cubemelon.net/feed> @ every 1 month @ 2 new comics
+"Paranatural" <http://paranatural.net/feed> @ Every 3 Months
+"Homestuck" <https://homestuck.com/feed> @ every 6 hours
+"#;
+        let policies: Vec<_> = parse_config(input)
+            .unwrap()
+            .into_iter()
+            .map(|feed| feed.update_policies)
+            .collect();
+        assert_eq!(
+            policies,
+            vec![
+                HashSet::from_iter(vec![UpdateSpec::Every(2, TimeUnit::Week)]),
+                HashSet::from_iter(vec![
+                    UpdateSpec::Every(1, TimeUnit::Month),
+                    UpdateSpec::Comics(2),
+                ]),
+                HashSet::from_iter(vec![UpdateSpec::Every(3, TimeUnit::Month)]),
+                HashSet::from_iter(vec![UpdateSpec::Every(6, TimeUnit::Hour)]),
+            ]
+        );
+        assert_eq!(
+            UpdateSpec::Every(3, TimeUnit::Month).to_string(),
+            "every 3 months"
+        );
+
+        // Each unit is bounded at about a hundred years
+        let too_long = |every: &str| {
+            let line = format!("\"Witchy\" <http://witchycomic.com/feed> @ every {}", every);
+            parse_config(&line).map(|_| ())
+        };
+        assert_eq!(too_long("876600 hours"), Ok(()));
+        assert_eq!(
+            too_long("876601 hours"),
+            Err(ParseError::expected(
+                "a number of hours no bigger than 876600",
+                1,
+                (47, 52)
+            ))
+        );
+        assert_eq!(
+            too_long("10000000000 days"),
+            Err(ParseError::expected(
+                "a number of days no bigger than 36525",
+                1,
+                (47, 57)
+            ))
+        );
+        assert_eq!(
+            too_long("18446744073709551615 weeks"),
+            Err(ParseError::expected(
+                "a number of weeks no bigger than 5217",
+                1,
+                (47, 66)
+            ))
+        );
+        assert_eq!(too_long("1200 months"), Ok(()));
+        assert!(too_long("1201 months").is_err());
+    }
+
+    #[test]
+    fn test_max() {
+        let input = r#""Witchy" <http://witchycomic.com/feed> @ 2 new comics @ MAX 1 comic"#;
+        assert_eq!(
+            parse_config(input).unwrap()[0].update_policies,
+            HashSet::from_iter(vec![UpdateSpec::Comics(2), UpdateSpec::Max(1)])
+        );
+
+        let zero = r#""Witchy" <http://witchycomic.com/feed> @ max 0 comics"#;
+        assert_eq!(
+            parse_config(zero),
+            Err(ParseError::expected(
+                "a max of at least 1 comic",
+                1,
+                (45, 45)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_every_from() {
+        let input = r#"
+"Witchy" <http://feeds.feedburner.com/WitchyComic?format=xml> @ every 14 days from 2024-01-06 @ 2 new comics
+"#;
+        assert_eq!(
+            parse_config(input),
+            Ok(vec![FeedInfo {
+                name: "Witchy".into(),
+                url: "http://feeds.feedburner.com/WitchyComic?format=xml".into(),
+                update_policies: HashSet::from_iter(vec![
+                    UpdateSpec::EveryFrom(14, NaiveDate::from_ymd(2024, 1, 6)),
+                    UpdateSpec::Comics(2),
+                ]),
+                root: None,
+                command: None,
+                source: None,
+            }])
+        );
+
+        let zero = r#""Witchy" <http://witchycomic.com/feed> @ every 0 days from 2024-01-06"#;
+        assert_eq!(
+            parse_config(zero),
+            Err(ParseError::expected(
+                "a number of days above 0",
+                1,
+                (47, 47)
+            ))
+        );
+
+        let weeks = r#""Witchy" <http://witchycomic.com/feed> @ every 2 weeks from 2024-01-06"#;
+        assert_eq!(
+            parse_config(weeks).unwrap()[0].update_policies,
+            HashSet::from_iter(vec![UpdateSpec::EveryFrom(
+                14,
+                NaiveDate::from_ymd(2024, 1, 6)
+            )])
+        );
+
+        let months = r#""Witchy" <http://witchycomic.com/feed> @ every 1 month from 2024-01-06"#;
+        assert_eq!(
+            parse_config(months),
+            Err(ParseError::expected(
+                "days or weeks before \"from\"",
+                1,
+                (49, 53)
+            ))
+        );
+
+        let bad_date = r#""Witchy" <http://witchycomic.com/feed> @ every 14 days from 2024-13-06"#;
+        assert_eq!(
+            parse_config(bad_date),
+            Err(ParseError::expected("a date like 2024-01-06", 1, (60, 69)))
+        );
+
+        // Only the number is underlined, whatever the unit
+        let zero_weeks =
+            r#""Witchy" <http://witchycomic.com/feed> @ every 0 weeks from 2024-01-06"#;
+        assert_eq!(
+            parse_config(zero_weeks),
+            Err(ParseError::expected(
+                "a number of weeks above 0",
+                1,
+                (47, 47)
+            ))
+        );
+        let too_long =
+            r#""Witchy" <http://witchycomic.com/feed> @ every 36526 days from 2024-01-06"#;
+        assert_eq!(
+            parse_config(too_long),
+            Err(ParseError::expected(
+                "a number of days no bigger than 36525",
+                1,
+                (47, 51)
+            ))
+        );
+        let overflow = r#""Witchy" <http://witchycomic.com/feed> @ every 3000000000000000000 weeks from 2024-01-06"#;
+        assert_eq!(
+            parse_config(overflow),
+            Err(ParseError::expected(
+                "a number of weeks no bigger than 5217",
+                1,
+                (47, 65)
+            ))
+        );
+        let longest =
+            r#""Witchy" <http://witchycomic.com/feed> @ every 5217 weeks from 2024-01-06"#;
+        assert_eq!(
+            parse_config(longest).unwrap()[0].update_policies,
+            HashSet::from_iter(vec![UpdateSpec::EveryFrom(
+                36_519,
+                NaiveDate::from_ymd(2024, 1, 6)
+            )])
+        );
+    }
+
     #[test]
     fn test_patterns() {
         let pattern_text = "
@@ -553,6 +1983,7 @@ read 2017-07-18T23:41:58.130248+00:00
                 ]),
                 root: None,
                 command: None,
+                source: None,
             }])
         );
     }
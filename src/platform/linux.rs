@@ -1,26 +1,83 @@
+use std::env;
 use std::ffi::OsStr;
-use std::process::Command;
+use std::process::{Child, Command};
 
 use crate::error::Error;
 
-pub fn open_url<T: AsRef<OsStr>>(url: T) -> Result<(), Error> {
-    let mut last_err = Err(Error::Msg("Unknown error".into()));
-    for program in &["xdg-open", "gnome-open", "kde-open"] {
-        match Command::new(program).arg(&url).spawn() {
-            Ok(mut child) => {
-                let exit_status = child.wait()?;
-                if exit_status.success() {
-                    return Ok(());
-                } else {
-                    let msg = format!("Failed opening url {}", url.as_ref().to_string_lossy());
-                    return Err(Error::Msg(msg));
-                }
-            }
+const DEFAULT_OPENERS: &[&str] = &["xdg-open", "gnome-open", "kde-open"];
+
+/// The programs to try opening URLs with, in order. A colon-separated list in
+/// `FEEDBURST_OPENERS` replaces the defaults.
+fn openers(var: Option<&str>) -> Vec<String> {
+    let custom: Vec<_> = var
+        .unwrap_or_default()
+        .split(':')
+        .map(str::trim)
+        .filter(|program| !program.is_empty())
+        .map(String::from)
+        .collect();
+    if custom.is_empty() {
+        DEFAULT_OPENERS
+            .iter()
+            .map(|&program| program.into())
+            .collect()
+    } else {
+        custom
+    }
+}
+
+/// Open `url` with `$BROWSER` if it's set, or else the first of the openers that runs
+pub fn spawn_url<T: AsRef<OsStr>>(url: T) -> Result<Child, Error> {
+    match super::unix::spawn_browser(&url) {
+        Some(child) => Ok(child),
+        None => spawn_opener(url),
+    }
+}
+
+fn spawn_opener<T: AsRef<OsStr>>(url: T) -> Result<Child, Error> {
+    let mut last_err = Error::Msg("Unknown error".into());
+    let var = env::var("FEEDBURST_OPENERS").ok();
+    for program in openers(var.as_ref().map(|x| &x[..])) {
+        match Command::new(&program).arg(&url).spawn() {
+            Ok(child) => return Ok(child),
             Err(err) => {
                 let msg = format!("Unable to open {}: {:?}", program, err);
-                last_err = Err(Error::Msg(msg));
+                last_err = Error::Msg(msg);
             }
         }
     }
-    last_err
+    Err(last_err)
+}
+
+/// Like `spawn_url`, but falls back to the openers if `$BROWSER` fails too
+pub fn open_url<T: AsRef<OsStr>>(url: T) -> Result<(), Error> {
+    if let Some(mut child) = super::unix::spawn_browser(&url) {
+        if child.wait()?.success() {
+            return Ok(());
+        }
+        debug!("$BROWSER failed, trying the usual openers");
+    }
+    let exit_status = spawn_opener(&url)?.wait()?;
+    if exit_status.success() {
+        Ok(())
+    } else {
+        let msg = format!("Failed opening url {}", url.as_ref().to_string_lossy());
+        Err(Error::Msg(msg))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_openers() {
+        assert_eq!(openers(None), vec!["xdg-open", "gnome-open", "kde-open"]);
+        assert_eq!(openers(Some("")), openers(None));
+        assert_eq!(openers(Some("wslview")), vec!["wslview"]);
+        assert_eq!(
+            openers(Some("handlr: mimeopen::")),
+            vec!["handlr", "mimeopen"],
+        );
+    }
 }
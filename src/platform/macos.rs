@@ -1,9 +1,24 @@
 use std::ffi::OsStr;
-use std::process::Command;
+use std::process::{Child, Command};
 
 use crate::error::Error;
 
+/// Open `url` with `$BROWSER` if it's set, or else with `open`
+pub fn spawn_url<T: AsRef<OsStr>>(url: T) -> Result<Child, Error> {
+    match super::unix::spawn_browser(&url) {
+        Some(child) => Ok(child),
+        None => Ok(Command::new("open").arg(&url).spawn()?),
+    }
+}
+
+/// Like `spawn_url`, but falls back to `open` if `$BROWSER` fails too
 pub fn open_url<T: AsRef<OsStr>>(url: T) -> Result<(), Error> {
+    if let Some(mut child) = super::unix::spawn_browser(&url) {
+        if child.wait()?.success() {
+            return Ok(());
+        }
+        debug!("$BROWSER failed, trying open");
+    }
     let exit_status = Command::new("open").arg(&url).spawn()?.wait()?;
     if exit_status.success() {
         Ok(())
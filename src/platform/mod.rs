@@ -1,20 +1,87 @@
-#[cfg(target_os = "linux")]
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::error::Error;
+
+/// Also for the BSDs, which open URLs with `xdg-open` and friends too
+#[cfg(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd"
+))]
 mod linux;
-#[cfg(target_os = "linux")]
-pub use self::linux::open_url;
+#[cfg(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd"
+))]
+pub use self::linux::{open_url, spawn_url};
 
 #[cfg(target_os = "windows")]
 mod windows;
 #[cfg(target_os = "windows")]
-pub use self::windows::{config_path, data_path, open_url};
+pub use self::windows::{
+    command, config_path, data_path, open_url, shell_command, shell_quote, spawn_url,
+};
 
 #[cfg(target_os = "macos")]
 mod macos;
 #[cfg(target_os = "macos")]
-pub use self::macos::open_url;
+pub use self::macos::{open_url, spawn_url};
 
-/// For code that's the same on macOS and Linux
+/// For code that's the same on macOS, Linux, and the BSDs
 #[cfg(unix)]
 mod unix;
 #[cfg(unix)]
-pub use self::unix::{config_path, data_path};
+pub use self::unix::{command, config_path, data_path, shell_command, shell_quote};
+
+/// Where files go when the platform has nowhere to keep them, such as in a
+/// container without a home directory. Relative to the working directory.
+const FALLBACK_DIR: &str = ".feedburst";
+
+/// The platform's directory if it found one, otherwise `FALLBACK_DIR`
+fn dir_or_fallback(dir: Option<PathBuf>, cwd: PathBuf) -> PathBuf {
+    match dir {
+        Some(dir) => dir,
+        None => {
+            let dir = cwd.join(FALLBACK_DIR);
+            debug!("No platform directory available, falling back to {:?}", dir);
+            dir
+        }
+    }
+}
+
+/// `path` under the fallback directory, creating its parent directories
+#[cfg(unix)]
+fn fallback_path(path: &str) -> Result<PathBuf, Error> {
+    let path = dir_or_fallback(None, env::current_dir()?).join(path);
+    fs::create_dir_all(path.parent().unwrap()).map_err(|err| {
+        Error::Msg(format!(
+            "Error creating directory for {}: {}",
+            path.display(),
+            err
+        ))
+    })?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fallback_dir() {
+        let cwd = PathBuf::from("/srv/comics");
+        assert_eq!(
+            dir_or_fallback(None, cwd.clone()),
+            PathBuf::from("/srv/comics/.feedburst"),
+        );
+        assert_eq!(
+            dir_or_fallback(Some("/home/me/.local/share/feedburst".into()), cwd),
+            PathBuf::from("/home/me/.local/share/feedburst"),
+        );
+    }
+}
@@ -1,34 +1,115 @@
 use std::env;
+use std::ffi::OsStr;
 use std::path::PathBuf;
+use std::process::{Child, Command};
 
 use crate::error::Error;
+use crate::parser::parse_command;
+
+/// The XDG directories, unless there's no home directory to base them on
+fn xdg_dirs() -> Option<::xdg::BaseDirectories> {
+    ::xdg::BaseDirectories::with_prefix(crate::APP_NAME).ok()
+}
 
 pub fn data_path(path: &str) -> Result<PathBuf, Error> {
     if let Some(path) = env::var_os("XDG_DATA_HOME") {
         Ok(path.into())
-    } else {
-        let xdg = ::xdg::BaseDirectories::with_prefix(crate::APP_NAME)
-            .map_err(|err| Error::Msg(format!("{}", err)))?;
+    } else if let Some(xdg) = xdg_dirs() {
         if let Some(path) = xdg.find_data_file(path) {
             Ok(path)
         } else {
             xdg.place_data_file(path)
                 .map_err(|err| Error::Msg(format!("{}", err)))
         }
+    } else {
+        super::fallback_path(path)
     }
 }
 
 pub fn config_path() -> Result<PathBuf, Error> {
     if let Some(path) = env::var_os("XDG_CONFIG_HOME") {
         Ok(path.into())
-    } else {
-        let xdg = ::xdg::BaseDirectories::with_prefix(crate::APP_NAME)
-            .map_err(|err| Error::Msg(format!("{}", err)))?;
+    } else if let Some(xdg) = xdg_dirs() {
         if let Some(path) = xdg.find_config_file("config.feeds") {
             Ok(path)
         } else {
             xdg.place_config_file("config.feeds")
                 .map_err(|err| Error::Msg(format!("{}", err)))
         }
+    } else {
+        super::fallback_path("config.feeds")
+    }
+}
+
+/// The commands to open `url` with from `$BROWSER`, a colon-separated list.
+/// `%s` in a command is replaced by the URL, otherwise it goes on the end.
+fn browser_commands(var: Option<&str>, url: &str) -> Vec<Vec<String>> {
+    var.unwrap_or_default()
+        .split(':')
+        .filter_map(|command| match parse_command(command) {
+            Ok(ref parts) if parts.is_empty() => None,
+            Ok(mut parts) => {
+                if parts.iter().any(|part| part.contains("%s")) {
+                    for part in &mut parts {
+                        *part = part.replace("%s", url);
+                    }
+                } else {
+                    parts.push(url.into());
+                }
+                Some(parts)
+            }
+            Err(_) => {
+                debug!("Skipping unreadable $BROWSER command {:?}", command);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Start the first command in `$BROWSER` that runs, or `None` if it's unset or none of them do
+pub fn spawn_browser<T: AsRef<OsStr>>(url: T) -> Option<Child> {
+    let var = env::var("BROWSER").ok();
+    let url = url.as_ref().to_string_lossy();
+    for command in browser_commands(var.as_ref().map(|x| &x[..]), &url) {
+        match Command::new(&command[0]).args(&command[1..]).spawn() {
+            Ok(child) => return Some(child),
+            Err(err) => debug!("Unable to open $BROWSER command {}: {:?}", command[0], err),
+        }
+    }
+    None
+}
+
+pub fn shell_command(script: &str) -> Vec<String> {
+    vec!["sh".into(), "-c".into(), script.into()]
+}
+
+pub fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+/// The `Command` for `parts`, a program followed by its arguments
+pub fn command(parts: &[String]) -> Command {
+    let mut command = Command::new(&parts[0]);
+    command.args(&parts[1..]);
+    command
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_browser_commands() {
+        let url = "http://example.com/1";
+        assert!(browser_commands(None, url).is_empty());
+        assert!(browser_commands(Some(" : "), url).is_empty());
+        assert_eq!(
+            browser_commands(Some("firefox --new-tab %s:lynx"), url),
+            vec![vec!["firefox", "--new-tab", url], vec!["lynx", url],],
+        );
+        assert_eq!(
+            browser_commands(Some("'my browser' --url=%s"), url),
+            vec![vec!["my browser", "--url=http://example.com/1"]],
+        );
     }
 }
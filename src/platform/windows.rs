@@ -1,16 +1,14 @@
 use std::ffi::OsStr;
+use std::os::windows::process::CommandExt;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Child, Command};
 use std::{env, fs};
 
 use crate::error::Error;
 
 fn app_data_dir() -> Result<PathBuf, Error> {
-    if let Some(app_data_dir) = env::var_os("APPDATA") {
-        Ok(Path::new(&app_data_dir).join("Feedburst"))
-    } else {
-        Err(Error::Msg("Unable to find the APPDATA directory".into()))
-    }
+    let app_data_dir = env::var_os("APPDATA").map(|dir| Path::new(&dir).join("Feedburst"));
+    Ok(super::dir_or_fallback(app_data_dir, env::current_dir()?))
 }
 
 pub fn data_path(path: &str) -> Result<PathBuf, Error> {
@@ -35,7 +33,7 @@ pub fn config_path() -> Result<PathBuf, Error> {
     Ok(path.join("config.feeds"))
 }
 
-pub fn open_url<T: AsRef<OsStr>>(url: T) -> Result<(), Error> {
+pub fn spawn_url<T: AsRef<OsStr>>(url: T) -> Result<Child, Error> {
     let mut cmd = Command::new("cmd");
     cmd.arg("/C").arg("start");
     if let Some(s) = url.as_ref().to_str() {
@@ -43,7 +41,11 @@ pub fn open_url<T: AsRef<OsStr>>(url: T) -> Result<(), Error> {
     } else {
         cmd.arg(url.as_ref());
     }
-    let exit_status = cmd.spawn()?.wait()?;
+    Ok(cmd.spawn()?)
+}
+
+pub fn open_url<T: AsRef<OsStr>>(url: T) -> Result<(), Error> {
+    let exit_status = spawn_url(&url)?.wait()?;
     if exit_status.success() {
         Ok(())
     } else {
@@ -51,3 +53,56 @@ pub fn open_url<T: AsRef<OsStr>>(url: T) -> Result<(), Error> {
         Err(Error::Msg(msg))
     }
 }
+
+/// `/S` makes cmd always strip the quotes `command` puts around the script
+pub fn shell_command(script: &str) -> Vec<String> {
+    vec!["cmd".into(), "/S".into(), "/C".into(), script.into()]
+}
+
+/// The `Command` for `parts`, a program followed by its arguments. A script
+/// from `shell_command` is handed to cmd as it is, since cmd doesn't undo the
+/// quoting that `Command::arg` adds.
+pub fn command(parts: &[String]) -> Command {
+    let mut command = Command::new(&parts[0]);
+    match parts {
+        [cmd, s, c, script] if cmd == "cmd" && s == "/S" && c == "/C" => {
+            command
+                .args(&parts[1..3])
+                .raw_arg(format!("\"{}\"", script));
+        }
+        _ => {
+            command.args(&parts[1..]);
+        }
+    }
+    command
+}
+
+/// cmd expands `%VAR%` even between quotes, so `%` and `^` are escaped with
+/// `^` outside of them
+pub fn shell_quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\"\""),
+            '%' | '^' => quoted.push_str(&format!("\"^{}\"", c)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("comic opened"), "\"comic opened\"");
+        assert_eq!(shell_quote("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(
+            shell_quote("http://example.com/?q=%PATH%^"),
+            "\"http://example.com/?q=\"^%\"PATH\"^%\"\"^^\"\""
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use std::io::{self, Read, Write};
+
+use chrono::{DateTime, Utc};
+
+use crate::error::Error;
+
+/// Where the time of the last completed run is kept, relative to the data dir
+pub const LAST_RUN_FILE: &str = "last-run";
+
+pub fn read_last_run<R: Read>(reader: &mut R) -> Result<Option<DateTime<Utc>>, Error> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    text.parse()
+        .map(Some)
+        .map_err(|err| Error::Msg(format!("Invalid last run time {:?}: {}", text, err)))
+}
+
+pub fn write_last_run<W: Write>(writer: &mut W, when: DateTime<Utc>) -> io::Result<()> {
+    writeln!(writer, "{}", when.to_rfc3339())
+}
+
+pub fn since_last_run_message(
+    last_run: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+    arrived: usize,
+) -> String {
+    let plural = if arrived == 1 { "comic" } else { "comics" };
+    let last_run = match last_run {
+        Some(last_run) => last_run,
+        None => return format!("{} new {} (no previous run recorded)", arrived, plural),
+    };
+
+    let elapsed = now.signed_duration_since(last_run);
+    let (count, unit) = if elapsed.num_days() > 0 {
+        (elapsed.num_days(), "day")
+    } else if elapsed.num_hours() > 0 {
+        (elapsed.num_hours(), "hour")
+    } else {
+        (elapsed.num_minutes(), "minute")
+    };
+    let unit_plural = if count == 1 { "" } else { "s" };
+    format!(
+        "{} new {} since the last run ({} {}{} ago)",
+        arrived, plural, count, unit, unit_plural,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_last_run_round_trip() {
+        let when = Utc.ymd(2017, 7, 17).and_hms_micro(3, 21, 21, 492180);
+        let mut file = Vec::new();
+        write_last_run(&mut file, when).unwrap();
+        assert_eq!(read_last_run(&mut &file[..]).unwrap(), Some(when));
+
+        assert_eq!(read_last_run(&mut "".as_bytes()).unwrap(), None);
+        assert!(read_last_run(&mut "yesterday".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_since_last_run_message() {
+        let now = Utc.ymd(2017, 7, 17).and_hms(12, 0, 0);
+        assert_eq!(
+            since_last_run_message(Some(Utc.ymd(2017, 7, 15).and_hms(9, 0, 0)), now, 3),
+            "3 new comics since the last run (2 days ago)",
+        );
+        assert_eq!(
+            since_last_run_message(Some(Utc.ymd(2017, 7, 17).and_hms(11, 0, 0)), now, 1),
+            "1 new comic since the last run (1 hour ago)",
+        );
+        assert_eq!(
+            since_last_run_message(Some(Utc.ymd(2017, 7, 17).and_hms(11, 55, 0)), now, 0),
+            "0 new comics since the last run (5 minutes ago)",
+        );
+        assert_eq!(
+            since_last_run_message(None, now, 2),
+            "2 new comics (no previous run recorded)",
+        );
+    }
+}
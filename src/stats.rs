@@ -0,0 +1,167 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use chrono::{Datelike, TimeZone, Weekday};
+
+use crate::feed::Feed;
+
+/// Reading analytics across every feed, for `--stats`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub total_comics: usize,
+    pub total_read: usize,
+    /// Comics read in each (year, month)
+    pub read_by_month: BTreeMap<(i32, u32), usize>,
+    /// The weekday the most comics were read on, and how many
+    pub busiest_day: Option<(Weekday, usize)>,
+    /// Feed names and how many comics they've had, most first
+    pub by_volume: Vec<(String, usize)>,
+}
+
+impl Stats {
+    pub fn collect<Tz: TimeZone>(feeds: &[Feed], tz: &Tz) -> Self {
+        let mut stats = Stats::default();
+        let mut by_day = [0; 7];
+        for feed in feeds {
+            let comics = feed.all_comics().len();
+            stats.total_comics += comics;
+            stats.by_volume.push((feed.info.name.clone(), comics));
+            for (when, count) in feed.read_history() {
+                let when = when.with_timezone(tz);
+                stats.total_read += count;
+                *stats
+                    .read_by_month
+                    .entry((when.year(), when.month()))
+                    .or_insert(0) += count;
+                by_day[when.weekday().num_days_from_monday() as usize] += count;
+            }
+        }
+        // Ties go to the feed that comes first alphabetically
+        stats
+            .by_volume
+            .sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        let (mut day, mut most) = (Weekday::Mon, 0);
+        for &count in &by_day {
+            if count > most {
+                most = count;
+                stats.busiest_day = Some((day, count));
+            }
+            day = day.succ();
+        }
+        stats
+    }
+
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{} across {}, {} read",
+            plural(self.total_comics, "comic"),
+            plural(self.by_volume.len(), "feed"),
+            self.total_read,
+        );
+        if !self.read_by_month.is_empty() {
+            let _ = writeln!(out, "\nRead per month:");
+            for (&(year, month), count) in &self.read_by_month {
+                let _ = writeln!(out, "  {}-{:02}  {}", year, month, count);
+            }
+        }
+        if let Some((day, count)) = self.busiest_day {
+            let _ = writeln!(
+                out,
+                "\nBusiest reading day: {:?} ({})",
+                day,
+                plural(count, "comic")
+            );
+        }
+        if !self.by_volume.is_empty() {
+            let _ = writeln!(out, "\nFeeds by volume:");
+            for (name, count) in &self.by_volume {
+                let _ = writeln!(out, "  {:>5}  {}", count, name);
+            }
+        }
+        out
+    }
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("{} {}", count, noun)
+    } else {
+        format!("{} {}s", count, noun)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::feed::FeedInfo;
+    use chrono::Utc;
+
+    fn make_feed(name: &str, events: &str) -> Feed {
+        let info = FeedInfo {
+            name: name.into(),
+            url: "http://example.com/feed".into(),
+            update_policies: Default::default(),
+            root: None,
+            command: None,
+            source: None,
+        };
+        info.read_feed(&mut events.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_aggregate_stats() {
+        let feeds = vec![
+            make_feed(
+                "Witchy",
+                "<http://example.com/w1>\n<http://example.com/w2>\n\
+                 read 2017-07-16T12:00:00+00:00\n<http://example.com/w3>\n",
+            ),
+            make_feed(
+                "Electrum",
+                "<http://example.com/e1>\n<http://example.com/e2>\n<http://example.com/e3>\n\
+                 read 2017-07-23T12:00:00+00:00 <http://example.com/e1>\n\
+                 read 2017-08-02T12:00:00+00:00\n<http://example.com/e4>\n",
+            ),
+            make_feed("Empty", ""),
+        ];
+        let stats = Stats::collect(&feeds, &Utc);
+        assert_eq!(stats.total_comics, 7);
+        assert_eq!(stats.total_read, 5);
+        assert_eq!(
+            stats.read_by_month.into_iter().collect::<Vec<_>>(),
+            vec![((2017, 7), 3), ((2017, 8), 2)],
+        );
+        // Two Sundays, against a Wednesday
+        assert_eq!(stats.busiest_day, Some((Weekday::Sun, 3)));
+        assert_eq!(
+            stats.by_volume,
+            vec![
+                ("Electrum".to_string(), 4),
+                ("Witchy".to_string(), 3),
+                ("Empty".to_string(), 0),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let feeds = vec![make_feed(
+            "Witchy",
+            "<http://example.com/w1>\nread 2017-07-16T12:00:00+00:00\n",
+        )];
+        assert_eq!(
+            Stats::collect(&feeds, &Utc).report(),
+            "1 comic across 1 feed, 1 read\n\
+             \nRead per month:\n  2017-07  1\n\
+             \nBusiest reading day: Sun (1 comic)\n\
+             \nFeeds by volume:\n      1  Witchy\n",
+        );
+        assert_eq!(
+            Stats::collect(&[], &Utc).report(),
+            "0 comics across 0 feeds, 0 read\n"
+        );
+    }
+}